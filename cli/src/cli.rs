@@ -1,14 +1,18 @@
 use clap::{Parser, ValueEnum};
-use totebag::format::default_format_detector;
+use totebag::format::{default_format_detector, Format, FormatDetector};
 use std::{io::BufRead, path::PathBuf};
 
 use totebag::{ArchiveConfig, ExtractConfig, ListConfig};
-use totebag::{IgnoreType, OutputFormat, Result, Error};
+use totebag::{IgnoreType, OutputFormat, OverwritePolicy, Result, Error};
 
 pub(crate) enum Mode {
     Archive(ArchiveConfig),
     Extract(ExtractConfig),
     List(ListConfig),
+    /// Writes the archive of the given format into stdout.
+    ArchiveStdout(ArchiveConfig, &'static Format),
+    /// Extracts the archive of the given format read from stdin.
+    ExtractStdin(ExtractConfig, &'static Format),
 }
 
 impl Mode {
@@ -16,8 +20,8 @@ impl Mode {
     #[allow(unused)]
     pub(crate) fn mode(&self) -> String {
         match self {
-            Self::Archive(_) => "archive",
-            Self::Extract(_) => "extract",
+            Self::Archive(_) | Self::ArchiveStdout(..) => "archive",
+            Self::Extract(_) | Self::ExtractStdin(..) => "extract",
             Self::List(_) => "list",
         }
         .to_string()
@@ -57,9 +61,20 @@ pub(crate) struct CliOpts {
     pub mode: RunMode,
 
     #[clap(short = 'F', long, value_name = "ARCHIVE_FORMAT", value_enum, ignore_case = true,
-        help = "Specify the archive format for listing mode (default auto). available on list and extract modes.")]
+        help = "Specify the archive format for listing mode (default auto). available on list and extract modes.
+'--stdin' and '--stdout' require the concrete format (neither auto nor parse).")]
     pub from: Option<ArchiveFormat>,
 
+    #[clap(long, requires = "from", conflicts_with = "stdout",
+        help = "Read the archive from stdin and extract it (extract mode). The arguments are the entries to be extracted.
+Available for ar, cpio, deb, rpm, tar family, zip, and single file compression (gz, bz2, xz, zst, and lz4) formats.")]
+    pub stdin: bool,
+
+    #[clap(long, requires = "from",
+        help = "Write the archive into stdout instead of the file (archive mode).
+Available for ar, cpio, deb, tar family, zip, and single file compression (gz, bz2, xz, zst, and lz4) formats.")]
+    pub stdout: bool,
+
     #[cfg(debug_assertions)]
     #[clap(
         long = "generate-completion",
@@ -90,7 +105,10 @@ In archive mode, the resultant archive file name is determined by the following
     - if output option is specified, use it.
     - if the first argument is the archive file name, use it.
     - otherwise, use the default name 'totebag.zip'.
-The format is determined by the extension of the resultant file name."###
+The format is determined by the extension of the resultant file name.
+In extract mode, the arguments following the archive files are the names or the globs
+of the entries to be extracted. They imply extract mode if the first archive file exists
+and none of them exist as the files."###
     )]
     pub args: Vec<String>,
 }
@@ -126,6 +144,27 @@ pub struct ArchiverOpts {
     )]
     pub ignores: Vec<IgnoreType>,
 
+    #[clap(
+        long = "include",
+        value_name = "GLOB",
+        help = "Archive only the files matching the given glob (archive mode). This option can be specified multiple times."
+    )]
+    pub includes: Vec<String>,
+
+    #[clap(
+        long = "exclude",
+        value_name = "GLOB",
+        help = "Skip the files and directories matching the given glob (archive and extract modes). This option can be specified multiple times."
+    )]
+    pub excludes: Vec<String>,
+
+    #[clap(
+        long = "exclude-from",
+        value_name = "FILE",
+        help = "Read the exclude globs from the given file, one per line (archive and extract modes)."
+    )]
+    pub exclude_from: Option<PathBuf>,
+
     #[clap(short = 'L', long = "level", default_value_t = 5, help = r#"Specify the compression level. [default: 5] [possible values: 0-9 (none to finest)]
 For more details of level of each compression method, see README."#, value_parser=compression_level)]
     pub level: u8,
@@ -137,6 +176,31 @@ For more details of level of each compression method, see README."#, value_parse
         default_value_t = false
     )]
     pub no_recursive: bool,
+
+    #[clap(
+        long = "max-depth",
+        value_name = "DEPTH",
+        help = "Descend at most DEPTH levels below the given directories (archive mode)."
+    )]
+    pub max_depth: Option<usize>,
+
+    #[clap(
+        long = "follow-links",
+        help = "Archive the files referred by the symbolic and hard links instead of the links themselves (archive mode).",
+        default_value_t = false
+    )]
+    pub follow_links: bool,
+}
+
+impl ArchiverOpts {
+    /// Returns the exclude globs given by `--exclude` and `--exclude-from`.
+    fn excludes(&self) -> Result<Vec<String>> {
+        let mut excludes = self.excludes.clone();
+        if let Some(file) = &self.exclude_from {
+            excludes.extend(reads_from_file(file.to_string_lossy())?);
+        }
+        Ok(excludes)
+    }
 }
 
 #[derive(Parser, Debug)]
@@ -147,6 +211,46 @@ pub struct ExtractorOpts {
         default_value_t = false
     )]
     pub to_archive_name_dir: bool,
+
+    #[clap(
+        long = "auto-archive-name-dir",
+        help = "extract files to DEST/ARCHIVE_NAME directory only if the archive has multiple top-level entries (extract mode).",
+        default_value_t = false,
+        conflicts_with = "to_archive_name_dir"
+    )]
+    pub auto_archive_name_dir: bool,
+
+    #[clap(
+        long = "strip-components",
+        value_name = "N",
+        help = "Remove the leading N components from the entry names (extract mode).",
+        default_value_t = 0
+    )]
+    pub strip_components: usize,
+
+    #[clap(
+        long = "transform",
+        value_name = "EXPRESSION",
+        help = "Rewrite the entry names by the sed-style expression, e.g., 's/^src/lib/' (extract mode). This option can be specified multiple times.",
+        value_parser = transform
+    )]
+    pub transforms: Vec<totebag::extractor::Transform>,
+
+    #[clap(
+        long = "overwrite-policy",
+        value_name = "POLICY",
+        help = "Specify how to handle the extracted files which already exist (extract mode).",
+        value_enum,
+        default_value_t = OverwritePolicy::Always
+    )]
+    pub overwrite_policy: OverwritePolicy,
+
+    #[clap(
+        long = "package-control",
+        help = "Extract the control files of deb packages into DEBIAN directory, too (extract mode).",
+        default_value_t = false
+    )]
+    pub package_control: bool,
 }
 
 #[derive(Parser, Debug, ValueEnum, Clone, PartialEq, Copy)]
@@ -155,8 +259,9 @@ pub enum ArchiveFormat {
     Auto,
     /// Detect the format by the file signature (header bytes).
     Parse,
-    Cab, Lha, Lzh, SevenZ, Rar, Tar, TarGz, TarBz2, TarXz, TarZstd, Zip,
-    Tgz, Tbz2, Txz, Tzst, Tzstd, Jar, War, Ear,
+    Ar, Cab, Cpio, Lha, Lzh, SevenZ, Rar, Tar, TarGz, TarBz2, TarXz, TarZstd, TarLz4, TarLzma, TarLz, TarZ, TarBr, Deb, Rpm, Iso, SquashFs, Zip,
+    Tgz, Tbz2, Txz, Tzst, Tzstd, Tlz4, Tlz, Taz, Tbr, Jar, War, Ear, Sqfs, Snap,
+    Gz, Bz2, Xz, Zstd, Zst, Lz4,
 }
 
 /// The log level.
@@ -178,6 +283,10 @@ fn compression_level(s: &str) -> core::result::Result<u8, String> {
     clap_num::number_range(s, 0, 9)
 }
 
+fn transform(s: &str) -> core::result::Result<totebag::extractor::Transform, String> {
+    totebag::extractor::Transform::new(s).map_err(|e| e.to_string())
+}
+
 #[derive(Parser, Debug)]
 struct ActualArgs {
     args: Vec<String>,
@@ -187,14 +296,20 @@ impl ActualArgs {}
 
 impl CliOpts {
     pub(crate) fn find_mode(&self) -> Result<(Mode, Vec<String>)> {
+        if self.stdin {
+            // stdin is the archive, therefore, the arguments are not read from stdin.
+            return to_extract_stdin_config(self, self.args.clone());
+        }
         let args = normalize_args(self.args.clone())?;
         if args.is_empty() {
             Err(Error::NoArgumentsGiven)
+        } else if self.stdout {
+            to_archive_stdout_config(self, args)
         } else {
             match self.mode {
                 RunMode::Auto => {
-                    let fd = default_format_detector();
-                    if totebag::format::is_all_archive_file(&args, fd.as_ref()) {
+                    let fd = self.format_detector()?;
+                    if is_extract_args(&args, fd.as_ref()) {
                         to_extract_config(self, args)
                     } else {
                         to_archive_config(self, args)
@@ -208,23 +323,39 @@ impl CliOpts {
     }
 
     fn format_detector(&self) -> Result<Box<dyn totebag::format::FormatDetector>> {
-        use totebag::format::{default_format_detector, fixed_format_detector, magic_number_format_detector};
+        use totebag::format::{fixed_format_detector, magic_number_format_detector};
         match self.from {
-            Some(ArchiveFormat::Auto) | None => {
-                Ok(default_format_detector())
-            }
             Some(ArchiveFormat::Parse) => Ok(magic_number_format_detector()),
+            _ => match self.fixed_format()? {
+                Some(format) => Ok(fixed_format_detector(format)),
+                None => Ok(default_format_detector()),
+            },
+        }
+    }
+
+    /// Returns the format specified by `--from`, or `None` for `auto`, `parse`, and no specification.
+    /// The aliases (e.g., `tgz`, and `jar`) are resolved by the extensions.
+    fn fixed_format(&self) -> Result<Option<&'static Format>> {
+        match self.from {
+            Some(ArchiveFormat::Auto) | Some(ArchiveFormat::Parse) | None => Ok(None),
             Some(f) => {
                 let name = format!("{f:?}");
-                let format = totebag::format::find_format_by_name(name).ok_or_else(|| {
-                    Error::UnsupportedFormat(format!(
+                totebag::format::find_format_by_name(&name)
+                    .or_else(|| totebag::format::find_format_by_ext(&name))
+                    .map(Some)
+                    .ok_or_else(|| Error::UnsupportedFormat(format!(
                         "The specified archive format '{f:?}' is not supported."
-                    ))
-                })?;
-                Ok(fixed_format_detector(format))
+                    )))
             }
         }
     }
+
+    /// Returns the format for `--stdin` and `--stdout`, which cannot be detected from the file name.
+    fn stream_format(&self) -> Result<&'static Format> {
+        self.fixed_format()?.ok_or_else(|| Error::UnsupportedFormat(
+            "'--stdin' and '--stdout' require the concrete archive format by '--from'".to_string()
+        ))
+    }
 }
 
 
@@ -235,26 +366,115 @@ fn to_archive_config(opts: &CliOpts, args: Vec<String>) -> Result<(Mode, Vec<Str
     } else {
         (None, args)
     };
+    let config = build_archive_config(opts, dest.unwrap_or_else(|| PathBuf::from("totebag.zip")))?;
+    Ok((Mode::Archive(config), args))
+}
+
+fn build_archive_config(opts: &CliOpts, dest: PathBuf) -> Result<ArchiveConfig> {
     let config = totebag::ArchiveConfig::builder()
-        .dest(dest.unwrap_or_else(|| PathBuf::from("totebag.zip")))
+        .dest(dest)
         .level(opts.archivers.level)
         .rebase_dir(opts.archivers.base_dir.clone())
         .overwrite(opts.overwrite)
         .no_recursive(opts.archivers.no_recursive)
+        .max_depth_opt(opts.archivers.max_depth)
         .ignore(opts.archivers.ignores.clone())
+        .includes(opts.archivers.includes.clone())
+        .excludes(opts.archivers.excludes()?)
+        .follow_links(opts.archivers.follow_links)
         .build();
-    Ok((Mode::Archive(config), args))
+    Ok(config)
+}
+
+fn to_archive_stdout_config(opts: &CliOpts, args: Vec<String>) -> Result<(Mode, Vec<String>)> {
+    let format = opts.stream_format()?;
+    // all of the arguments are the targets, since the destination is stdout.
+    let config = build_archive_config(opts, PathBuf::from("-"))?;
+    Ok((Mode::ArchiveStdout(config, format), args))
+}
+
+fn to_extract_stdin_config(opts: &CliOpts, patterns: Vec<String>) -> Result<(Mode, Vec<String>)> {
+    let format = opts.stream_format()?;
+    let config = build_extract_config(opts, patterns)?;
+    Ok((Mode::ExtractStdin(config, format), vec![]))
 }
 
 fn to_extract_config(opts: &CliOpts, args: Vec<String>) -> Result<(Mode, Vec<String>)> {
+    let (args, patterns) = split_patterns(args, opts.format_detector()?.as_ref());
+    let config = build_extract_config(opts, patterns)?;
+    Ok((Mode::Extract(config), args))
+}
+
+fn build_extract_config(opts: &CliOpts, patterns: Vec<String>) -> Result<ExtractConfig> {
     let dest = opts.output.clone().unwrap_or_else(|| PathBuf::from("."));
+    let from_tty = opts.stdin;
+    let (includes, entries): (Vec<_>, Vec<_>) = patterns.into_iter()
+        .partition(|p| p.contains(['*', '?', '[', '{']));
     let config = totebag::ExtractConfig::builder()
         .overwrite(opts.overwrite)
         .use_archive_name_dir(opts.extractors.to_archive_name_dir)
+        .auto_archive_name_dir(opts.extractors.auto_archive_name_dir)
+        .overwrite_policy(opts.extractors.overwrite_policy)
+        .overwrite_prompt(Box::new(move |path| ask_overwrite(path, from_tty)))
+        .strip_components(opts.extractors.strip_components)
+        .transforms(opts.extractors.transforms.clone())
+        .package_control(opts.extractors.package_control)
         .dest(dest)
+        .entries(entries)
+        .includes(includes)
+        .excludes(opts.archivers.excludes()?)
         .format_detector(opts.format_detector()?)
         .build();
-    Ok((Mode::Extract(config), args))
+    Ok(config)
+}
+
+/// Asks the user whether to overwrite the existing file for [`OverwritePolicy::Ask`].
+/// The answer is read from the terminal if `from_tty` is `true`, since stdin is the archive with `--stdin`.
+fn ask_overwrite(path: &std::path::Path, from_tty: bool) -> bool {
+    eprint!("{}: overwrite? [y/N] ", path.display());
+    let mut answer = String::new();
+    let r = if from_tty {
+        std::fs::File::open(TTY)
+            .and_then(|tty| std::io::BufReader::new(tty).read_line(&mut answer))
+    } else {
+        std::io::stdin().read_line(&mut answer)
+    };
+    r.is_ok() && matches!(answer.trim(), "y" | "Y" | "yes")
+}
+
+#[cfg(unix)]
+const TTY: &str = "/dev/tty";
+#[cfg(windows)]
+const TTY: &str = "CONIN$";
+
+/// Returns `true` if the arguments are the archive files optionally followed by the patterns of the entries.
+/// The trailing arguments are the patterns only if the first archive file exists and none of them exist as the files,
+/// since the targets in archive mode must exist.
+fn is_extract_args(args: &[String], fd: &dyn FormatDetector) -> bool {
+    let (archives, patterns) = split_patterns(args.to_vec(), fd);
+    totebag::format::is_all_archive_file(&archives, fd)
+        && (patterns.is_empty()
+            || (PathBuf::from(&archives[0]).exists() && patterns.iter().all(|p| !PathBuf::from(p).exists())))
+}
+
+/// Splits the arguments in extract mode into the archive files and the trailing patterns of the entries.
+/// The first argument is always an archive file.
+/// The following arguments are the archive files while `fd` detects their formats, and they exist
+/// or have the archive extensions (the fixed format detector detects any names).
+fn split_patterns(args: Vec<String>, fd: &dyn FormatDetector) -> (Vec<String>, Vec<String>) {
+    let by_ext = default_format_detector();
+    let is_archive = |arg: &String| {
+        let path = PathBuf::from(arg);
+        fd.detect(&path).is_some() && (path.exists() || by_ext.detect(&path).is_some())
+    };
+    let index = args.iter()
+        .skip(1)
+        .position(|arg| !is_archive(arg))
+        .map(|i| i + 1)
+        .unwrap_or(args.len());
+    let mut archives = args;
+    let patterns = archives.split_off(index);
+    (archives, patterns)
 }
 
 fn to_list_config(opts: &CliOpts, args: Vec<String>) -> Result<(Mode, Vec<String>)> {
@@ -327,14 +547,14 @@ mod tests {
 
     #[test]
     fn test_read_from_file1() {
-        let cli = CliOpts::parse_from(&["totebag_test", "@../testdata/files/archive_mode1.txt"]);
+        let cli = CliOpts::parse_from(["totebag_test", "@../testdata/files/archive_mode1.txt"]);
         let (mode, args) = cli.find_mode().unwrap();
         match mode {
-            Mode::List(_) | Mode::Extract(_) => panic!("invalid mode"),
             Mode::Archive(config) => assert_eq!(
                 config.dest_file().unwrap(),
                 PathBuf::from("testdata/targets.tar.gz")
             ),
+            _ => panic!("invalid mode"),
         }
         assert_eq!(
             args,
@@ -344,13 +564,13 @@ mod tests {
 
     #[test]
     fn test_read_from_file2() {
-        let cli = CliOpts::parse_from(&["totebag_test", "@../testdata/files/archive_mode2.txt"]);
+        let cli = CliOpts::parse_from(["totebag_test", "@../testdata/files/archive_mode2.txt"]);
         let (mode, args) = cli.find_mode().unwrap();
         match mode {
-            Mode::List(_) | Mode::Extract(_) => panic!("invalid mode"),
             Mode::Archive(config) => {
                 assert_eq!(config.dest_file().unwrap(), PathBuf::from("totebag.zip"))
             }
+            _ => panic!("invalid mode"),
         }
         assert_eq!(
             args,
@@ -360,11 +580,11 @@ mod tests {
 
     #[test]
     fn test_read_from_file3() {
-        let cli = CliOpts::parse_from(&["totebag_test", "@../testdata/files/extract_mode.txt"]);
+        let cli = CliOpts::parse_from(["totebag_test", "@../testdata/files/extract_mode.txt"]);
         let (mode, args) = cli.find_mode().unwrap();
         match mode {
-            Mode::List(_) | Mode::Archive(_) => panic!("invalid mode"),
             Mode::Extract(config) => assert_eq!(config.dest, PathBuf::from(".")),
+            _ => panic!("invalid mode"),
         }
         assert_eq!(args, vec!["testdata/test.cab", "testdata/test.tar"]);
     }
@@ -372,7 +592,7 @@ mod tests {
     #[test]
     fn test_find_mode_1() {
         let cli1 =
-            CliOpts::parse_from(&["totebag_test", "src", "LICENSE", "README.md", "Cargo.toml"]);
+            CliOpts::parse_from(["totebag_test", "src", "LICENSE", "README.md", "Cargo.toml"]);
         let (mode, args) = cli1.find_mode().unwrap();
         assert_eq!(mode.mode(), "archive");
         assert_eq!(args, vec!["src", "LICENSE", "README.md", "Cargo.toml"]);
@@ -381,7 +601,7 @@ mod tests {
     #[test]
     fn test_find_mode_2() {
         let cli2 =
-            CliOpts::parse_from(&["totebag_test", "src", "LICENSE", "README.md", "hoge.zip"]);
+            CliOpts::parse_from(["totebag_test", "src", "LICENSE", "README.md", "hoge.zip"]);
         let (mode, args) = cli2.find_mode().unwrap();
         assert_eq!(mode.mode(), "archive");
         assert_eq!(args, vec!["src", "LICENSE", "README.md", "hoge.zip"]);
@@ -389,7 +609,7 @@ mod tests {
 
     #[test]
     fn test_find_mode_3() {
-        let cli3 = CliOpts::parse_from(&[
+        let cli3 = CliOpts::parse_from([
             "totebag_test",
             "src.zip",
             "LICENSE.tar",
@@ -406,7 +626,7 @@ mod tests {
 
     #[test]
     fn test_find_mode_4() {
-        let cli4 = CliOpts::parse_from(&[
+        let cli4 = CliOpts::parse_from([
             "totebag_test",
             "src.zip",
             "LICENSE.tar",
@@ -425,7 +645,124 @@ mod tests {
 
     #[test]
     fn test_cli_parse_error() {
-        let r = CliOpts::try_parse_from(&["totebag_test"]);
+        let r = CliOpts::try_parse_from(["totebag_test"]);
         assert!(r.is_err());
     }
+
+    #[test]
+    fn test_archive_filters() {
+        let cli = CliOpts::parse_from([
+            "totebag_test", "-o", "results/test.zip", "--include", "*.rs",
+            "--exclude", "*.bak", "--exclude-from", "../testdata/files/excludes.txt", "src",
+        ]);
+        let (mode, _) = cli.find_mode().unwrap();
+        match mode {
+            Mode::Archive(config) => {
+                assert_eq!(config.includes, vec!["*.rs"]);
+                assert_eq!(config.excludes, vec!["*.bak", "target/**", "*.o"]);
+            }
+            _ => panic!("invalid mode"),
+        }
+    }
+
+    #[test]
+    fn test_extract_patterns() {
+        let cli = CliOpts::parse_from([
+            "totebag_test", "-m", "extract", "--exclude", "*.bak",
+            "../testdata/test.zip", "../testdata/test.tar", "Cargo.toml", "src/*.rs",
+        ]);
+        let (mode, args) = cli.find_mode().unwrap();
+        match mode {
+            Mode::Extract(config) => {
+                assert_eq!(config.entries, vec!["Cargo.toml"]);
+                assert_eq!(config.includes, vec!["src/*.rs"]);
+                assert_eq!(config.excludes, vec!["*.bak"]);
+            }
+            _ => panic!("invalid mode"),
+        }
+        assert_eq!(args, vec!["../testdata/test.zip", "../testdata/test.tar"]);
+
+        // the patterns imply extract mode, and the archive formats are detected by `--from`.
+        std::fs::create_dir_all("results").unwrap();
+        std::fs::copy("../testdata/test.zip", "results/test_zip_without_ext").unwrap();
+        let cli = CliOpts::parse_from([
+            "totebag_test", "--from", "parse", "results/test_zip_without_ext", "../testdata/test.zip", "hoge.txt", "src/*.rs",
+        ]);
+        let (mode, args) = cli.find_mode().unwrap();
+        match mode {
+            Mode::Extract(config) => {
+                assert_eq!(config.entries, vec!["hoge.txt"]);
+                assert_eq!(config.includes, vec!["src/*.rs"]);
+            }
+            _ => panic!("invalid mode"),
+        }
+        assert_eq!(args, vec!["results/test_zip_without_ext", "../testdata/test.zip"]);
+        std::fs::remove_file("results/test_zip_without_ext").unwrap();
+    }
+
+    #[test]
+    fn test_extract_rewriting() {
+        let cli = CliOpts::parse_from([
+            "totebag_test", "--strip-components", "1", "--transform", "s/^src/lib/",
+            "--transform", "s,\\.txt$,.md,", "../testdata/test.zip",
+        ]);
+        let (mode, _) = cli.find_mode().unwrap();
+        match mode {
+            Mode::Extract(config) => {
+                assert_eq!(config.strip_components, 1);
+                let names = config.transforms.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+                assert_eq!(names, vec!["s/^src/lib/", "s,\\.txt$,.md,"]);
+            }
+            _ => panic!("invalid mode"),
+        }
+        assert!(CliOpts::try_parse_from(["totebag_test", "--transform", "y/a/b/", "../testdata/test.zip"]).is_err());
+    }
+
+    #[test]
+    fn test_overwrite_policy() {
+        let cli = CliOpts::parse_from(["totebag_test", "--overwrite-policy", "if-newer", "../testdata/test.zip"]);
+        match cli.find_mode().unwrap().0 {
+            Mode::Extract(config) => assert_eq!(config.overwrite_policy, OverwritePolicy::IfNewer),
+            _ => panic!("invalid mode"),
+        }
+        assert!(CliOpts::try_parse_from(["totebag_test", "--overwrite-policy", "sometimes", "../testdata/test.zip"]).is_err());
+    }
+
+    #[test]
+    fn test_auto_archive_name_dir() {
+        let cli = CliOpts::parse_from(["totebag_test", "--auto-archive-name-dir", "../testdata/test.zip"]);
+        match cli.find_mode().unwrap().0 {
+            Mode::Extract(config) => assert!(config.auto_archive_name_dir),
+            _ => panic!("invalid mode"),
+        }
+        assert!(CliOpts::try_parse_from(["totebag_test", "--auto-archive-name-dir", "--to-archive-name-dir", "../testdata/test.zip"]).is_err());
+    }
+
+    #[test]
+    fn test_stdin_and_stdout() {
+        let cli = CliOpts::parse_from(["totebag_test", "--stdin", "--from", "tgz", "src", "*.toml"]);
+        match cli.find_mode().unwrap() {
+            (Mode::ExtractStdin(config, format), args) => {
+                assert_eq!(format.name, "TarGz");
+                assert_eq!(config.entries, vec!["src".to_string()]);
+                assert_eq!(config.includes, vec!["*.toml".to_string()]);
+                assert!(args.is_empty());
+            }
+            _ => panic!("invalid mode"),
+        }
+
+        let cli = CliOpts::parse_from(["totebag_test", "--stdout", "--from", "zip", "test.tar.gz", "src"]);
+        match cli.find_mode().unwrap() {
+            (Mode::ArchiveStdout(_, format), args) => {
+                assert_eq!(format.name, "Zip");
+                assert_eq!(args, vec!["test.tar.gz", "src"]);
+            }
+            _ => panic!("invalid mode"),
+        }
+
+        assert!(CliOpts::try_parse_from(["totebag_test", "--stdin", "test.zip"]).is_err());
+        assert!(CliOpts::try_parse_from(["totebag_test", "--stdin", "--stdout", "--from", "zip"]).is_err());
+        let cli = CliOpts::parse_from(["totebag_test", "--stdout", "--from", "auto", "src"]);
+        assert!(matches!(cli.find_mode(), Err(Error::UnsupportedFormat(_))));
+    }
 }
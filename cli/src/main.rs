@@ -3,6 +3,7 @@ use std::path::PathBuf;
 
 use cli::LogLevel;
 use totebag::archiver::ArchiveEntries;
+use totebag::format::Format;
 use totebag::{Result, Error};
 
 use crate::cli::Mode;
@@ -44,6 +45,8 @@ fn perform(opts: cli::CliOpts) -> Result<()> {
             Err(e) => Err(e),
         },
         Mode::Extract(config) => perform_extract(config, args),
+        Mode::ArchiveStdout(config, format) => perform_archive_stdout(config, format, args),
+        Mode::ExtractStdin(config, format) => totebag::extract_stream(std::io::stdin(), format, &config),
     }
 }
 
@@ -84,9 +87,19 @@ fn perform_archive(config: totebag::ArchiveConfig, args: Vec<String>) -> Result<
     totebag::archive(&targets, &config)
 }
 
+/// The result is not printed, since stdout is occupied by the archive.
+fn perform_archive_stdout(config: totebag::ArchiveConfig, format: &Format, args: Vec<String>) -> Result<()> {
+    let targets = args.into_iter()
+        .map(PathBuf::from)
+        .collect::<Vec<_>>();
+    let entries = totebag::archive_stream(std::io::stdout(), format, &targets, &config)?;
+    log::info!("archived {} entries ({} bytes) into stdout", entries.len(), entries.total());
+    Ok(())
+}
+
 fn main() -> Result<()> {
     if let Err(e) = perform(cli::CliOpts::parse()) {
-        println!("{e}");
+        eprintln!("{e}");
         std::process::exit(1);
     }
     Ok(())
@@ -177,7 +190,7 @@ mod tests {
 
     #[test]
     fn test_run() {
-        let opts = cli::CliOpts::parse_from(&[
+        let opts = cli::CliOpts::parse_from([
             "totebag_test",
             "-o",
             "test.zip",
@@ -195,7 +208,7 @@ mod tests {
     #[test]
     fn test_list() {
         let opts =
-            cli::CliOpts::parse_from(&["totebag_test", "--mode", "list", "../testdata/test.zip"]);
+            cli::CliOpts::parse_from(["totebag_test", "--mode", "list", "../testdata/test.zip"]);
         match perform(opts) {
             Ok(_) => (),
             Err(e) => panic!("unexpected error: {:?}", e),
@@ -1,6 +1,8 @@
 //! This module provides an interface and struct for archiving the files.
-//! The supported formats are: `cab`, `7z`, `tar`, `tar.gz`, `tar.bz2`, `tar.xz`, `tar.zst`, and `zip`.
-//! `lha` and `rar` formats are not supported for archiving.
+//! The supported formats are: `cab`, `7z`, `tar`, `tar.gz`, `tar.bz2`, `tar.xz`, `tar.zst`, `tar.lz4`,
+//! `tar.lzma`, `tar.lz`, `tar.Z`, `tar.br`, `deb` (from the staged directory), `lha`, and `zip`,
+//! and the single file compression formats, `gz`, `bz2`, `xz`, `zst`, and `lz4`, which accept only one file.
+//! `iso`, `rar`, `rpm`, and `squashfs` formats are not supported for archiving.
 //!
 //! # Example: archiving the files
 //!
@@ -13,25 +15,32 @@
 //!     .overwrite(true)                           // set overwrite flag of the destination file.
 //!     .build();
 //! let targets = vec!["src", "Cargo.toml"].iter() // files to be archived.
-//!     .map(|s| PathBuf::from(s)).collect::<Vec<PathBuf>>();   
+//!     .map(PathBuf::from).collect::<Vec<PathBuf>>();   
 //! match totebag::archive(&targets, &config) {
 //!     Ok(_) => println!("archiving is done"),
 //!     Err(e) => eprintln!("error: {:?}", e),
 //! }
 //! ```
-use std::fs::File;
+use std::collections::HashMap;
+use std::io::{Seek, Write};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-use crate::format::default_format_detector;
+use crate::format::{default_format_detector, ArchiverFactory, Format};
 use crate::{Result, Error};
 
 mod ar;
 mod cab;
+mod compressed;
 mod cpio;
+mod deb;
+mod iso;
 mod lha;
 mod os;
 mod rar;
+mod rpm;
 mod sevenz;
+mod squashfs;
 mod tar;
 mod zip;
 
@@ -91,20 +100,37 @@ impl ArchiveEntry {
     }
 }
 
+/// The destination of the archive, which is writable and seekable, e.g., [`File`](std::fs::File) and [`Cursor`](std::io::Cursor).
+pub trait WriteSeek: Write + Seek {}
+
+impl<T: Write + Seek + ?Sized> WriteSeek for T {}
+
 /// The trait for creating an archive file.
 /// If you want to support archiving for a new format, you need to implement the `ToteArchiver` trait.
 /// Then, the call [`perform`](ToteArchiver::perform) method of [`ToteArchiver`].
 pub trait ToteArchiver {
     /// Perform the archiving operation.
-    /// - `file` is the destination file for the archive.
-    /// - `tps` is the list of files to be archived.
+    /// - `writer` is the destination of the archive, e.g., [`File`](std::fs::File) and [`Cursor`](std::io::Cursor).
+    /// - `targets` is the list of files to be archived.
     fn perform(
         &self,
-        file: File,
+        writer: Box<dyn WriteSeek + '_>,
         targets: &[PathBuf],
         config: &crate::ArchiveConfig,
     ) -> Result<Vec<ArchiveEntry>>;
 
+    /// Perform the archiving operation into the given stream (e.g., stdout).
+    /// The formats requiring the random access to the destination return [`Error::UnsupportedFormat`].
+    fn perform_stream(
+        &self,
+        writer: Box<dyn Write + '_>,
+        targets: &[PathBuf],
+        config: &crate::ArchiveConfig,
+    ) -> Result<Vec<ArchiveEntry>> {
+        let _ = (writer, targets, config);
+        Err(Error::UnsupportedFormat("archiving into the stream".to_string()))
+    }
+
     /// Returns true if this archiver is enabled.
     fn enable(&self) -> bool;
 }
@@ -114,7 +140,7 @@ pub(crate) fn collect_entries<P: AsRef<Path>>(targets: &[P], config: &crate::Arc
     for path in targets {
         for entry in config.iter(path) {
             let path = entry.into_path();
-            if path.is_file() {
+            if path.is_file() || (!config.follow_links && path.is_symlink()) {
                 r.push(path)
             }
         }
@@ -122,43 +148,85 @@ pub(crate) fn collect_entries<P: AsRef<Path>>(targets: &[P], config: &crate::Arc
     r
 }
 
-pub fn create<P: AsRef<Path>>(dest: P) -> Result<Box<dyn ToteArchiver>> {
-    use crate::archiver::*;
+/// The kind of the archiving target.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Target {
+    File,
+    Dir,
+    /// The symbolic link with its target.
+    Symlink(PathBuf),
+    /// The hard link to the given path in the archive, which was archived before.
+    Hardlink(PathBuf),
+}
 
-    let dest = dest.as_ref();
-    let fd = default_format_detector();
-    let format = fd.detect(dest);
-    match format {
-        Some(format) => {
-            let archiver: Box<dyn ToteArchiver> = match format.name.as_str() {
-                "Ar" => Box::new(ar::Archiver {}),
-                "Cab" => Box::new(cab::Archiver {}),
-                "Cpio" => Box::new(cpio::Archiver {}),
-                "Lha" => Box::new(lha::Archiver {}),
-                "Rar" => Box::new(rar::Archiver {}),
-                "SevenZ" => Box::new(sevenz::Archiver {}),
-                "Tar" => Box::new(tar::Archiver {}),
-                "TarBz2" => Box::new(tar::Bz2Archiver {}),
-                "TarGz" => Box::new(tar::GzArchiver {}),
-                "TarXz" => Box::new(tar::XzArchiver {}),
-                "TarZstd" => Box::new(tar::ZstdArchiver {}),
-                "Zip" => Box::new(zip::Archiver::new()),
-                _ => {
-                    return Err(Error::UnknownFormat(format!(
-                        "{}: unknown format",
-                        format.name
-                    )));
-                }
-            };
-            if !archiver.enable() {
-                Err(Error::UnsupportedFormat(format!(
-                    "{}: unsupported format (archiving)",
-                    format.name
-                )))
+/// Classifies the archiving targets.
+/// The links are classified only if [`ArchiveConfig::follow_links`](crate::ArchiveConfig::follow_links) is `false`,
+/// and the hard links are detected by the device and the inode numbers on unix platforms.
+pub(crate) struct Links {
+    follow_links: bool,
+    #[cfg_attr(not(unix), allow(dead_code))]
+    inodes: HashMap<(u64, u64), PathBuf>,
+}
+
+impl Links {
+    pub(crate) fn new(config: &crate::ArchiveConfig) -> Self {
+        Self {
+            follow_links: config.follow_links,
+            inodes: HashMap::new(),
+        }
+    }
+
+    /// Returns the kind of the given path, or `None` for the other types (e.g., sockets and devices).
+    pub(crate) fn classify(&mut self, path: &Path, path_in_archive: &Path) -> Result<Option<Target>> {
+        if self.follow_links {
+            return Ok(if path.is_file() {
+                Some(Target::File)
+            } else if path.is_dir() {
+                Some(Target::Dir)
             } else {
-                Ok(archiver)
+                None
+            });
+        }
+        let metadata = path.symlink_metadata().map_err(Error::IO)?;
+        if metadata.is_symlink() {
+            std::fs::read_link(path)
+                .map(|target| Some(Target::Symlink(target)))
+                .map_err(Error::IO)
+        } else if metadata.is_dir() {
+            Ok(Some(Target::Dir))
+        } else if metadata.is_file() {
+            Ok(Some(self.hardlink_of(&metadata, path_in_archive).unwrap_or(Target::File)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    #[cfg(unix)]
+    fn hardlink_of(&mut self, metadata: &std::fs::Metadata, path_in_archive: &Path) -> Option<Target> {
+        use std::os::unix::fs::MetadataExt;
+        if metadata.nlink() <= 1 {
+            return None;
+        }
+        match self.inodes.entry((metadata.dev(), metadata.ino())) {
+            std::collections::hash_map::Entry::Occupied(e) => Some(Target::Hardlink(e.get().clone())),
+            std::collections::hash_map::Entry::Vacant(e) => {
+                e.insert(path_in_archive.to_path_buf());
+                None
             }
         }
+    }
+
+    #[cfg(not(unix))]
+    fn hardlink_of(&mut self, _: &std::fs::Metadata, _: &Path) -> Option<Target> {
+        None
+    }
+}
+
+pub fn create<P: AsRef<Path>>(dest: P) -> Result<Box<dyn ToteArchiver>> {
+    let dest = dest.as_ref();
+    let fd = default_format_detector();
+    match fd.detect(dest) {
+        Some(format) => create_with(format),
         None => Err(Error::Archiver(format!(
             "{:?}: no suitable archiver",
             dest.file_name().unwrap()
@@ -166,6 +234,109 @@ pub fn create<P: AsRef<Path>>(dest: P) -> Result<Box<dyn ToteArchiver>> {
     }
 }
 
+/// Returns the archiver for the given format, including the formats registered by [`Registration`](crate::format::Registration).
+pub fn create_with(format: &Format) -> Result<Box<dyn ToteArchiver>> {
+    let archiver = match crate::format::create_archiver(format) {
+        Some(archiver) => archiver?,
+        None => return Err(Error::UnknownFormat(format!(
+            "{}: unknown format",
+            format.name
+        ))),
+    };
+    if !archiver.enable() {
+        Err(Error::UnsupportedFormat(format!(
+            "{}: unsupported format (archiving)",
+            format.name
+        )))
+    } else {
+        Ok(archiver)
+    }
+}
+
+/// Returns the factories of the archivers for the built-in formats with their names,
+/// which are registered in the same registry as [`Registration`](crate::format::Registration).
+pub(crate) fn builtin_factories() -> Vec<(&'static str, ArchiverFactory)> {
+    fn factory<F>(f: F) -> ArchiverFactory
+    where
+        F: Fn() -> Box<dyn ToteArchiver> + Send + Sync + 'static,
+    {
+        Arc::new(f)
+    }
+    vec![
+        ("Ar", factory(|| Box::new(ar::Archiver {}))),
+        ("Bz2", factory(|| Box::new(compressed::Bz2Archiver {}))),
+        ("Cab", factory(|| Box::new(cab::Archiver {}))),
+        ("Cpio", factory(|| Box::new(cpio::Archiver {}))),
+        ("Deb", factory(|| Box::new(deb::Archiver {}))),
+        ("Gz", factory(|| Box::new(compressed::GzArchiver {}))),
+        ("Iso", factory(|| Box::new(iso::Archiver {}))),
+        ("Lz4", factory(|| Box::new(compressed::Lz4Archiver {}))),
+        ("Lha", factory(|| Box::new(lha::Archiver {}))),
+        ("Rar", factory(|| Box::new(rar::Archiver {}))),
+        ("Rpm", factory(|| Box::new(rpm::Archiver {}))),
+        ("SevenZ", factory(|| Box::new(sevenz::Archiver {}))),
+        ("SquashFs", factory(|| Box::new(squashfs::Archiver {}))),
+        ("Tar", factory(|| Box::new(tar::Archiver {}))),
+        ("TarBr", factory(|| Box::new(tar::BrArchiver {}))),
+        ("TarBz2", factory(|| Box::new(tar::Bz2Archiver {}))),
+        ("TarGz", factory(|| Box::new(tar::GzArchiver {}))),
+        ("TarLz", factory(|| Box::new(tar::LzArchiver {}))),
+        ("TarLz4", factory(|| Box::new(tar::Lz4Archiver {}))),
+        ("TarLzma", factory(|| Box::new(tar::LzmaArchiver {}))),
+        ("TarXz", factory(|| Box::new(tar::XzArchiver {}))),
+        ("TarZ", factory(|| Box::new(tar::ZArchiver {}))),
+        ("TarZstd", factory(|| Box::new(tar::ZstdArchiver {}))),
+        ("Xz", factory(|| Box::new(compressed::XzArchiver {}))),
+        ("Zip", factory(|| Box::new(zip::Archiver::new()))),
+        ("Zstd", factory(|| Box::new(compressed::ZstdArchiver {}))),
+    ]
+}
+
+/// Creates the directory containing a file, a symbolic link chain, a dangling link, and a hard link.
+#[cfg(all(test, unix))]
+pub(crate) fn create_links_tree<P: AsRef<Path>>(dir: P) -> PathBuf {
+    let dir = dir.as_ref();
+    let _ = std::fs::remove_dir_all(dir);
+    std::fs::create_dir_all(dir).unwrap();
+    std::fs::write(dir.join("libfoo.so.1.2"), "libfoo").unwrap();
+    std::os::unix::fs::symlink("libfoo.so.1.2", dir.join("libfoo.so.1")).unwrap();
+    std::os::unix::fs::symlink("libfoo.so.1", dir.join("libfoo")).unwrap();
+    std::os::unix::fs::symlink("missing", dir.join("dangling")).unwrap();
+    std::fs::hard_link(dir.join("libfoo.so.1.2"), dir.join("hard")).unwrap();
+    dir.to_path_buf()
+}
+
+/// Archives the tree by [`create_links_tree`] into `archive_file`, extracts it, and checks the links.
+#[cfg(all(test, unix))]
+pub(crate) fn assert_links_roundtrip(archive_file: &str, hardlink: bool) {
+    let src = create_links_tree(format!("{archive_file}.src"));
+    let config = crate::ArchiveConfig::builder()
+        .dest(archive_file)
+        .overwrite(true)
+        .build();
+    crate::archive(std::slice::from_ref(&src), &config).unwrap();
+    let dest = PathBuf::from(format!("{archive_file}.dest"));
+    let _ = std::fs::remove_dir_all(&dest);
+    let config = crate::ExtractConfig::builder().dest(&dest).build();
+    crate::extract(archive_file, &config).unwrap();
+
+    let extracted = dest.join(&src);
+    assert_eq!(std::fs::read_link(extracted.join("libfoo")).unwrap(), PathBuf::from("libfoo.so.1"));
+    assert_eq!(std::fs::read_link(extracted.join("libfoo.so.1")).unwrap(), PathBuf::from("libfoo.so.1.2"));
+    assert_eq!(std::fs::read_link(extracted.join("dangling")).unwrap(), PathBuf::from("missing"));
+    assert_eq!(std::fs::read_to_string(extracted.join("libfoo")).unwrap(), "libfoo");
+    assert_eq!(std::fs::read_to_string(extracted.join("hard")).unwrap(), "libfoo");
+    if hardlink {
+        use std::os::unix::fs::MetadataExt;
+        let ino = |name: &str| extracted.join(name).metadata().unwrap().ino();
+        assert_eq!(ino("hard"), ino("libfoo.so.1.2"));
+    }
+    for p in [src, dest] {
+        std::fs::remove_dir_all(p).unwrap();
+    }
+    std::fs::remove_file(archive_file).unwrap();
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -180,8 +351,8 @@ mod tests {
         if let Ok(p) = config.dest_file() {
             assert_eq!(PathBuf::from("results/test.zip"), p);
         }
-        assert_eq!(true, config.overwrite);
-        assert_eq!(false, config.no_recursive);
+        assert!(config.overwrite);
+        assert!(!config.no_recursive);
         assert_eq!(1, config.ignore.len());
         assert!(config.dest_file().is_ok())
     }
@@ -226,4 +397,163 @@ mod tests {
             config.path_in_archive("testdata/sample/Cargo.toml")
         );
     }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_classify_links() {
+        let dir = create_links_tree("results/classify_links");
+        let config = crate::ArchiveConfig::builder().build();
+        let mut links = Links::new(&config);
+        let classify = |links: &mut Links, name: &str| links.classify(&dir.join(name), Path::new(name)).unwrap();
+        assert_eq!(classify(&mut links, "libfoo.so.1.2"), Some(Target::File));
+        assert_eq!(classify(&mut links, "hard"), Some(Target::Hardlink(PathBuf::from("libfoo.so.1.2"))));
+        assert_eq!(classify(&mut links, "libfoo"), Some(Target::Symlink(PathBuf::from("libfoo.so.1"))));
+
+        let config = crate::ArchiveConfig::builder().follow_links(true).build();
+        let mut links = Links::new(&config);
+        assert_eq!(classify(&mut links, "libfoo"), Some(Target::File));
+        assert_eq!(classify(&mut links, "hard"), Some(Target::File));
+        assert_eq!(classify(&mut links, "dangling"), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_walk_depth() {
+        let walk = |config: &crate::ArchiveConfig| {
+            let mut r = config.iter("../testdata/sample")
+                .map(|e| e.into_path())
+                .collect::<Vec<_>>();
+            r.sort();
+            r
+        };
+        let config = crate::ArchiveConfig::builder().build();
+        assert_eq!(walk(&config).len(), 20);
+
+        let config = crate::ArchiveConfig::builder().no_recursive(true).build();
+        assert_eq!(walk(&config), vec![PathBuf::from("../testdata/sample")]);
+
+        let config = crate::ArchiveConfig::builder().max_depth(1).build();
+        let r = walk(&config);
+        assert_eq!(r.len(), 6);
+        assert!(r.contains(&PathBuf::from("../testdata/sample/src")));
+        assert!(!r.contains(&PathBuf::from("../testdata/sample/src/cli.rs")));
+
+        let config = crate::ArchiveConfig::builder().no_recursive(true).max_depth(1).build();
+        assert_eq!(walk(&config).len(), 1);
+    }
+
+    #[test]
+    fn test_archive_no_recursive() {
+        let config = crate::ArchiveConfig::builder()
+            .dest("results/no_recursive.tar")
+            .no_recursive(true)
+            .overwrite(true)
+            .build();
+        let targets = vec![PathBuf::from("src"), PathBuf::from("Cargo.toml")];
+        crate::archive(&targets, &config).unwrap();
+        let entries = crate::entries("results/no_recursive.tar", crate::format::default_format_detector().as_ref()).unwrap();
+        let names = entries.iter().map(|e| e.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["src", "Cargo.toml"]);
+        std::fs::remove_file("results/no_recursive.tar").unwrap();
+    }
+
+    #[test]
+    fn test_archive_stream_roundtrip() {
+        let targets = vec![PathBuf::from("src")];
+        for (name, level) in [("Zip", 0), ("Zip", 5), ("Zip", 8), ("TarGz", 5), ("Cpio", 5), ("Ar", 5)] {
+            let config = crate::ArchiveConfig::builder().level(level).build();
+            let format = crate::format::find_format_by_name(name).unwrap();
+            let archive_file = PathBuf::from(format!("results/stream_{name}_{level}"));
+            std::fs::create_dir_all("results").unwrap();
+            let file = std::fs::File::create(&archive_file).unwrap();
+            let entries = crate::archive_stream(file, format, &targets, &config).unwrap();
+            assert_eq!(entries.archive_file, PathBuf::from("-"));
+
+            let dest = PathBuf::from(format!("results/stream_{name}_{level}_dest"));
+            let config = crate::ExtractConfig::builder().dest(&dest).overwrite(true).build();
+            let reader = std::fs::File::open(&archive_file).unwrap();
+            crate::extract_stream(reader, format, &config).unwrap();
+            assert_eq!(std::fs::read(dest.join("src/lib.rs")).unwrap(), std::fs::read("src/lib.rs").unwrap(), "{name} ({level})");
+            std::fs::remove_file(&archive_file).unwrap();
+            std::fs::remove_dir_all(&dest).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_archive_to_roundtrip() {
+        let targets = vec![PathBuf::from("src")];
+        for name in ["Ar", "Cab", "Cpio", "SevenZ", "Tar", "TarXz", "Zip"] {
+            let config = crate::ArchiveConfig::builder().build();
+            let format = crate::format::find_format_by_name(name).unwrap();
+            let mut buffer = std::io::Cursor::new(Vec::new());
+            let entries = crate::archive_to(&mut buffer, format, &targets, &config).unwrap();
+            assert_eq!(entries.compressed, buffer.get_ref().len() as u64, "{name}");
+
+            let dest = PathBuf::from(format!("results/archive_to_{name}"));
+            let config = crate::ExtractConfig::builder().dest(&dest).overwrite(true).build();
+            buffer.set_position(0);
+            crate::extract_from(buffer, format, &config).unwrap();
+            assert_eq!(std::fs::read(dest.join("src/lib.rs")).unwrap(), std::fs::read("src/lib.rs").unwrap(), "{name}");
+            std::fs::remove_dir_all(&dest).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_archive_stream_unsupported() {
+        let config = crate::ArchiveConfig::builder().build();
+        let format = crate::format::find_format_by_name("SevenZ").unwrap();
+        match crate::archive_stream(Vec::new(), format, &[PathBuf::from("src")], &config) {
+            Err(Error::UnsupportedFormat(_)) => {}
+            r => panic!("unexpected result: {r:?}"),
+        }
+    }
+
+    #[test]
+    fn test_walk_filters() {
+        let walk = |config: &crate::ArchiveConfig, root: &str| {
+            let mut r = config.iter(root)
+                .filter(|e| e.file_type().is_some_and(|t| t.is_file()))
+                .map(|e| e.into_path())
+                .collect::<Vec<_>>();
+            r.sort();
+            r
+        };
+        let config = crate::ArchiveConfig::builder()
+            .includes(vec!["*.rs".to_string()])
+            .build();
+        let r = walk(&config, "../testdata/sample");
+        assert_eq!(r.len(), 13);
+        assert!(r.iter().all(|p| p.extension().is_some_and(|e| e == "rs")));
+
+        let config = crate::ArchiveConfig::builder()
+            .includes(vec!["*.rs".to_string()])
+            .excludes(vec!["src/**".to_string()])
+            .build();
+        assert_eq!(walk(&config, "../testdata/sample"), vec![PathBuf::from("../testdata/sample/build.rs")]);
+
+        let config = crate::ArchiveConfig::builder()
+            .excludes(vec!["extractor".to_string(), "*.md".to_string()])
+            .build();
+        let r = walk(&config, "../testdata/sample");
+        assert_eq!(r.len(), 12);
+        assert!(!r.contains(&PathBuf::from("../testdata/sample/README.md")));
+        assert!(!r.contains(&PathBuf::from("../testdata/sample/src/extractor/tar.rs")));
+        assert!(r.contains(&PathBuf::from("../testdata/sample/src/extractor.rs")));
+
+        // the explicitly given files are not filtered.
+        assert_eq!(walk(&config, "../testdata/sample/README.md"), vec![PathBuf::from("../testdata/sample/README.md")]);
+    }
+
+    #[test]
+    fn test_invalid_filter() {
+        let config = crate::ArchiveConfig::builder()
+            .dest("results/invalid_filter.tar")
+            .excludes(vec!["src/[".to_string()])
+            .build();
+        match crate::archive(&["src"], &config) {
+            Err(crate::Error::Archiver(m)) => assert!(m.starts_with("src/[: invalid glob pattern"), "{m}"),
+            r => panic!("unexpected result: {r:?}"),
+        }
+        assert!(!PathBuf::from("results/invalid_filter.tar").exists());
+    }
 }
@@ -1,16 +1,19 @@
-use std::fs::File;
 use std::io::Write;
 use std::path::{Path, PathBuf};
 
-use crate::archiver::{ArchiveEntry, ToteArchiver};
+use crate::archiver::{ArchiveEntry, ToteArchiver, WriteSeek};
 use crate::{Result, Error};
 
 /// TAR format archiver implementation.
 pub(super) struct Archiver {}
 
 impl ToteArchiver for Archiver {
-    fn perform(&self, file: File, targets: &[PathBuf], config: &crate::ArchiveConfig) -> Result<Vec<ArchiveEntry>> {
-        let mut builder = ar::Builder::new(file);
+    fn perform(&self, writer: Box<dyn WriteSeek + '_>, targets: &[PathBuf], config: &crate::ArchiveConfig) -> Result<Vec<ArchiveEntry>> {
+        self.perform_stream(writer, targets, config)
+    }
+
+    fn perform_stream(&self, writer: Box<dyn Write + '_>, targets: &[PathBuf], config: &crate::ArchiveConfig) -> Result<Vec<ArchiveEntry>> {
+        let mut builder = ar::Builder::new(writer);
         let mut errs = vec![];
         let mut entries = vec![];
         for tp in targets {
@@ -22,10 +25,10 @@ impl ToteArchiver for Archiver {
                     if let Err(e) = process_file(&mut builder, &path, &dest_dir) {
                         errs.push(e);
                     }
-                } else if path.is_dir() {
-                    if let Err(e) = append_dir(&mut builder, &dest_dir, &path) {
-                        errs.push(e);
-                    }
+                } else if path.is_dir()
+                    && let Err(e) = append_dir(&mut builder, &dest_dir, &path)
+                {
+                    errs.push(e);
                 }
             }
         }
@@ -40,7 +43,8 @@ impl ToteArchiver for Archiver {
 fn append_dir<W: Write>(builder: &mut ar::Builder<W>, dest_path: &Path, src_path: &Path) -> Result<()> {
     let identifier = dest_path.to_str().unwrap().to_string();
     let metadata = std::fs::metadata(src_path).map_err(Error::IO)?;
-    let header = ar::Header::from_metadata(identifier.into_bytes(), &metadata);
+    let mut header = ar::Header::from_metadata(identifier.into_bytes(), &metadata);
+    header.set_size(0);
     builder.append(&header, &mut std::io::empty()).map_err(Error::IO)
 }
 
@@ -78,9 +82,9 @@ mod tests {
                 .dest("results/test.ar")
                 .overwrite(true)
                 .build();
-            let v = vec!["lib", "cli", "Cargo.toml"]
+            let v = ["lib", "cli", "Cargo.toml"]
                 .iter()
-                .map(|s| PathBuf::from(s))
+                .map(PathBuf::from)
                 .collect::<Vec<_>>();
             let result = crate::archive(&v, &config);
             let path = PathBuf::from("results/test.ar");
@@ -1,9 +1,10 @@
 use std::fs::File;
+use std::io::{Seek, Write};
 use std::path::{Path, PathBuf};
 
 use cab::{CabinetBuilder, CabinetWriter};
 
-use crate::archiver::{ArchiveEntry, ToteArchiver};
+use crate::archiver::{ArchiveEntry, ToteArchiver, WriteSeek};
 use crate::{Result, Error};
 
 /// CAB (Cabinet) format archiver implementation.
@@ -14,7 +15,7 @@ pub(super) struct Archiver {}
 impl ToteArchiver for Archiver {
     fn perform(
         &self,
-        file: File,
+        writer: Box<dyn WriteSeek + '_>,
         targets: &[PathBuf],
         config: &crate::ArchiveConfig,
     ) -> Result<Vec<ArchiveEntry>> {
@@ -28,7 +29,7 @@ impl ToteArchiver for Archiver {
             entries.push(ArchiveEntry::from(path));
             folder.add_file(config.path_in_archive(path).to_str().unwrap());
         }
-        let mut writer = match builder.build(file) {
+        let mut writer = match builder.build(writer) {
             Ok(w) => w,
             Err(e) => return Err(Error::Archiver(e.to_string())),
         };
@@ -55,7 +56,7 @@ fn compression_type(level: u8) -> cab::CompressionType {
     }
 }
 
-fn write_entry(writer: &mut CabinetWriter<File>, path: &Path) -> Result<()> {
+fn write_entry<W: Write + Seek>(writer: &mut CabinetWriter<W>, path: &Path) -> Result<()> {
     match (File::open(path), writer.next_file()) {
         (Ok(mut reader), Ok(Some(mut w))) => match std::io::copy(&mut reader, &mut w) {
             Ok(_) => Ok(()),
@@ -98,7 +99,7 @@ mod tests {
                 .build();
             let v = vec!["lib", "cli", "Cargo.toml"]
                 .into_iter()
-                .map(|s| PathBuf::from(s))
+                .map(PathBuf::from)
                 .collect::<Vec<_>>();
             if let Err(e) = crate::archive(&v, &config) {
                 panic!("{e:?}")
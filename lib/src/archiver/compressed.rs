@@ -0,0 +1,235 @@
+use bzip2::write::BzEncoder;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use xz2::write::XzEncoder;
+
+use crate::archiver::{collect_entries, ArchiveEntry, ToteArchiver, WriteSeek};
+use crate::{Result, Error};
+
+/// GZIP format archiver implementation, which compresses a single file.
+pub(super) struct GzArchiver {}
+
+/// BZIP2 format archiver implementation, which compresses a single file.
+pub(super) struct Bz2Archiver {}
+
+/// XZ format archiver implementation, which compresses a single file.
+pub(super) struct XzArchiver {}
+
+/// ZSTD format archiver implementation, which compresses a single file.
+pub(super) struct ZstdArchiver {}
+
+/// LZ4 format archiver implementation, which compresses a single file.
+pub(super) struct Lz4Archiver {}
+
+impl ToteArchiver for GzArchiver {
+    fn perform(
+        &self,
+        writer: Box<dyn WriteSeek + '_>,
+        targets: &[PathBuf],
+        config: &crate::ArchiveConfig,
+    ) -> Result<Vec<ArchiveEntry>> {
+        self.perform_stream(writer, targets, config)
+    }
+    fn perform_stream(
+        &self,
+        writer: Box<dyn Write + '_>,
+        targets: &[PathBuf],
+        config: &crate::ArchiveConfig,
+    ) -> Result<Vec<ArchiveEntry>> {
+        let path = single_target(targets, config)?;
+        let level = config.level as u32;
+        let mut builder = flate2::GzBuilder::new();
+        if let Some(name) = path.file_name() {
+            builder = builder.filename(name.to_string_lossy().as_bytes());
+        }
+        if let Some(mtime) = mtime_of(&path) {
+            builder = builder.mtime(mtime);
+        }
+        let mut encoder = builder.write(writer, flate2::Compression::new(level));
+        copy_file(&path, &mut encoder)?;
+        encoder.finish().map_err(Error::IO)?;
+        Ok(vec![ArchiveEntry::from(&path)])
+    }
+    fn enable(&self) -> bool {
+        true
+    }
+}
+
+impl ToteArchiver for Bz2Archiver {
+    fn perform(
+        &self,
+        writer: Box<dyn WriteSeek + '_>,
+        targets: &[PathBuf],
+        config: &crate::ArchiveConfig,
+    ) -> Result<Vec<ArchiveEntry>> {
+        self.perform_stream(writer, targets, config)
+    }
+    fn perform_stream(
+        &self,
+        writer: Box<dyn Write + '_>,
+        targets: &[PathBuf],
+        config: &crate::ArchiveConfig,
+    ) -> Result<Vec<ArchiveEntry>> {
+        let path = single_target(targets, config)?;
+        let level = config.level as u32;
+        let mut encoder = BzEncoder::new(writer, bzip2::Compression::new(level));
+        copy_file(&path, &mut encoder)?;
+        encoder.finish().map_err(Error::IO)?;
+        Ok(vec![ArchiveEntry::from(&path)])
+    }
+    fn enable(&self) -> bool {
+        true
+    }
+}
+
+impl ToteArchiver for XzArchiver {
+    fn perform(
+        &self,
+        writer: Box<dyn WriteSeek + '_>,
+        targets: &[PathBuf],
+        config: &crate::ArchiveConfig,
+    ) -> Result<Vec<ArchiveEntry>> {
+        self.perform_stream(writer, targets, config)
+    }
+    fn perform_stream(
+        &self,
+        writer: Box<dyn Write + '_>,
+        targets: &[PathBuf],
+        config: &crate::ArchiveConfig,
+    ) -> Result<Vec<ArchiveEntry>> {
+        let path = single_target(targets, config)?;
+        let level = config.level as u32;
+        let mut encoder = XzEncoder::new(writer, level);
+        copy_file(&path, &mut encoder)?;
+        encoder.finish().map_err(Error::IO)?;
+        Ok(vec![ArchiveEntry::from(&path)])
+    }
+    fn enable(&self) -> bool {
+        true
+    }
+}
+
+impl ToteArchiver for ZstdArchiver {
+    fn perform(
+        &self,
+        writer: Box<dyn WriteSeek + '_>,
+        targets: &[PathBuf],
+        config: &crate::ArchiveConfig,
+    ) -> Result<Vec<ArchiveEntry>> {
+        self.perform_stream(writer, targets, config)
+    }
+    fn perform_stream(
+        &self,
+        writer: Box<dyn Write + '_>,
+        targets: &[PathBuf],
+        config: &crate::ArchiveConfig,
+    ) -> Result<Vec<ArchiveEntry>> {
+        let path = single_target(targets, config)?;
+        let level = config.level as u32;
+        let level = (level as f64 + 1.0) / 10.0 * 22.0; // convert to 1-22
+        let mut encoder = zstd::Encoder::new(writer, level as i32).map_err(Error::IO)?;
+        copy_file(&path, &mut encoder)?;
+        encoder.finish().map_err(Error::IO)?;
+        Ok(vec![ArchiveEntry::from(&path)])
+    }
+    fn enable(&self) -> bool {
+        true
+    }
+}
+
+impl ToteArchiver for Lz4Archiver {
+    fn perform(
+        &self,
+        writer: Box<dyn WriteSeek + '_>,
+        targets: &[PathBuf],
+        config: &crate::ArchiveConfig,
+    ) -> Result<Vec<ArchiveEntry>> {
+        self.perform_stream(writer, targets, config)
+    }
+    fn perform_stream(
+        &self,
+        writer: Box<dyn Write + '_>,
+        targets: &[PathBuf],
+        config: &crate::ArchiveConfig,
+    ) -> Result<Vec<ArchiveEntry>> {
+        // lz4 has no compression levels, therefore, `config.level` is ignored.
+        let path = single_target(targets, config)?;
+        let mut encoder = lz4_flex::frame::FrameEncoder::new(writer);
+        copy_file(&path, &mut encoder)?;
+        encoder.finish().map_err(|e| Error::Archiver(e.to_string()))?;
+        Ok(vec![ArchiveEntry::from(&path)])
+    }
+    fn enable(&self) -> bool {
+        true
+    }
+}
+
+/// Returns the only one file in the given targets,
+/// since the single file compression formats have no room for the multiple files.
+fn single_target(targets: &[PathBuf], config: &crate::ArchiveConfig) -> Result<PathBuf> {
+    let files = collect_entries(targets, config);
+    match files.as_slice() {
+        [file] => Ok(file.clone()),
+        [] => Err(Error::Archiver("no file to compress".to_string())),
+        _ => Err(Error::Archiver(format!(
+            "{} files given: the single file compression formats accept only one file",
+            files.len()
+        ))),
+    }
+}
+
+fn mtime_of(path: &Path) -> Option<u32> {
+    path.metadata().ok()?
+        .modified().ok()?
+        .duration_since(std::time::UNIX_EPOCH).ok()
+        .and_then(|d| u32::try_from(d.as_secs()).ok())
+}
+
+fn copy_file<W: Write>(path: &Path, writer: &mut W) -> Result<u64> {
+    let mut file = std::fs::File::open(path).map_err(Error::IO)?;
+    std::io::copy(&mut file, writer).map_err(Error::IO)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compress_roundtrip() {
+        for ext in ["gz", "bz2", "xz", "zst", "lz4"] {
+            let archive_file = PathBuf::from(format!("results/compressed/README.md.{ext}"));
+            let config = crate::ArchiveConfig::builder()
+                .dest(&archive_file)
+                .overwrite(true)
+                .build();
+            let entries = crate::archive(&["../testdata/sample/README.md"], &config).unwrap();
+            assert_eq!(entries.len(), 1, "{ext}");
+
+            let dest = PathBuf::from(format!("results/compressed/{ext}"));
+            let config = crate::ExtractConfig::builder()
+                .dest(&dest)
+                .overwrite(true)
+                .build();
+            crate::extract(&archive_file, &config).unwrap();
+            assert_eq!(
+                std::fs::read(dest.join("README.md")).unwrap(),
+                std::fs::read("../testdata/sample/README.md").unwrap(),
+                "{ext}"
+            );
+        }
+        let _ = std::fs::remove_dir_all("results/compressed");
+    }
+
+    #[test]
+    fn test_compress_multiple_files() {
+        let config = crate::ArchiveConfig::builder()
+            .dest("results/multiple.gz")
+            .overwrite(true)
+            .build();
+        match crate::archive(&["../testdata/sample/src"], &config) {
+            Err(Error::Archiver(m)) => assert!(m.contains("accept only one file"), "{m}"),
+            r => panic!("unexpected result: {r:?}"),
+        }
+        let _ = std::fs::remove_file("results/multiple.gz");
+    }
+}
@@ -1,7 +1,8 @@
 use std::fs::File;
-use std::path::PathBuf;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 
-use crate::archiver::{ArchiveEntry, ToteArchiver};
+use crate::archiver::{ArchiveEntry, ToteArchiver, WriteSeek};
 use crate::{Result, Error};
 
 /// CPIO format archiver implementation.
@@ -10,15 +11,25 @@ use crate::{Result, Error};
 pub(super) struct Archiver {}
 
 impl ToteArchiver for Archiver {
-    fn perform(&self, file: File, targets: &[PathBuf], config: &crate::ArchiveConfig) -> Result<Vec<ArchiveEntry>> {
-        let entries = super::collect_entries(targets, config);
+    fn perform(&self, writer: Box<dyn WriteSeek + '_>, targets: &[PathBuf], config: &crate::ArchiveConfig) -> Result<Vec<ArchiveEntry>> {
+        self.perform_stream(writer, targets, config)
+    }
+
+    fn perform_stream(&self, writer: Box<dyn Write + '_>, targets: &[PathBuf], config: &crate::ArchiveConfig) -> Result<Vec<ArchiveEntry>> {
+        let entries = collect_entries_with_dirs(targets, config);
         let format = find_format(config.level);
-        let mut builder = cpio::Builder::new(file);
+        let mut builder = cpio::Builder::new(writer);
         builder.set_format(format);
         let mut errs = vec![];
         for path in entries.iter() {
             let path_in_archive = config.path_in_archive(path);
-            if let Err(e) = builder.append_path(path, &path_in_archive) {
+            // append_path stores the directories, the symbolic links and the hard links as they are.
+            let r = if config.follow_links && path.is_file() {
+                append_followed_path(&mut builder, path, &path_in_archive)
+            } else {
+                builder.append_path(path, &path_in_archive).map(|_| ())
+            };
+            if let Err(e) = r {
                 errs.push(Error::Archiver(e.to_string()));
             };
         }
@@ -36,6 +47,28 @@ impl ToteArchiver for Archiver {
     }
 }
 
+/// Collects the files, the directories and the links in the targets.
+/// The links to the directories are skipped when following the links, since their contents are collected.
+fn collect_entries_with_dirs(targets: &[PathBuf], config: &crate::ArchiveConfig) -> Vec<PathBuf> {
+    let mut r = vec![];
+    for path in targets {
+        for entry in config.iter(path) {
+            let path = entry.into_path();
+            let is_link = path.is_symlink();
+            if path.is_file() || (path.is_dir() && !is_link) || (is_link && !config.follow_links) {
+                r.push(path)
+            }
+        }
+    }
+    r
+}
+
+fn append_followed_path<W: Write>(builder: &mut cpio::Builder<W>, path: &Path, path_in_archive: &Path) -> std::io::Result<()> {
+    let metadata: cpio::Metadata = (&path.metadata()?).try_into()?;
+    builder.append_entry(metadata, path_in_archive, File::open(path)?)
+        .map(|_| ())
+}
+
 fn find_format(level: u8) -> cpio::Format {
     use cpio::ByteOrder::{LittleEndian, BigEndian};
     match level {
@@ -75,7 +108,7 @@ mod tests {
                 .build();
             let v = vec!["lib", "cli", "Cargo.toml"]
                 .into_iter()
-                .map(|s| PathBuf::from(s))
+                .map(PathBuf::from)
                 .collect::<Vec<_>>();
             if let Err(e) = crate::archive(&v, &config) {
                 panic!("{e:?}")
@@ -83,6 +116,12 @@ mod tests {
         });
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn test_links() {
+        crate::archiver::assert_links_roundtrip("results/links.cpio", true);
+    }
+
     fn teardown() {
         let _ = std::fs::remove_file("results/test.cpio");
     }
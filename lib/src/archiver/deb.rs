@@ -0,0 +1,238 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use xz2::write::XzEncoder;
+
+use crate::archiver::{ArchiveEntry, ToteArchiver, WriteSeek};
+use crate::{Result, Error};
+
+/// The directory of the control files in the staged directory, the same as `dpkg-deb --build`.
+const CONTROL_DIR: &str = "DEBIAN";
+/// The content of `debian-binary`, the format version of the package.
+const DEBIAN_BINARY: &[u8] = b"2.0\n";
+
+/// DEB format archiver implementation.
+/// It builds the package from the staged directory like `dpkg-deb --build`,
+/// that is, `DEBIAN/` in the staged directory holds the control files, and the others are the data files.
+/// The files are owned by root in the package, as `dpkg-deb --root-owner-group` does.
+pub(super) struct Archiver {}
+
+impl ToteArchiver for Archiver {
+    fn perform(&self, writer: Box<dyn WriteSeek + '_>, targets: &[PathBuf], config: &crate::ArchiveConfig) -> Result<Vec<ArchiveEntry>> {
+        self.perform_stream(writer, targets, config)
+    }
+
+    fn perform_stream(&self, writer: Box<dyn Write + '_>, targets: &[PathBuf], config: &crate::ArchiveConfig) -> Result<Vec<ArchiveEntry>> {
+        let staged = staged_dir(targets)?;
+        let control_dir = staged.join(CONTROL_DIR);
+        let level = config.level as u32;
+        // the ar members need their sizes in advance, therefore, the control tarball is built on memory,
+        // and the data tarball, which grows with the package, is spooled into a temporary file.
+        let (control, _) = build_tar(XzEncoder::new(vec![], level), &control_dir, None, None)?;
+        let mut spool = Spool::create()?;
+        let (_, entries) = build_tar(XzEncoder::new(&mut spool.file, level), staged, Some(&control_dir), config.rebase_dir.as_deref())?;
+        let data_size = spool.file.stream_position().map_err(Error::IO)?;
+        spool.file.seek(SeekFrom::Start(0)).map_err(Error::IO)?;
+
+        let mtime = source_date_epoch();
+        let mut builder = ar::Builder::new(writer);
+        append_member(&mut builder, "debian-binary", DEBIAN_BINARY.len() as u64, DEBIAN_BINARY, mtime)?;
+        append_member(&mut builder, "control.tar.xz", control.len() as u64, control.as_slice(), mtime)?;
+        append_member(&mut builder, "data.tar.xz", data_size, &mut spool.file, mtime)?;
+        Ok(entries)
+    }
+
+    fn enable(&self) -> bool {
+        true
+    }
+}
+
+/// Returns `SOURCE_DATE_EPOCH` for the timestamps of the ar members, or 0 if it is not given,
+/// so that the same staged directory results in the same package.
+fn source_date_epoch() -> u64 {
+    std::env::var("SOURCE_DATE_EPOCH").ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+/// The temporary file to spool the data tarball, which is removed when dropped.
+struct Spool {
+    path: PathBuf,
+    file: File,
+}
+
+impl Spool {
+    fn create() -> Result<Self> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let name = format!("totebag-deb-{}-{}.tar.xz", std::process::id(), COUNTER.fetch_add(1, Ordering::Relaxed));
+        let path = std::env::temp_dir().join(name);
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .map_err(Error::IO)?;
+        Ok(Self { path, file })
+    }
+}
+
+impl Drop for Spool {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            log::warn!("{:?}: failed to remove ({e})", self.path);
+        }
+    }
+}
+
+/// Returns the staged directory, which must be the only one target having `DEBIAN/control`.
+fn staged_dir(targets: &[PathBuf]) -> Result<&PathBuf> {
+    match targets {
+        [dir] if dir.join(CONTROL_DIR).join("control").is_file() => Ok(dir),
+        [dir] => Err(Error::Archiver(format!("{}: {CONTROL_DIR}/control not found in the staged directory", dir.display()))),
+        _ => Err(Error::Archiver(format!("{} targets given: deb accepts only one staged directory", targets.len()))),
+    }
+}
+
+/// Builds the tarball of the files in `dir` (except `excluded`), and returns the writer of the compressed data and the archived entries.
+/// The entry names are relative to `dir`, and prefixed with `rebase_dir` if given.
+fn build_tar<W: Write>(encoder: XzEncoder<W>, dir: &Path, excluded: Option<&Path>, rebase_dir: Option<&Path>) -> Result<(W, Vec<ArchiveEntry>)> {
+    let mut builder = tar::Builder::new(encoder);
+    let mut entries = vec![];
+    // the staged directory is packaged as is, therefore, the ignore settings are not applied.
+    let walker = ignore::WalkBuilder::new(dir)
+        .standard_filters(false)
+        .sort_by_file_name(|a, b| a.cmp(b))
+        .build();
+    for entry in walker {
+        let entry = entry.map_err(|e| Error::Archiver(e.to_string()))?;
+        let path = entry.path();
+        if excluded.is_some_and(|excluded| path.starts_with(excluded)) {
+            continue;
+        }
+        let relative = path.strip_prefix(dir).unwrap_or(path);
+        if relative.as_os_str().is_empty() {
+            continue;
+        }
+        let name = match rebase_dir {
+            Some(rebase) => rebase.join(relative),
+            None => relative.to_path_buf(),
+        };
+        append_entry(&mut builder, path, &name)?;
+        entries.push(ArchiveEntry::from(path));
+    }
+    let encoder = builder.into_inner().map_err(|e| Error::Archiver(e.to_string()))?;
+    let writer = encoder.finish().map_err(Error::IO)?;
+    Ok((writer, entries))
+}
+
+fn append_entry<W: Write>(builder: &mut tar::Builder<W>, path: &Path, name: &Path) -> Result<()> {
+    let metadata = path.symlink_metadata().map_err(Error::IO)?;
+    let mut header = tar::Header::new_gnu();
+    header.set_metadata(&metadata);
+    header.set_uid(0);
+    header.set_gid(0);
+    header.set_username("root")
+        .and_then(|_| header.set_groupname("root"))
+        .map_err(|e| Error::Archiver(e.to_string()))?;
+    let r = if metadata.is_symlink() {
+        let target = std::fs::read_link(path).map_err(Error::IO)?;
+        builder.append_link(&mut header, name, target)
+    } else if metadata.is_file() {
+        let file = std::fs::File::open(path).map_err(Error::IO)?;
+        builder.append_data(&mut header, name, file)
+    } else if metadata.is_dir() {
+        header.set_size(0);
+        builder.append_data(&mut header, name, std::io::empty())
+    } else {
+        log::warn!("{}: unsupported file type, skipped", path.display());
+        return Ok(());
+    };
+    r.map_err(|e| Error::Archiver(e.to_string()))
+}
+
+fn append_member<W: Write, R: Read>(builder: &mut ar::Builder<W>, name: &str, size: u64, data: R, mtime: u64) -> Result<()> {
+    let mut header = ar::Header::new(name.as_bytes().to_vec(), size);
+    header.set_mtime(mtime);
+    header.set_mode(0o100644);
+    builder.append(&header, data).map_err(Error::IO)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    fn create_staged_dir(dir: &str) -> PathBuf {
+        let dir = PathBuf::from(dir);
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("DEBIAN")).unwrap();
+        std::fs::create_dir_all(dir.join("usr/share/doc/sample")).unwrap();
+        std::fs::write(dir.join("DEBIAN/control"), "Package: sample\nVersion: 1.0\nArchitecture: all\n").unwrap();
+        std::fs::copy("../testdata/sample/README.md", dir.join("usr/share/doc/sample/README.md")).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_deb_roundtrip() {
+        let staged = create_staged_dir("results/deb_stage");
+        let config = crate::ArchiveConfig::builder()
+            .dest("results/sample.deb")
+            .overwrite(true)
+            .build();
+        let entries = crate::archive(&[&staged], &config).unwrap();
+        assert!(entries.entries.iter().any(|e| e.path.ends_with("README.md")));
+        assert!(!entries.entries.iter().any(|e| e.path.ends_with("control")));
+
+        let entries = crate::entries("results/sample.deb", crate::format::default_format_detector().as_ref()).unwrap();
+        let names = entries.iter().map(|e| e.name.clone()).collect::<Vec<_>>();
+        assert!(names.contains(&"DEBIAN/control".to_string()), "{names:?}");
+        assert!(names.contains(&"usr/share/doc/sample/README.md".to_string()), "{names:?}");
+
+        let config = crate::ExtractConfig::builder()
+            .dest("results/deb_extracted")
+            .overwrite(true)
+            .package_control(true)
+            .build();
+        crate::extract("results/sample.deb", &config).unwrap();
+        assert_eq!(
+            std::fs::read("results/deb_extracted/usr/share/doc/sample/README.md").unwrap(),
+            std::fs::read("../testdata/sample/README.md").unwrap()
+        );
+        assert!(PathBuf::from("results/deb_extracted/DEBIAN/control").exists());
+
+        let _ = std::fs::remove_dir_all(staged);
+        let _ = std::fs::remove_dir_all("results/deb_extracted");
+        let _ = std::fs::remove_file("results/sample.deb");
+    }
+
+    #[test]
+    fn test_deb_reproducible() {
+        let staged = create_staged_dir("results/deb_reproducible_stage");
+        let mut packages = vec![];
+        for _ in 0..2 {
+            let config = crate::ArchiveConfig::builder()
+                .dest("results/reproducible.deb")
+                .overwrite(true)
+                .build();
+            crate::archive(&[&staged], &config).unwrap();
+            packages.push(std::fs::read("results/reproducible.deb").unwrap());
+        }
+        assert!(packages[0] == packages[1]);
+
+        let _ = std::fs::remove_dir_all(staged);
+        let _ = std::fs::remove_file("results/reproducible.deb");
+    }
+
+    #[test]
+    fn test_deb_without_control() {
+        let config = crate::ArchiveConfig::builder()
+            .dest("results/no_control.deb")
+            .overwrite(true)
+            .build();
+        match crate::archive(&["../testdata/sample"], &config) {
+            Err(crate::Error::Archiver(m)) => assert!(m.contains("DEBIAN/control"), "{m}"),
+            r => panic!("unexpected result: {r:?}"),
+        }
+        let _ = std::fs::remove_file("results/no_control.deb");
+    }
+}
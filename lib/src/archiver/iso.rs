@@ -0,0 +1,48 @@
+use std::path::PathBuf;
+
+use crate::archiver::{ArchiveEntry, ToteArchiver, WriteSeek};
+use crate::{Result, Error};
+
+/// ISO 9660 image archiver implementation.
+///
+/// Note: This archiver is not supported for creating archives,
+/// only extraction is supported for ISO 9660 format.
+pub(super) struct Archiver {}
+
+impl ToteArchiver for Archiver {
+    fn perform(
+        &self,
+        _: Box<dyn WriteSeek + '_>,
+        _: &[PathBuf],
+        _config: &crate::ArchiveConfig,
+    ) -> Result<Vec<ArchiveEntry>> {
+        Err(Error::UnsupportedFormat(
+            "only extraction support for iso".to_string(),
+        ))
+    }
+
+    fn enable(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ArchiveConfig;
+    use crate::Error;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_iso_archive() {
+        let config = ArchiveConfig::builder().dest("results/test.iso").build();
+        let v = Vec::<PathBuf>::new();
+
+        let r = crate::archive(&v, &config);
+        assert!(r.is_err());
+        if let Err(Error::UnsupportedFormat(e)) = r {
+            assert_eq!(e, "Iso: unsupported format (archiving)");
+        } else {
+            panic!("unexpected result: {:?}", r);
+        }
+    }
+}
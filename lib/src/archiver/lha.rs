@@ -1,49 +1,435 @@
-use std::fs::File;
-use std::path::PathBuf;
+use std::fs::{File, Metadata};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
 
-use crate::archiver::{ArchiveEntry, ToteArchiver};
+use crc::{Crc, Digest, CRC_16_ARC};
+
+use crate::archiver::os::permission;
+use crate::archiver::{ArchiveEntry, Links, Target, ToteArchiver, WriteSeek};
+use crate::codec::Lh5Encoder;
 use crate::{Result, Error};
 
+const CRC16: Crc<u16> = Crc::<u16>::new(&CRC_16_ARC);
+const LEVEL2_BASE_SIZE: usize = 26;
+const EXT_COMMON: u8 = 0x00;
+const EXT_FILENAME: u8 = 0x01;
+const EXT_DIRNAME: u8 = 0x02;
+const EXT_UNIX_PERMISSION: u8 = 0x50;
+const EXT_UNIX_GID_UID: u8 = 0x51;
+const OS_UNIX: u8 = b'U';
+
 /// LHA/LZH format archiver implementation.
 ///
-/// Note: This archiver is not supported for creating archives,
-/// only extraction is supported for LHA/LZH format.
+/// This archiver writes the level-2 headers, and compresses the files with `-lh5-`,
+/// or stores them with `-lh0-` when the level is 0 or the compression does not reduce the size.
+/// The hard links are stored as the regular files, and the symbolic links as `name|target`
+/// with the `-lhd-` method, as LHa for UNIX does.
 pub(super) struct Archiver {}
 
 impl ToteArchiver for Archiver {
-    fn perform(
-        &self,
-        _: File,
-        _: &[PathBuf],
-        _config: &crate::ArchiveConfig,
-    ) -> Result<Vec<ArchiveEntry>> {
-        Err(Error::UnsupportedFormat(
-            "only extraction support for lha".to_string(),
-        ))
+    fn perform(&self, mut writer: Box<dyn WriteSeek + '_>, targets: &[PathBuf], config: &crate::ArchiveConfig) -> Result<Vec<ArchiveEntry>> {
+        let mut errs = vec![];
+        let mut links = Links::new(config);
+        let mut entries = vec![];
+        for tp in targets {
+            for t in config.iter(tp) {
+                let path = t.into_path();
+                entries.push(ArchiveEntry::from(&path));
+                let dest_path = config.path_in_archive(&path);
+                let r = match links.classify(&path, &dest_path) {
+                    Ok(Some(Target::File | Target::Hardlink(_))) => process_file(&mut writer, &path, &dest_path, config.level),
+                    Ok(Some(Target::Symlink(target))) => process_symlink(&mut writer, &path, &dest_path, &target),
+                    Ok(Some(Target::Dir)) => process_dir(&mut writer, &path, &dest_path),
+                    Ok(None) => Ok(()),
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = r {
+                    errs.push(e);
+                }
+            }
+        }
+        if let Err(e) = terminate(&mut writer) {
+            errs.push(Error::IO(e));
+        }
+        Error::error_or(entries, errs)
     }
+
     fn enable(&self) -> bool {
-        false
+        true
+    }
+}
+
+/// Writes the header of size 0, which terminates the archive.
+/// The rest of the stream is filled with 0, since the stored data falling back from `-lh5-`
+/// may leave the longer compressed data behind.
+fn terminate<W: Write + Seek + ?Sized>(writer: &mut W) -> std::io::Result<()> {
+    let end = writer.stream_position()?;
+    let len = writer.seek(SeekFrom::End(0))?;
+    writer.seek(SeekFrom::Start(end))?;
+    let fill = len.saturating_sub(end).max(1);
+    std::io::copy(&mut std::io::repeat(0).take(fill), writer)?;
+    writer.flush()
+}
+
+/// Writes the header with the empty sizes and the CRC, streams the data, and then rewrites the header
+/// with the actual ones.
+/// The data is rewritten with `-lh0-` when `-lh5-` does not reduce the size.
+fn process_file<W: Write + Seek + ?Sized>(writer: &mut W, target: &Path, dest_path: &Path, level: u8) -> Result<()> {
+    let metadata = std::fs::metadata(target).map_err(Error::IO)?;
+    if metadata.len() > u32::MAX as u64 {
+        return Err(Error::Archiver(format!("{target:?}: too large for lha (over 4 GiB)")));
+    }
+    let (dirname, filename) = split_path(dest_path);
+    let mut header = Header {
+        method: if level == 0 || metadata.len() == 0 { b"-lh0-" } else { b"-lh5-" },
+        compressed_size: 0,
+        original_size: 0,
+        file_crc: 0,
+        dirname,
+        filename: filename.into_bytes(),
+        metadata: &metadata,
+    };
+    let header_pos = writer.stream_position().map_err(Error::IO)?;
+    writer.write_all(&header.to_bytes()?).map_err(Error::IO)?;
+    let data_pos = writer.stream_position().map_err(Error::IO)?;
+    let mut data = write_data(writer, target, header.method, level)?;
+    if data.method == b"-lh5-" && data.compressed_size >= data.size {
+        writer.seek(SeekFrom::Start(data_pos)).map_err(Error::IO)?;
+        let stored = write_data(writer, target, b"-lh0-", level)?;
+        if stored.size != data.size || stored.crc != data.crc {
+            return Err(Error::Archiver(format!("{target:?}: changed while archiving")));
+        }
+        data = stored;
+    }
+    let (Ok(compressed_size), Ok(original_size)) = (u32::try_from(data.compressed_size), u32::try_from(data.size)) else {
+        return Err(Error::Archiver(format!("{target:?}: too large for lha (over 4 GiB)")));
+    };
+    header.method = data.method;
+    header.compressed_size = compressed_size;
+    header.original_size = original_size;
+    header.file_crc = data.crc;
+    writer.seek(SeekFrom::Start(header_pos)).map_err(Error::IO)?;
+    writer.write_all(&header.to_bytes()?).map_err(Error::IO)?;
+    writer.seek(SeekFrom::Start(data_pos + data.compressed_size)).map_err(Error::IO)?;
+    Ok(())
+}
+
+/// The method, the sizes, and the CRC of the written data.
+struct Data {
+    method: &'static [u8; 5],
+    size: u64,
+    compressed_size: u64,
+    crc: u16,
+}
+
+/// Streams the data of the given file into the writer with the given method (`-lh0-` or `-lh5-`).
+fn write_data<W: Write + ?Sized>(writer: &mut W, target: &Path, method: &'static [u8; 5], level: u8) -> Result<Data> {
+    let mut file = File::open(target).map_err(Error::IO)?;
+    let mut counter = CountingWriter { writer, written: 0 };
+    let mut digest = CRC16.digest();
+    let size = if method == b"-lh5-" {
+        let mut encoder = Lh5Encoder::new(&mut counter, level);
+        let size = copy_with_crc(&mut file, &mut encoder, &mut digest)?;
+        encoder.finish().map_err(Error::IO)?;
+        size
+    } else {
+        copy_with_crc(&mut file, &mut counter, &mut digest)?
+    };
+    Ok(Data { method, size, compressed_size: counter.written, crc: digest.finalize() })
+}
+
+fn copy_with_crc<R: Read, W: Write>(reader: &mut R, writer: &mut W, digest: &mut Digest<'_, u16>) -> Result<u64> {
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut size = 0;
+    loop {
+        let n = match reader.read(&mut buf) {
+            Ok(0) => return Ok(size),
+            Ok(n) => n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(Error::IO(e)),
+        };
+        digest.update(&buf[..n]);
+        writer.write_all(&buf[..n]).map_err(Error::IO)?;
+        size += n as u64;
+    }
+}
+
+/// The writer counting the bytes written into the archive.
+struct CountingWriter<'a, W: Write + ?Sized> {
+    writer: &'a mut W,
+    written: u64,
+}
+
+impl<W: Write + ?Sized> Write for CountingWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.writer.write(buf)?;
+        self.written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+fn process_dir<W: Write + ?Sized>(writer: &mut W, target: &Path, dest_path: &Path) -> Result<()> {
+    let metadata = std::fs::metadata(target).map_err(Error::IO)?;
+    let (mut dirname, filename) = split_path(dest_path);
+    if filename.is_empty() {
+        return Ok(());
+    }
+    dirname.extend(filename.into_bytes());
+    dirname.push(0xff);
+    let header = Header::empty(dirname, vec![], &metadata);
+    writer.write_all(&header.to_bytes()?).map_err(Error::IO)
+}
+
+fn process_symlink<W: Write + ?Sized>(writer: &mut W, target: &Path, dest_path: &Path, link: &Path) -> Result<()> {
+    let metadata = std::fs::symlink_metadata(target).map_err(Error::IO)?;
+    let (dirname, filename) = split_path(dest_path);
+    let filename = format!("{filename}|{}", link.to_string_lossy());
+    let header = Header::empty(dirname, filename.into_bytes(), &metadata);
+    writer.write_all(&header.to_bytes()?).map_err(Error::IO)
+}
+
+/// Splits the path into the directory name (the components terminated by `0xff`) and the file name.
+/// The root, the current and the parent directories are dropped from the path.
+fn split_path(path: &Path) -> (Vec<u8>, String) {
+    let mut names = path.components()
+        .filter_map(|c| match c {
+            Component::Normal(name) => Some(name.to_string_lossy().to_string()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let filename = names.pop().unwrap_or_default();
+    let mut dirname = vec![];
+    for name in names {
+        dirname.extend(name.into_bytes());
+        dirname.push(0xff);
+    }
+    (dirname, filename)
+}
+
+/// The level-2 header of the entry.
+struct Header<'a> {
+    method: &'static [u8; 5],
+    compressed_size: u32,
+    original_size: u32,
+    file_crc: u16,
+    dirname: Vec<u8>,
+    filename: Vec<u8>,
+    metadata: &'a Metadata,
+}
+
+impl<'a> Header<'a> {
+    /// The header of the directory or the symbolic link, which have no data.
+    fn empty(dirname: Vec<u8>, filename: Vec<u8>, metadata: &'a Metadata) -> Self {
+        Self {
+            method: b"-lhd-",
+            compressed_size: 0,
+            original_size: 0,
+            file_crc: 0,
+            dirname,
+            filename,
+            metadata,
+        }
+    }
+
+    /// Returns the bytes of the header, or an error if the header exceeds 64 KiB (e.g., by a long path).
+    fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mtime = self.metadata.modified().ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map_or(0, |d| d.as_secs().min(u32::MAX as u64) as u32);
+        let mut extras = vec![vec![EXT_COMMON, 0, 0]];
+        if !self.filename.is_empty() {
+            extras.push([&[EXT_FILENAME], self.filename.as_slice()].concat());
+        }
+        if !self.dirname.is_empty() {
+            extras.push([&[EXT_DIRNAME], self.dirname.as_slice()].concat());
+        }
+        let mut permission = vec![EXT_UNIX_PERMISSION];
+        permission.extend((unix_mode(self.metadata) as u16).to_le_bytes());
+        extras.push(permission);
+        if let Some((uid, gid)) = owner(self.metadata) {
+            let mut owner = vec![EXT_UNIX_GID_UID];
+            owner.extend((gid as u16).to_le_bytes());
+            owner.extend((uid as u16).to_le_bytes());
+            extras.push(owner);
+        }
+
+        let mut header = Vec::with_capacity(LEVEL2_BASE_SIZE + 64);
+        header.extend([0, 0]);
+        header.extend(self.method);
+        header.extend(self.compressed_size.to_le_bytes());
+        header.extend(self.original_size.to_le_bytes());
+        header.extend(mtime.to_le_bytes());
+        header.push(if self.metadata.is_dir() { 0x10 } else { 0x20 });
+        header.push(2);
+        header.extend(self.file_crc.to_le_bytes());
+        header.push(OS_UNIX);
+        header.extend((extras[0].len() as u16 + 2).to_le_bytes());
+        for (i, extra) in extras.iter().enumerate() {
+            header.extend(extra);
+            let next = extras.get(i + 1).map_or(0, |e| e.len() as u16 + 2);
+            header.extend(next.to_le_bytes());
+        }
+        // the first byte of the header must not be 0, which is the end of the archive.
+        if header.len() & 0xff == 0 {
+            header.push(0);
+        }
+        let Ok(size) = u16::try_from(header.len()) else {
+            return Err(Error::Archiver(format!("too large lha header ({} bytes)", header.len())));
+        };
+        header[0..2].copy_from_slice(&size.to_le_bytes());
+        let crc = CRC16.checksum(&header);
+        // the common extended header is the first one, and its CRC follows its type.
+        let offset = LEVEL2_BASE_SIZE + 1;
+        header[offset..offset + 2].copy_from_slice(&crc.to_le_bytes());
+        Ok(header)
     }
 }
 
+/// Returns the unix mode including the file type.
+fn unix_mode(metadata: &Metadata) -> u32 {
+    let mode = permission(metadata);
+    if mode & 0o170000 != 0 {
+        mode
+    } else if metadata.is_dir() {
+        0o040000 | mode
+    } else if metadata.is_symlink() {
+        0o120000 | mode
+    } else {
+        0o100000 | mode
+    }
+}
+
+#[cfg(unix)]
+fn owner(metadata: &Metadata) -> Option<(u32, u32)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.uid(), metadata.gid()))
+}
+
+#[cfg(not(unix))]
+fn owner(_: &Metadata) -> Option<(u32, u32)> {
+    None
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     use std::path::PathBuf;
 
+    fn list(archive_file: &str) -> Vec<(String, String, u64)> {
+        let file = std::fs::File::open(archive_file).unwrap();
+        let mut reader = delharc::LhaDecodeReader::new(file).unwrap();
+        let mut result = vec![];
+        loop {
+            let header = reader.header();
+            let method = String::from_utf8_lossy(&header.compression).to_string();
+            let name = header.parse_pathname_to_str();
+            let size = header.original_size;
+            if reader.is_decoder_supported() {
+                std::io::copy(&mut reader, &mut std::io::sink()).unwrap();
+                reader.crc_check().unwrap();
+            }
+            result.push((name, method, size));
+            if !reader.next_file().unwrap() {
+                break;
+            }
+        }
+        result
+    }
+
     #[test]
     fn test_lha_archive() {
         let config = crate::ArchiveConfig::builder()
-            .dest(PathBuf::from("results/test.lzh"))
+            .dest("results/test.lzh")
+            .rebase_dir("rebased")
+            .overwrite(true)
+            .build();
+        let v = vec![PathBuf::from("../testdata/sample")];
+        crate::archive(&v, &config).unwrap();
+        let entries = list("results/test.lzh");
+        let cargo = entries.iter().find(|e| e.0 == "rebased/testdata/sample/Cargo.toml").unwrap();
+        assert_eq!(cargo.1, "-lh5-");
+        assert_eq!(cargo.2, 723);
+        assert!(entries.iter().any(|e| e.0 == "rebased/testdata/sample/src" && e.1 == "-lhd-"));
+
+        let dest = PathBuf::from("results/lha_roundtrip");
+        let _ = std::fs::remove_dir_all(&dest);
+        let config = crate::ExtractConfig::builder().dest(&dest).build();
+        crate::extract("results/test.lzh", &config).unwrap();
+        for name in ["Cargo.toml", "LICENSE", "build.rs", "src/main.rs", "src/archiver/tar.rs"] {
+            let original = std::fs::read(PathBuf::from("../testdata/sample").join(name)).unwrap();
+            let extracted = std::fs::read(dest.join("rebased/testdata/sample").join(name)).unwrap();
+            assert!(original == extracted, "{name}");
+        }
+        std::fs::remove_dir_all(dest).unwrap();
+        std::fs::remove_file("results/test.lzh").unwrap();
+    }
+
+    #[test]
+    fn test_lha_store() {
+        let config = crate::ArchiveConfig::builder()
+            .dest("results/test_store.lzh")
+            .level(0)
+            .overwrite(true)
             .build();
-        let v: Vec<PathBuf> = Vec::new();
-        let r = crate::archive(&v, &config);
-        assert!(r.is_err());
-        if let Err(Error::UnsupportedFormat(e)) = r {
-            assert_eq!(e, "Lha: unsupported format (archiving)");
-        } else {
-            panic!("unexpected result: {:?}", r);
+        let v = vec![PathBuf::from("../testdata/sample/Cargo.toml")];
+        crate::archive(&v, &config).unwrap();
+        let entries = list("results/test_store.lzh");
+        assert_eq!(entries, vec![("testdata/sample/Cargo.toml".to_string(), "-lh0-".to_string(), 723)]);
+        std::fs::remove_file("results/test_store.lzh").unwrap();
+    }
+
+    #[test]
+    fn test_header_padding() {
+        let metadata = std::fs::metadata("../testdata/sample/Cargo.toml").unwrap();
+        for len in 1..300 {
+            let header = Header::empty(vec![], vec![b'a'; len], &metadata).to_bytes().unwrap();
+            assert_ne!(header[0], 0);
+            assert_eq!(u16::from_le_bytes([header[0], header[1]]) as usize, header.len());
+            let mut reader = delharc::LhaDecodeReader::new(header.as_slice()).unwrap();
+            assert_eq!(reader.header().parse_pathname_to_str().len(), len);
+            assert!(!reader.next_file().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_too_large_header() {
+        let metadata = std::fs::metadata("../testdata/sample/Cargo.toml").unwrap();
+        let header = Header::empty(vec![], vec![b'a'; 70000], &metadata);
+        assert!(matches!(header.to_bytes(), Err(Error::Archiver(_))));
+    }
+
+    #[test]
+    fn test_lha_store_incompressible() {
+        // the pseudo random bytes which `-lh5-` does not reduce.
+        let mut x = 0x2545f491_u32;
+        let data = (0..20000).map(|_| {
+            x ^= x << 13;
+            x ^= x >> 17;
+            x ^= x << 5;
+            x as u8
+        }).collect::<Vec<_>>();
+        std::fs::create_dir_all("results/lha_incompressible").unwrap();
+        std::fs::write("results/lha_incompressible/random.bin", &data).unwrap();
+
+        let incompressible = ("results/lha_incompressible/random.bin".to_string(), "-lh0-".to_string(), 20000);
+        let compressible = ("testdata/sample/Cargo.toml".to_string(), "-lh5-".to_string(), 723);
+        // the stored data is followed by the other entry, or by the end of the archive.
+        for expected in [vec![incompressible.clone(), compressible.clone()], vec![compressible, incompressible]] {
+            let config = crate::ArchiveConfig::builder()
+                .dest("results/lha_incompressible/test.lzh")
+                .overwrite(true)
+                .build();
+            let v = expected.iter()
+                .map(|e| if e.0.starts_with("testdata") { PathBuf::from("..").join(&e.0) } else { PathBuf::from(&e.0) })
+                .collect::<Vec<_>>();
+            crate::archive(&v, &config).unwrap();
+            assert_eq!(list("results/lha_incompressible/test.lzh"), expected);
         }
+        std::fs::remove_dir_all("results/lha_incompressible").unwrap();
     }
 }
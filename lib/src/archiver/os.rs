@@ -10,6 +10,19 @@ pub(super) fn create_file_opts(target: &Path, level: i64) -> SimpleFileOptions {
     create_file_option(metadata, level)
 }
 
+/// Creates the options for the directory or the symbolic link from its own metadata (not following the link).
+pub(super) fn create_entry_opts(target: &Path) -> SimpleFileOptions {
+    let opts = SimpleFileOptions::default();
+    let mod_time = std::fs::symlink_metadata(target)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| DateTime::try_from(OffsetDateTime::from(t)).ok());
+    match mod_time {
+        Some(t) => opts.last_modified_time(t),
+        None => opts,
+    }
+}
+
 pub(crate) fn permission(metadata: &Metadata) -> u32 {
     #[cfg(target_os = "windows")]
     {
@@ -1,7 +1,6 @@
-use std::fs::File;
 use std::path::PathBuf;
 
-use crate::archiver::{ArchiveEntry, ToteArchiver};
+use crate::archiver::{ArchiveEntry, ToteArchiver, WriteSeek};
 use crate::{Result, Error};
 
 /// RAR format archiver implementation.
@@ -13,7 +12,7 @@ pub(super) struct Archiver {}
 impl ToteArchiver for Archiver {
     fn perform(
         &self,
-        _: File,
+        _: Box<dyn WriteSeek + '_>,
         _: &[PathBuf],
         _config: &crate::ArchiveConfig,
     ) -> Result<Vec<ArchiveEntry>> {
@@ -1,9 +1,11 @@
 use std::fs::File;
-use std::path::PathBuf;
+use std::io::{Seek, Write};
+use std::path::{Path, PathBuf};
 
+use sevenz_rust::nt_time::FileTime;
 use sevenz_rust::{SevenZArchiveEntry, SevenZMethod, SevenZMethodConfiguration, SevenZWriter};
 
-use crate::archiver::{ArchiveEntry, ToteArchiver};
+use crate::archiver::{ArchiveEntry, Links, Target, ToteArchiver, WriteSeek};
 use crate::{Result, Error};
 
 /// 7-Zip format archiver implementation.
@@ -14,25 +16,33 @@ pub(super) struct Archiver {}
 impl ToteArchiver for Archiver {
     fn perform(
         &self,
-        file: File,
+        writer: Box<dyn WriteSeek + '_>,
         targets: &[PathBuf],
         config: &crate::ArchiveConfig,
     ) -> Result<Vec<ArchiveEntry>> {
-        let mut w = match SevenZWriter::new(file) {
+        let mut w = match SevenZWriter::new(writer) {
             Ok(writer) => writer,
             Err(e) => return Err(Error::Archiver(e.to_string())),
         };
         set_compression_level(&mut w, config.level);
         let mut errs = vec![];
+        let mut links = Links::new(config);
         let mut entries = vec![];
         for tp in targets {
             for t in config.iter(tp) {
                 let path = t.into_path();
                 entries.push(ArchiveEntry::from(&path));
-                if path.is_file() {
-                    if let Err(e) = process_file(&mut w, &path, &config.path_in_archive(&path)) {
-                        errs.push(e);
-                    }
+                let dest_path = config.path_in_archive(&path);
+                // 7z has no hard links, therefore, the hard links are stored as the regular files.
+                let r = match links.classify(&path, &dest_path) {
+                    Ok(Some(Target::File | Target::Hardlink(_))) => process_file(&mut w, &path, &dest_path),
+                    Ok(Some(Target::Symlink(target))) => process_symlink(&mut w, &path, &dest_path, &target),
+                    Ok(Some(Target::Dir)) => process_dir(&mut w, &path, &dest_path),
+                    Ok(None) => Ok(()),
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = r {
+                    errs.push(e);
                 }
             }
         }
@@ -47,7 +57,7 @@ impl ToteArchiver for Archiver {
     }
 }
 
-fn set_compression_level(szw: &mut SevenZWriter<File>, level: u8) {
+fn set_compression_level<W: Write + Seek>(szw: &mut SevenZWriter<W>, level: u8) {
     let level = match level {
         0..=4 => SevenZMethod::LZMA,
         _ => SevenZMethod::LZMA2,
@@ -55,17 +65,50 @@ fn set_compression_level(szw: &mut SevenZWriter<File>, level: u8) {
     szw.set_content_methods(vec![SevenZMethodConfiguration::new(level)]);
 }
 
-fn process_file(szw: &mut SevenZWriter<File>, target: &PathBuf, dest_path: &PathBuf) -> Result<()> {
-    let name = &dest_path.to_str().unwrap();
+fn process_file<W: Write + Seek>(szw: &mut SevenZWriter<W>, target: &Path, dest_path: &Path) -> Result<()> {
+    let name = dest_path.to_string_lossy().to_string();
+    let file = File::open(target).map_err(Error::IO)?;
     if let Err(e) = szw.push_archive_entry(
-        SevenZArchiveEntry::from_path(dest_path, name.to_string()),
-        Some(File::open(target).unwrap()),
+        SevenZArchiveEntry::from_path(target, name),
+        Some(file),
     ) {
         return Err(Error::Archiver(e.to_string()));
     }
     Ok(())
 }
 
+fn process_dir<W: Write + Seek>(szw: &mut SevenZWriter<W>, target: &Path, dest_path: &Path) -> Result<()> {
+    if dest_path.components().all(|c| c == std::path::Component::CurDir) {
+        return Ok(());
+    }
+    let name = dest_path.to_string_lossy().to_string();
+    szw.push_archive_entry::<File>(SevenZArchiveEntry::from_path(target, name), None)
+        .map(|_| ())
+        .map_err(|e| Error::Archiver(e.to_string()))
+}
+
+/// Stores the symbolic link as the entry whose content is the link target,
+/// and whose attributes have the unix mode (`S_IFLNK`) with `FILE_ATTRIBUTE_UNIX_EXTENSION` as p7zip does.
+fn process_symlink<W: Write + Seek>(szw: &mut SevenZWriter<W>, target: &Path, dest_path: &Path, link_target: &Path) -> Result<()> {
+    let mut entry = SevenZArchiveEntry::new();
+    entry.name = dest_path.to_string_lossy().to_string();
+    entry.has_stream = true;
+    entry.has_windows_attributes = true;
+    entry.windows_attributes = 0x8000 | (0o120777 << 16);
+    if let Some(date) = target.symlink_metadata()
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| FileTime::try_from(t).ok())
+    {
+        entry.has_last_modified_date = true;
+        entry.last_modified_date = date;
+    }
+    let link_target = link_target.to_string_lossy().to_string();
+    szw.push_archive_entry(entry, Some(link_target.as_bytes()))
+        .map(|_| ())
+        .map_err(|e| Error::Archiver(e.to_string()))
+}
+
 #[cfg(test)]
 mod tests {
     use std::path::PathBuf;
@@ -90,9 +133,9 @@ mod tests {
                 .dest("results/test.7z")
                 .overwrite(true)
                 .build();
-            let v = vec!["lib", "cli", "Cargo.toml"]
+            let v = ["lib", "cli", "Cargo.toml"]
                 .iter()
-                .map(|s| PathBuf::from(s))
+                .map(PathBuf::from)
                 .collect::<Vec<PathBuf>>();
             if let Err(e) = crate::archive(&v, &config) {
                 panic!("{:?}", e);
@@ -100,6 +143,12 @@ mod tests {
         });
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn test_links() {
+        crate::archiver::assert_links_roundtrip("results/links.7z", false);
+    }
+
     fn teardown() {
         let _ = std::fs::remove_file("results/test.7z");
     }
@@ -1,12 +1,12 @@
 use bzip2::write::BzEncoder;
 use flate2::write::GzEncoder;
-use std::fs::File;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tar::Builder;
 use xz2::write::XzEncoder;
 
-use crate::archiver::{ArchiveEntry, ToteArchiver};
+use crate::codec::{LzipEncoder, LzwEncoder};
+use crate::archiver::{ArchiveEntry, Links, Target, ToteArchiver, WriteSeek};
 use crate::{Result, Error};
 
 /// TAR format archiver implementation.
@@ -24,14 +24,37 @@ pub(super) struct XzArchiver {}
 /// TAR+ZSTD format archiver implementation.
 pub(super) struct ZstdArchiver {}
 
+/// TAR+LZ4 format archiver implementation.
+pub(super) struct Lz4Archiver {}
+
+/// TAR+LZMA (the legacy `.lzma` format) archiver implementation.
+pub(super) struct LzmaArchiver {}
+
+/// TAR+LZIP format archiver implementation.
+pub(super) struct LzArchiver {}
+
+/// TAR+COMPRESS (the unix `compress` command, `.Z`) archiver implementation.
+pub(super) struct ZArchiver {}
+
+/// TAR+BROTLI format archiver implementation.
+pub(super) struct BrArchiver {}
+
 impl ToteArchiver for Archiver {
     fn perform(
         &self,
-        file: File,
+        writer: Box<dyn WriteSeek + '_>,
         targets: &[PathBuf],
         config: &crate::ArchiveConfig,
     ) -> Result<Vec<ArchiveEntry>> {
-        write_tar(file, targets, config)
+        self.perform_stream(writer, targets, config)
+    }
+    fn perform_stream(
+        &self,
+        writer: Box<dyn Write + '_>,
+        targets: &[PathBuf],
+        config: &crate::ArchiveConfig,
+    ) -> Result<Vec<ArchiveEntry>> {
+        write_tar(writer, targets, config)
     }
     fn enable(&self) -> bool {
         true
@@ -41,13 +64,21 @@ impl ToteArchiver for Archiver {
 impl ToteArchiver for GzArchiver {
     fn perform(
         &self,
-        file: File,
+        writer: Box<dyn WriteSeek + '_>,
+        targets: &[PathBuf],
+        config: &crate::ArchiveConfig,
+    ) -> Result<Vec<ArchiveEntry>> {
+        self.perform_stream(writer, targets, config)
+    }
+    fn perform_stream(
+        &self,
+        writer: Box<dyn Write + '_>,
         targets: &[PathBuf],
         config: &crate::ArchiveConfig,
     ) -> Result<Vec<ArchiveEntry>> {
         let level = config.level as u32;
         write_tar(
-            GzEncoder::new(file, flate2::Compression::new(level)),
+            GzEncoder::new(writer, flate2::Compression::new(level)),
             targets,
             config,
         )
@@ -60,13 +91,21 @@ impl ToteArchiver for GzArchiver {
 impl ToteArchiver for Bz2Archiver {
     fn perform(
         &self,
-        file: File,
+        writer: Box<dyn WriteSeek + '_>,
+        targets: &[PathBuf],
+        config: &crate::ArchiveConfig,
+    ) -> Result<Vec<ArchiveEntry>> {
+        self.perform_stream(writer, targets, config)
+    }
+    fn perform_stream(
+        &self,
+        writer: Box<dyn Write + '_>,
         targets: &[PathBuf],
         config: &crate::ArchiveConfig,
     ) -> Result<Vec<ArchiveEntry>> {
         let level = config.level as u32;
         write_tar(
-            BzEncoder::new(file, bzip2::Compression::new(level)),
+            BzEncoder::new(writer, bzip2::Compression::new(level)),
             targets,
             config,
         )
@@ -79,12 +118,20 @@ impl ToteArchiver for Bz2Archiver {
 impl ToteArchiver for XzArchiver {
     fn perform(
         &self,
-        file: File,
+        writer: Box<dyn WriteSeek + '_>,
+        targets: &[PathBuf],
+        config: &crate::ArchiveConfig,
+    ) -> Result<Vec<ArchiveEntry>> {
+        self.perform_stream(writer, targets, config)
+    }
+    fn perform_stream(
+        &self,
+        writer: Box<dyn Write + '_>,
         targets: &[PathBuf],
         config: &crate::ArchiveConfig,
     ) -> Result<Vec<ArchiveEntry>> {
         let level = config.level as u32;
-        write_tar(XzEncoder::new(file, level), targets, config)
+        write_tar(XzEncoder::new(writer, level), targets, config)
     }
     fn enable(&self) -> bool {
         true
@@ -94,16 +141,154 @@ impl ToteArchiver for XzArchiver {
 impl ToteArchiver for ZstdArchiver {
     fn perform(
         &self,
-        file: File,
+        writer: Box<dyn WriteSeek + '_>,
+        targets: &[PathBuf],
+        config: &crate::ArchiveConfig,
+    ) -> Result<Vec<ArchiveEntry>> {
+        self.perform_stream(writer, targets, config)
+    }
+    fn perform_stream(
+        &self,
+        writer: Box<dyn Write + '_>,
         targets: &[PathBuf],
         config: &crate::ArchiveConfig,
     ) -> Result<Vec<ArchiveEntry>> {
         let level = config.level as u32;
         let level = (level as f64 + 1.0) / 10.0 * 22.0; // convert to 1-22
-        let encoder = zstd::Encoder::new(file, level as i32).unwrap();
-        write_tar(encoder.auto_finish(), targets, config)
+        let encoder = zstd::Encoder::new(writer, level as i32).map_err(Error::IO)?;
+        write_tar_then(encoder, targets, config, |encoder| {
+            encoder.finish().map(|_| ()).map_err(std::io::Error::other)
+        })
+    }
+    fn enable(&self) -> bool {
+        true
+    }
+}
+
+impl ToteArchiver for Lz4Archiver {
+    fn perform(
+        &self,
+        writer: Box<dyn WriteSeek + '_>,
+        targets: &[PathBuf],
+        config: &crate::ArchiveConfig,
+    ) -> Result<Vec<ArchiveEntry>> {
+        self.perform_stream(writer, targets, config)
+    }
+    fn perform_stream(
+        &self,
+        writer: Box<dyn Write + '_>,
+        targets: &[PathBuf],
+        config: &crate::ArchiveConfig,
+    ) -> Result<Vec<ArchiveEntry>> {
+        // lz4 has no compression levels, therefore, `config.level` is ignored.
+        let encoder = lz4_flex::frame::FrameEncoder::new(writer);
+        write_tar_then(encoder, targets, config, |encoder| {
+            encoder.finish().map(|_| ()).map_err(std::io::Error::other)
+        })
+    }
+    fn enable(&self) -> bool {
+        true
+    }
+}
+
+impl ToteArchiver for LzmaArchiver {
+    fn perform(
+        &self,
+        writer: Box<dyn WriteSeek + '_>,
+        targets: &[PathBuf],
+        config: &crate::ArchiveConfig,
+    ) -> Result<Vec<ArchiveEntry>> {
+        self.perform_stream(writer, targets, config)
+    }
+    fn perform_stream(
+        &self,
+        writer: Box<dyn Write + '_>,
+        targets: &[PathBuf],
+        config: &crate::ArchiveConfig,
+    ) -> Result<Vec<ArchiveEntry>> {
+        let level = config.level as u32;
+        let stream = xz2::stream::LzmaOptions::new_preset(level)
+            .and_then(|options| xz2::stream::Stream::new_lzma_encoder(&options))
+            .map_err(|e| Error::Archiver(e.to_string()))?;
+        write_tar(XzEncoder::new_stream(writer, stream), targets, config)
+    }
+    fn enable(&self) -> bool {
+        true
     }
+}
 
+impl ToteArchiver for LzArchiver {
+    fn perform(
+        &self,
+        writer: Box<dyn WriteSeek + '_>,
+        targets: &[PathBuf],
+        config: &crate::ArchiveConfig,
+    ) -> Result<Vec<ArchiveEntry>> {
+        self.perform_stream(writer, targets, config)
+    }
+    fn perform_stream(
+        &self,
+        writer: Box<dyn Write + '_>,
+        targets: &[PathBuf],
+        config: &crate::ArchiveConfig,
+    ) -> Result<Vec<ArchiveEntry>> {
+        let level = config.level as u32;
+        let encoder = LzipEncoder::new(writer, level).map_err(Error::IO)?;
+        write_tar_then(encoder, targets, config, |mut encoder| encoder.finish())
+    }
+    fn enable(&self) -> bool {
+        true
+    }
+}
+
+impl ToteArchiver for ZArchiver {
+    fn perform(
+        &self,
+        writer: Box<dyn WriteSeek + '_>,
+        targets: &[PathBuf],
+        config: &crate::ArchiveConfig,
+    ) -> Result<Vec<ArchiveEntry>> {
+        self.perform_stream(writer, targets, config)
+    }
+    fn perform_stream(
+        &self,
+        writer: Box<dyn Write + '_>,
+        targets: &[PathBuf],
+        config: &crate::ArchiveConfig,
+    ) -> Result<Vec<ArchiveEntry>> {
+        // compress always uses the 16 bits codes, therefore, `config.level` is ignored.
+        write_tar_then(LzwEncoder::new(writer), targets, config, |mut encoder| encoder.finish())
+    }
+    fn enable(&self) -> bool {
+        true
+    }
+}
+
+impl ToteArchiver for BrArchiver {
+    fn perform(
+        &self,
+        writer: Box<dyn WriteSeek + '_>,
+        targets: &[PathBuf],
+        config: &crate::ArchiveConfig,
+    ) -> Result<Vec<ArchiveEntry>> {
+        self.perform_stream(writer, targets, config)
+    }
+    fn perform_stream(
+        &self,
+        writer: Box<dyn Write + '_>,
+        targets: &[PathBuf],
+        config: &crate::ArchiveConfig,
+    ) -> Result<Vec<ArchiveEntry>> {
+        let level = config.level as u32;
+        let quality = level * 11 / 9; // convert to 0-11
+        let lgwin = (16 + level).min(24); // the window size from 64KiB (level 0) to 16MiB (level 8 and 9)
+        let encoder = brotli::CompressorWriter::new(writer, 4096, quality, lgwin);
+        // `into_inner` ignores the errors, so the pending data are flushed beforehand.
+        write_tar_then(encoder, targets, config, |mut encoder| {
+            encoder.flush()?;
+            encoder.into_inner().flush()
+        })
+    }
     fn enable(&self) -> bool {
         true
     }
@@ -114,7 +299,22 @@ fn write_tar<W: Write>(
     targets: &[PathBuf],
     config: &crate::ArchiveConfig,
 ) -> Result<Vec<ArchiveEntry>> {
+    write_tar_then(f, targets, config, |_| Ok(()))
+}
+
+/// Writes the tar archive into `f`, and then finishes `f` by `finish`, e.g., writing the trailer of the compressed stream.
+fn write_tar_then<W: Write, F>(
+    f: W,
+    targets: &[PathBuf],
+    config: &crate::ArchiveConfig,
+    finish: F,
+) -> Result<Vec<ArchiveEntry>>
+where
+    F: FnOnce(W) -> std::io::Result<()>,
+{
     let mut builder = tar::Builder::new(f);
+    builder.follow_symlinks(config.follow_links);
+    let mut links = Links::new(config);
     let mut errs = vec![];
     let mut entries = vec![];
     for tp in targets {
@@ -122,18 +322,25 @@ fn write_tar<W: Write>(
             let path = entry.into_path();
             entries.push(ArchiveEntry::from(&path));
             let dest_dir = config.path_in_archive(&path);
-            if path.is_file() {
-                if let Err(e) = process_file(&mut builder, &path, &dest_dir) {
-                    errs.push(e);
+            let r = match links.classify(&path, &dest_dir) {
+                Ok(Some(Target::File)) => process_file(&mut builder, &path, &dest_dir),
+                Ok(Some(Target::Dir)) => builder.append_dir(&dest_dir, &path)
+                    .map_err(|e| Error::Archiver(e.to_string())),
+                Ok(Some(Target::Symlink(target))) => {
+                    process_link(&mut builder, &path, &dest_dir, &target, tar::EntryType::Symlink)
                 }
-            } else if path.is_dir() {
-                if let Err(e) = builder.append_dir(&dest_dir, &path) {
-                    errs.push(Error::Archiver(e.to_string()));
+                Ok(Some(Target::Hardlink(original))) => {
+                    process_link(&mut builder, &path, &dest_dir, &original, tar::EntryType::Link)
                 }
+                Ok(None) => Ok(()),
+                Err(e) => Err(e),
+            };
+            if let Err(e) = r {
+                errs.push(e);
             }
         }
     }
-    if let Err(e) = builder.finish() {
+    if let Err(e) = builder.into_inner().and_then(finish) {
         errs.push(Error::Archiver(e.to_string()));
     }
     Error::error_or(entries, errs)
@@ -151,6 +358,22 @@ fn process_file<W: Write>(
     }
 }
 
+fn process_link<W: Write>(
+    builder: &mut Builder<W>,
+    target: &Path,
+    dest_path: &Path,
+    link_name: &Path,
+    entry_type: tar::EntryType,
+) -> Result<()> {
+    let metadata = target.symlink_metadata().map_err(Error::IO)?;
+    let mut header = tar::Header::new_gnu();
+    header.set_metadata(&metadata);
+    header.set_entry_type(entry_type);
+    header.set_size(0);
+    builder.append_link(&mut header, dest_path, link_name)
+        .map_err(|e| Error::Archiver(e.to_string()))
+}
+
 #[cfg(test)]
 mod tests {
     use std::path::PathBuf;
@@ -174,9 +397,9 @@ mod tests {
                 .dest("results/test.tar")
                 .overwrite(true)
                 .build();
-            let v = vec!["lib", "cli", "Cargo.toml"]
+            let v = ["lib", "cli", "Cargo.toml"]
                 .iter()
-                .map(|s| PathBuf::from(s))
+                .map(PathBuf::from)
                 .collect::<Vec<_>>();
             let result = crate::archive(&v, &config);
             let path = PathBuf::from("results/test.tar");
@@ -196,9 +419,9 @@ mod tests {
                 .dest("results/test.tar.gz")
                 .overwrite(true)
                 .build();
-            let v = vec!["lib", "cli", "Cargo.toml"]
+            let v = ["lib", "cli", "Cargo.toml"]
                 .iter()
-                .map(|s| PathBuf::from(s))
+                .map(PathBuf::from)
                 .collect::<Vec<_>>();
             let result = crate::archive(&v, &config);
             let path = PathBuf::from("results/test.tar.gz");
@@ -215,9 +438,9 @@ mod tests {
                 .dest("results/test.tar.bz2")
                 .overwrite(true)
                 .build();
-            let v = vec!["lib", "cli", "Cargo.toml"]
+            let v = ["lib", "cli", "Cargo.toml"]
                 .iter()
-                .map(|s| PathBuf::from(s))
+                .map(PathBuf::from)
                 .collect::<Vec<_>>();
             let result = crate::archive(&v, &config);
             let path = PathBuf::from("results/test.tar.bz2");
@@ -234,9 +457,9 @@ mod tests {
                 .dest("results/test.tar.xz")
                 .overwrite(true)
                 .build();
-            let v = vec!["lib", "cli", "Cargo.toml"]
+            let v = ["lib", "cli", "Cargo.toml"]
                 .iter()
-                .map(|s| PathBuf::from(s))
+                .map(PathBuf::from)
                 .collect::<Vec<_>>();
             let result = crate::archive(&v, &config);
             let path = PathBuf::from("results/test.tar.xz");
@@ -253,9 +476,9 @@ mod tests {
                 .dest("results/test.tar.zst")
                 .overwrite(true)
                 .build();
-            let v = vec!["lib", "cli", "Cargo.toml"]
+            let v = ["lib", "cli", "Cargo.toml"]
                 .iter()
-                .map(|s| PathBuf::from(s))
+                .map(PathBuf::from)
                 .collect::<Vec<_>>();
             let result = crate::archive(&v, &config);
             let path = PathBuf::from("results/test.tar.zst");
@@ -265,7 +488,142 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_tarlz4() {
+        run_test(|| {
+            let config = crate::ArchiveConfig::builder()
+                .dest("results/test.tar.lz4")
+                .overwrite(true)
+                .build();
+            let v = ["lib", "cli", "Cargo.toml"]
+                .iter()
+                .map(PathBuf::from)
+                .collect::<Vec<_>>();
+            let result = crate::archive(&v, &config);
+            let path = PathBuf::from("results/test.tar.lz4");
+            assert!(result.is_ok());
+            assert!(path.exists());
+            path
+        });
+    }
+
+    #[test]
+    fn test_tarbr() {
+        run_test(|| {
+            let config = crate::ArchiveConfig::builder()
+                .dest("results/test.tar.br")
+                .overwrite(true)
+                .build();
+            let v = ["lib", "cli", "Cargo.toml"]
+                .iter()
+                .map(PathBuf::from)
+                .collect::<Vec<_>>();
+            let result = crate::archive(&v, &config);
+            let path = PathBuf::from("results/test.tar.br");
+            assert!(result.is_ok());
+            assert!(path.exists());
+            path
+        });
+    }
+
+    #[test]
+    fn test_tarlzma() {
+        run_test(|| {
+            let config = crate::ArchiveConfig::builder()
+                .dest("results/test.tar.lzma")
+                .overwrite(true)
+                .build();
+            let v = ["lib", "cli", "Cargo.toml"]
+                .iter()
+                .map(PathBuf::from)
+                .collect::<Vec<_>>();
+            let result = crate::archive(&v, &config);
+            let path = PathBuf::from("results/test.tar.lzma");
+            assert!(result.is_ok());
+            assert!(path.exists());
+            path
+        });
+    }
+
+    #[test]
+    fn test_tarlz() {
+        run_test(|| {
+            let config = crate::ArchiveConfig::builder()
+                .dest("results/test.tar.lz")
+                .overwrite(true)
+                .build();
+            let v = ["lib", "cli", "Cargo.toml"]
+                .iter()
+                .map(PathBuf::from)
+                .collect::<Vec<_>>();
+            let result = crate::archive(&v, &config);
+            let path = PathBuf::from("results/test.tar.lz");
+            assert!(result.is_ok());
+            assert!(path.exists());
+            path
+        });
+    }
+
+    #[test]
+    fn test_tarz() {
+        run_test(|| {
+            let config = crate::ArchiveConfig::builder()
+                .dest("results/test.tar.Z")
+                .overwrite(true)
+                .build();
+            let v = ["lib", "cli", "Cargo.toml"]
+                .iter()
+                .map(PathBuf::from)
+                .collect::<Vec<_>>();
+            let result = crate::archive(&v, &config);
+            let path = PathBuf::from("results/test.tar.Z");
+            assert!(result.is_ok());
+            assert!(path.exists());
+            path
+        });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_links() {
+        crate::archiver::assert_links_roundtrip("results/links.tar", true);
+    }
+
     fn teardown(path: PathBuf) {
         let _ = std::fs::remove_file(path);
     }
+
+    #[test]
+    fn test_finish_error() {
+        use crate::archiver::ToteArchiver;
+
+        /// The writer which fails after the given number of bytes.
+        struct LimitedWriter(usize);
+
+        impl std::io::Write for LimitedWriter {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                if self.0 < buf.len() {
+                    // lz4_flex panics with the custom errors, so the error must be a plain kind.
+                    return Err(std::io::ErrorKind::StorageFull.into());
+                }
+                self.0 -= buf.len();
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        // the compressed data are written when the stream is finished.
+        let config = crate::ArchiveConfig::builder().build();
+        let targets = vec![PathBuf::from("Cargo.toml")];
+        let r = super::LzArchiver {}.perform_stream(Box::new(LimitedWriter(16)), &targets, &config);
+        assert!(r.is_err());
+        let r = super::ZArchiver {}.perform_stream(Box::new(LimitedWriter(16)), &targets, &config);
+        assert!(r.is_err());
+        let r = super::Lz4Archiver {}.perform_stream(Box::new(LimitedWriter(16)), &targets, &config);
+        assert!(r.is_err());
+        let r = super::BrArchiver {}.perform_stream(Box::new(LimitedWriter(16)), &targets, &config);
+        assert!(r.is_err());
+    }
 }
@@ -5,11 +5,11 @@ use crate::archiver::os;
 use crate::archiver::os;
 
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{BufReader, Seek, Write};
 use std::path::{Path, PathBuf};
 use zip::ZipWriter;
 
-use crate::archiver::{ArchiveEntry, ToteArchiver};
+use crate::archiver::{ArchiveEntry, Links, Target, ToteArchiver, WriteSeek};
 use crate::{Result, Error};
 
 /// ZIP format archiver implementation.
@@ -21,9 +21,9 @@ impl Archiver {
     pub fn new() -> Self {
         Self {}
     }
-    fn process_file(
+    fn process_file<W: Write + Seek>(
         &self,
-        zw: &mut ZipWriter<File>,
+        zw: &mut ZipWriter<W>,
         target: &Path,
         dest_path: PathBuf,
         level: u8,
@@ -40,31 +40,54 @@ impl Archiver {
             }
         }
     }
-}
 
-impl ToteArchiver for Archiver {
-    fn perform(
+    fn process_dir<W: Write + Seek>(&self, zw: &mut ZipWriter<W>, target: &Path, dest_path: PathBuf) -> Result<()> {
+        if dest_path.components().all(|c| c == std::path::Component::CurDir) {
+            return Ok(());
+        }
+        let opts = os::create_entry_opts(target);
+        zw.add_directory(dest_path.to_string_lossy(), opts)
+            .map_err(|e| Error::Archiver(e.to_string()))
+    }
+
+    fn process_symlink<W: Write + Seek>(
         &self,
-        file: File,
+        zw: &mut ZipWriter<W>,
+        target: &Path,
+        dest_path: PathBuf,
+        link_target: &Path,
+    ) -> Result<()> {
+        let opts = os::create_entry_opts(target);
+        zw.add_symlink(dest_path.to_string_lossy(), link_target.to_string_lossy(), opts)
+            .map_err(|e| Error::Archiver(e.to_string()))
+    }
+
+    fn write_zip<W: Write + Seek>(
+        &self,
+        mut zw: ZipWriter<W>,
         targets: &[PathBuf],
         config: &crate::ArchiveConfig,
     ) -> Result<Vec<ArchiveEntry>> {
         let mut errs = vec![];
-        let mut zw = zip::ZipWriter::new(file);
+        let mut links = Links::new(config);
         let mut entries = vec![];
         for tp in targets.iter() {
             for entry in config.iter(tp) {
                 let path = entry.path().to_path_buf();
                 entries.push(ArchiveEntry::from(&path));
-                if path.is_file() {
-                    if let Err(e) = self.process_file(
-                        &mut zw,
-                        &path,
-                        config.path_in_archive(&path),
-                        config.level,
-                    ) {
-                        errs.push(e);
+                let dest_path = config.path_in_archive(&path);
+                // zip has no hard links, therefore, the hard links are stored as the regular files.
+                let r = match links.classify(&path, &dest_path) {
+                    Ok(Some(Target::File | Target::Hardlink(_))) => {
+                        self.process_file(&mut zw, &path, dest_path, config.level)
                     }
+                    Ok(Some(Target::Symlink(target))) => self.process_symlink(&mut zw, &path, dest_path, &target),
+                    Ok(Some(Target::Dir)) => self.process_dir(&mut zw, &path, dest_path),
+                    Ok(None) => Ok(()),
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = r {
+                    errs.push(e);
                 }
             }
         }
@@ -76,6 +99,28 @@ impl ToteArchiver for Archiver {
             }
         }
     }
+}
+
+impl ToteArchiver for Archiver {
+    fn perform(
+        &self,
+        writer: Box<dyn WriteSeek + '_>,
+        targets: &[PathBuf],
+        config: &crate::ArchiveConfig,
+    ) -> Result<Vec<ArchiveEntry>> {
+        self.write_zip(ZipWriter::new(writer), targets, config)
+    }
+
+    /// Writes the zip archive with the data descriptors, since the local file headers cannot be updated
+    /// after writing the entry data.
+    fn perform_stream(
+        &self,
+        writer: Box<dyn Write + '_>,
+        targets: &[PathBuf],
+        config: &crate::ArchiveConfig,
+    ) -> Result<Vec<ArchiveEntry>> {
+        self.write_zip(ZipWriter::new_stream(writer), targets, config)
+    }
 
     fn enable(&self) -> bool {
         true
@@ -109,7 +154,7 @@ mod tests {
                 .build();
             let v = vec!["lib", "cli", "Cargo.toml"]
                 .into_iter()
-                .map(|s| PathBuf::from(s))
+                .map(PathBuf::from)
                 .collect::<Vec<_>>();
             if let Err(e) = crate::archive(&v, &config) {
                 panic!("{e:?}")
@@ -117,6 +162,12 @@ mod tests {
         });
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn test_links() {
+        crate::archiver::assert_links_roundtrip("results/links.zip", false);
+    }
+
     fn teardown() {
         let _ = std::fs::remove_file("results/test.zip");
     }
@@ -0,0 +1,14 @@
+//! The compression codecs which have no suitable crates,
+//! used by the archivers and the extractors of the tar family, and by the lha archiver.
+
+pub(crate) use lh5::Lh5Encoder;
+pub(crate) use lzip::{LzipDecoder, LzipEncoder};
+pub(crate) use lzw::{LzwDecoder, LzwEncoder};
+
+mod lh5;
+mod lzip;
+mod lzw;
+
+fn invalid_data<S: Into<String>>(message: S) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message.into())
+}
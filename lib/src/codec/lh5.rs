@@ -0,0 +1,469 @@
+//! The encoder of the `-lh5-` method of LHA (LZSS with the static Huffman coding).
+//!
+//! The data is split into the blocks, and each block starts with the number of its codes
+//! and the Huffman tables of the literals and the lengths, and of the positions.
+//! The decoder of `-lh5-` is provided by `delharc`, so only the encoder lives here.
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::io::Write;
+
+/// the size of the dictionary (8 KiB).
+const DICSIZ: usize = 1 << 13;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 256;
+/// the number of the literals and the lengths (256 + 254).
+const NC: usize = 256 + MAX_MATCH - MIN_MATCH + 1;
+/// the number of the position codes (0 to 13 bits).
+const NP: usize = 14;
+/// the number of the codes for the lengths of the literal and length table.
+const NT: usize = 19;
+const CBIT: u32 = 9;
+const PBIT: u32 = 4;
+const TBIT: u32 = 5;
+const MAX_CODE_LENGTH: u8 = 16;
+/// the number of the codes in a block, which must fit in 16 bits.
+const BLOCK_CODES: usize = 0x4000;
+const HASH_BITS: u32 = 15;
+const NIL: usize = usize::MAX;
+
+/// A literal (`< 256`) or a length code with the position (distance - 1) of the match.
+#[derive(Clone, Copy)]
+struct Token {
+    code: u16,
+    position: u16,
+}
+
+/// The bit writer packing the bits from the most significant bit.
+#[derive(Default)]
+struct BitWriter {
+    out: Vec<u8>,
+    acc: u32,
+    acc_bits: u32,
+}
+
+impl BitWriter {
+    fn put(&mut self, bits: u32, value: u32) {
+        for i in (0..bits).rev() {
+            self.acc = (self.acc << 1) | ((value >> i) & 1);
+            self.acc_bits += 1;
+            if self.acc_bits == 8 {
+                self.out.push(self.acc as u8);
+                self.acc = 0;
+                self.acc_bits = 0;
+            }
+        }
+    }
+
+    fn pad(&mut self) {
+        if self.acc_bits > 0 {
+            self.put(8 - self.acc_bits, 0);
+        }
+    }
+}
+
+/// The encoder of the `-lh5-` stream.
+/// `level` (1 to 9) determines how long the hash chains are searched for the matches.
+/// The stream is finished by [`Lh5Encoder::finish`], or when this encoder is dropped
+/// (the errors are only logged in this case).
+pub(crate) struct Lh5Encoder<W: Write> {
+    writer: W,
+    /// the input data from the absolute position `start`.
+    buf: Vec<u8>,
+    start: usize,
+    pos: usize,
+    head: Vec<usize>,
+    prev: Vec<usize>,
+    max_chain: usize,
+    tokens: Vec<Token>,
+    bits: BitWriter,
+    finished: bool,
+}
+
+impl<W: Write> Lh5Encoder<W> {
+    pub(crate) fn new(writer: W, level: u8) -> Self {
+        Self {
+            writer,
+            buf: Vec::new(),
+            start: 0,
+            pos: 0,
+            head: vec![NIL; 1 << HASH_BITS],
+            prev: vec![NIL; DICSIZ],
+            max_chain: 4 << level.clamp(1, 9),
+            tokens: Vec::with_capacity(BLOCK_CODES),
+            bits: BitWriter::default(),
+            finished: false,
+        }
+    }
+
+    fn end(&self) -> usize {
+        self.start + self.buf.len()
+    }
+
+    fn byte(&self, pos: usize) -> u8 {
+        self.buf[pos - self.start]
+    }
+
+    fn hash(&self, pos: usize) -> usize {
+        let i = pos - self.start;
+        let v = (self.buf[i] as u32) << 16 | (self.buf[i + 1] as u32) << 8 | self.buf[i + 2] as u32;
+        (v.wrapping_mul(0x9e3779b1) >> (32 - HASH_BITS)) as usize
+    }
+
+    fn insert(&mut self, pos: usize) {
+        if pos + MIN_MATCH <= self.end() {
+            let h = self.hash(pos);
+            self.prev[pos % DICSIZ] = self.head[h];
+            self.head[h] = pos;
+        }
+    }
+
+    /// Returns the length and the distance of the longest match at the given position.
+    fn longest_match(&self, pos: usize) -> (usize, usize) {
+        let limit = MAX_MATCH.min(self.end() - pos);
+        if limit < MIN_MATCH {
+            return (0, 0);
+        }
+        let data = &self.buf[pos - self.start..pos - self.start + limit];
+        let mut best = (0, 0);
+        let mut candidate = self.head[self.hash(pos)];
+        let mut chain = self.max_chain;
+        while candidate != NIL && candidate < pos && pos - candidate < DICSIZ && chain > 0 {
+            let from = &self.buf[candidate - self.start..];
+            let len = data.iter().zip(from).take_while(|(a, b)| a == b).count();
+            if len > best.0 {
+                best = (len, pos - candidate);
+                if len == limit {
+                    break;
+                }
+            }
+            let next = self.prev[candidate % DICSIZ];
+            if next == NIL || next >= candidate {
+                break;
+            }
+            candidate = next;
+            chain -= 1;
+        }
+        if best.0 >= MIN_MATCH { best } else { (0, 0) }
+    }
+
+    /// Encodes the buffered data, keeping `MAX_MATCH + 1` bytes of look-ahead unless `flush`.
+    fn compress(&mut self, flush: bool) -> std::io::Result<()> {
+        let lookahead = if flush { 0 } else { MAX_MATCH + 1 };
+        while self.pos + lookahead < self.end() {
+            let (len, distance) = self.longest_match(self.pos);
+            // defers the match when the next position has the longer one (lazy matching).
+            let deferred = len > 0 && len < MAX_MATCH && self.longest_match(self.pos + 1).0 > len;
+            if len == 0 || deferred {
+                self.push(Token { code: self.byte(self.pos) as u16, position: 0 })?;
+                self.insert(self.pos);
+                self.pos += 1;
+            } else {
+                let code = (len - MIN_MATCH + 256) as u16;
+                self.push(Token { code, position: (distance - 1) as u16 })?;
+                for p in self.pos..self.pos + len {
+                    self.insert(p);
+                }
+                self.pos += len;
+            }
+        }
+        if self.pos - self.start > 2 * DICSIZ {
+            let drop = self.pos - DICSIZ - self.start;
+            self.buf.drain(..drop);
+            self.start += drop;
+        }
+        Ok(())
+    }
+
+    fn push(&mut self, token: Token) -> std::io::Result<()> {
+        self.tokens.push(token);
+        if self.tokens.len() == BLOCK_CODES {
+            self.write_block()?;
+        }
+        Ok(())
+    }
+
+    fn write_block(&mut self) -> std::io::Result<()> {
+        if self.tokens.is_empty() {
+            return Ok(());
+        }
+        let mut c_freq = [0u32; NC];
+        let mut p_freq = [0u32; NP];
+        for t in self.tokens.iter() {
+            c_freq[t.code as usize] += 1;
+            if t.code >= 256 {
+                p_freq[bit_length(t.position) as usize] += 1;
+            }
+        }
+        let bits = &mut self.bits;
+        bits.put(16, self.tokens.len() as u32);
+
+        let c_len = code_lengths(&c_freq);
+        if let Some(single) = single_symbol(&c_freq) {
+            bits.put(TBIT, 0);
+            bits.put(TBIT, 0);
+            bits.put(CBIT, 0);
+            bits.put(CBIT, single as u32);
+        } else {
+            let items = c_len_items(&c_len);
+            let mut t_freq = [0u32; NT];
+            items.iter().for_each(|(t, _, _)| t_freq[*t] += 1);
+            let t_len = code_lengths(&t_freq);
+            if let Some(single) = single_symbol(&t_freq) {
+                bits.put(TBIT, 0);
+                bits.put(TBIT, single as u32);
+            } else {
+                write_pt_len(bits, &t_len, TBIT, true);
+            }
+            let t_code = canonical_codes(&t_len);
+            bits.put(CBIT, used_length(&c_len) as u32);
+            for (t, extra_bits, extra) in items {
+                bits.put(t_len[t] as u32, t_code[t] as u32);
+                bits.put(extra_bits, extra);
+            }
+        }
+
+        let p_len = code_lengths(&p_freq);
+        if let Some(single) = single_symbol(&p_freq) {
+            bits.put(PBIT, 0);
+            bits.put(PBIT, single as u32);
+        } else {
+            write_pt_len(bits, &p_len, PBIT, false);
+        }
+
+        let c_code = canonical_codes(&c_len);
+        let p_code = canonical_codes(&p_len);
+        for t in self.tokens.drain(..) {
+            let c = t.code as usize;
+            bits.put(c_len[c] as u32, c_code[c] as u32);
+            if c >= 256 {
+                let k = bit_length(t.position) as usize;
+                bits.put(p_len[k] as u32, p_code[k] as u32);
+                if k > 1 {
+                    bits.put(k as u32 - 1, t.position as u32 & ((1 << (k - 1)) - 1));
+                }
+            }
+        }
+        self.writer.write_all(&self.bits.out)?;
+        self.bits.out.clear();
+        Ok(())
+    }
+
+    /// Writes the rest of the compressed data and pads the last byte.
+    pub(crate) fn finish(&mut self) -> std::io::Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+        self.finished = true;
+        self.compress(true)?;
+        self.write_block()?;
+        self.bits.pad();
+        self.writer.write_all(&self.bits.out)?;
+        self.bits.out.clear();
+        self.writer.flush()
+    }
+}
+
+impl<W: Write> Write for Lh5Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        self.compress(false)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl<W: Write> Drop for Lh5Encoder<W> {
+    fn drop(&mut self) {
+        if let Err(e) = self.finish() {
+            log::error!("failed to finish the lh5 stream: {e}");
+        }
+    }
+}
+
+/// Returns the number of the bits of the given value (0 for 0).
+fn bit_length(value: u16) -> u32 {
+    16 - value.leading_zeros()
+}
+
+/// Returns the symbol if at most one symbol appears (the tables of a single code).
+fn single_symbol(freq: &[u32]) -> Option<usize> {
+    let mut used = freq.iter().enumerate().filter(|(_, f)| **f > 0);
+    match (used.next(), used.next()) {
+        (None, _) => Some(0),
+        (Some((i, _)), None) => Some(i),
+        _ => None,
+    }
+}
+
+/// Returns the number of the code lengths to be written, i.e., without the trailing zeros.
+fn used_length(lengths: &[u8]) -> usize {
+    lengths.iter().rposition(|l| *l != 0).map_or(0, |i| i + 1)
+}
+
+/// Builds the Huffman code lengths limited to 16 bits.
+/// The frequencies are halved until the lengths fit, so the tree is always complete.
+fn code_lengths(freq: &[u32]) -> Vec<u8> {
+    let mut freq = freq.to_vec();
+    loop {
+        let lengths = huffman_lengths(&freq);
+        if lengths.iter().all(|l| *l <= MAX_CODE_LENGTH) {
+            return lengths;
+        }
+        freq.iter_mut().filter(|f| **f > 0).for_each(|f| *f = (*f >> 1) | 1);
+    }
+}
+
+fn huffman_lengths(freq: &[u32]) -> Vec<u8> {
+    let mut lengths = vec![0u8; freq.len()];
+    let mut parents = vec![usize::MAX; freq.len()];
+    let mut heap = freq.iter().enumerate()
+        .filter(|(_, f)| **f > 0)
+        .map(|(i, f)| Reverse((*f as u64, i)))
+        .collect::<BinaryHeap<_>>();
+    if heap.len() < 2 {
+        return lengths;
+    }
+    while heap.len() > 1 {
+        let Reverse((f1, n1)) = heap.pop().unwrap();
+        let Reverse((f2, n2)) = heap.pop().unwrap();
+        let node = parents.len();
+        parents.push(usize::MAX);
+        parents[n1] = node;
+        parents[n2] = node;
+        heap.push(Reverse((f1 + f2, node)));
+    }
+    for (i, length) in lengths.iter_mut().enumerate() {
+        let mut n = i;
+        let mut depth = 0u32;
+        while parents[n] != usize::MAX {
+            n = parents[n];
+            depth += 1;
+        }
+        *length = depth.min(u8::MAX as u32) as u8;
+    }
+    lengths
+}
+
+/// Assigns the canonical codes, the shorter codes first and the smaller symbols first in the same length.
+fn canonical_codes(lengths: &[u8]) -> Vec<u16> {
+    let mut count = [0u32; MAX_CODE_LENGTH as usize + 1];
+    lengths.iter().filter(|l| **l > 0).for_each(|l| count[*l as usize] += 1);
+    let mut next = [0u32; MAX_CODE_LENGTH as usize + 2];
+    for i in 1..=MAX_CODE_LENGTH as usize {
+        next[i + 1] = (next[i] + count[i]) << 1;
+    }
+    lengths.iter()
+        .map(|l| {
+            if *l == 0 {
+                return 0;
+            }
+            let code = next[*l as usize];
+            next[*l as usize] += 1;
+            code as u16
+        })
+        .collect()
+}
+
+/// Converts the code lengths of the literal and length table into the codes of the `NT` table
+/// with their extra bits; the codes 0 to 2 represent the runs of zeros.
+fn c_len_items(c_len: &[u8]) -> Vec<(usize, u32, u32)> {
+    let n = used_length(c_len);
+    let mut items = vec![];
+    let mut i = 0;
+    while i < n {
+        if c_len[i] != 0 {
+            items.push((c_len[i] as usize + 2, 0, 0));
+            i += 1;
+            continue;
+        }
+        let count = c_len[i..n].iter().take_while(|l| **l == 0).count();
+        i += count;
+        match count {
+            1..=2 => (0..count).for_each(|_| items.push((0, 0, 0))),
+            3..=18 => items.push((1, 4, count as u32 - 3)),
+            19 => {
+                items.push((0, 0, 0));
+                items.push((1, 4, 15));
+            }
+            _ => items.push((2, CBIT, count as u32 - 20)),
+        }
+    }
+    items
+}
+
+/// Writes the code lengths of the `NT` table (`special` is true) or the position table.
+/// The lengths over 6 are written as 7 followed by the unary ones and a zero.
+fn write_pt_len(bits: &mut BitWriter, lengths: &[u8], nbit: u32, special: bool) {
+    let n = used_length(lengths);
+    bits.put(nbit, n as u32);
+    let mut i = 0;
+    while i < n {
+        let k = lengths[i] as u32;
+        i += 1;
+        if k <= 6 {
+            bits.put(3, k);
+        } else {
+            bits.put(k - 3, 0xfffe);
+        }
+        if special && i == 3 {
+            while i < 6 && lengths[i] == 0 {
+                i += 1;
+            }
+            bits.put(2, i as u32 - 3);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Read;
+
+    fn roundtrip(data: &[u8], level: u8) -> Vec<u8> {
+        let mut compressed = vec![];
+        {
+            let mut encoder = Lh5Encoder::new(&mut compressed, level);
+            encoder.write_all(data).unwrap();
+        }
+        let mut decoder = delharc::decode::Lh5Decoder::new(compressed.as_slice());
+        let mut decoded = vec![0u8; data.len()];
+        delharc::decode::Decoder::fill_buffer(&mut decoder, &mut decoded).unwrap();
+        decoded
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        assert_eq!(roundtrip(b"a", 5), b"a");
+        assert_eq!(roundtrip(b"abababababababab", 5), b"abababababababab");
+        assert_eq!(roundtrip(&[0u8; 100_000], 5), vec![0u8; 100_000]);
+        let mut data = vec![];
+        std::fs::File::open("../testdata/test.tar").unwrap().read_to_end(&mut data).unwrap();
+        let mut state = 0x2545f491u32;
+        data.extend((0..100_000).map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        }));
+        for level in [1, 9] {
+            assert!(roundtrip(&data, level) == data);
+        }
+    }
+
+    #[test]
+    fn test_code_lengths() {
+        // the fibonacci frequencies make the deepest tree, which must be limited to 16 bits.
+        let mut freq = vec![1u32, 1];
+        while freq.len() < 30 {
+            freq.push(freq[freq.len() - 1] + freq[freq.len() - 2]);
+        }
+        let lengths = code_lengths(&freq);
+        assert!(lengths.iter().all(|l| (1..=MAX_CODE_LENGTH).contains(l)));
+        let kraft: f64 = lengths.iter().map(|l| 0.5f64.powi(*l as i32)).sum();
+        assert_eq!(kraft, 1.0);
+    }
+}
@@ -0,0 +1,355 @@
+//! The lzip format (`.lz`), which wraps the raw LZMA stream with a header and a trailer.
+//!
+//! A lzip file consists of one or more members, and each member has the following layout.
+//!
+//! - the header: `LZIP`, the version (1), and the coded dictionary size (6 bytes).
+//! - the LZMA stream (lc = 3, lp = 0, pb = 2) terminated by the end of stream marker.
+//! - the trailer: CRC32 and the size of the uncompressed data, and the size of the member (20 bytes).
+//!
+//! The LZMA stream is coded by the `.lzma` (LZMA_Alone) coder of liblzma, since `xz2` has no raw LZMA1 coder.
+//! Its 13 bytes header is given to the decoder before the stream, and is dropped from the output of the encoder.
+use std::io::{Read, Write};
+
+use crc::{Crc, CRC_32_ISO_HDLC};
+use xz2::stream::{Action, LzmaOptions, Status, Stream};
+
+use super::invalid_data;
+
+const CRC32: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+const MAGIC: [u8; 4] = *b"LZIP";
+const VERSION: u8 = 1;
+const HEADER_SIZE: usize = 6;
+const TRAILER_SIZE: usize = 20;
+const BUFFER_SIZE: usize = 64 * 1024;
+/// The size of the header of `.lzma` (LZMA_Alone): the properties, the dictionary size, and the uncompressed size.
+const ALONE_HEADER_SIZE: usize = 13;
+/// The properties byte of lc = 3, lp = 0, and pb = 2, i.e., `(pb * 5 + lp) * 9 + lc`.
+const PROPERTIES: u8 = 0x5d;
+
+/// The dictionary sizes for the compression levels (0-9), the same as `lzip -0` to `lzip -9`.
+const DICT_SIZES: [u32; 10] = [
+    1 << 16, 1 << 20, 3 << 19, 1 << 21, 3 << 20, 1 << 22, 1 << 23, 1 << 24, 3 << 23, 1 << 25,
+];
+
+fn decode_dict_size(coded: u8) -> Option<u32> {
+    let base_log = (coded & 0x1f) as u32;
+    if !(12..=29).contains(&base_log) {
+        return None;
+    }
+    let base = 1u32 << base_log;
+    Some(base - (base / 16) * (coded >> 5) as u32)
+}
+
+fn encode_dict_size(size: u32) -> u8 {
+    let base_log = size.next_power_of_two().trailing_zeros().clamp(12, 29);
+    let base = 1u32 << base_log;
+    let fraction = ((base - size.min(base)) / (base / 16)).min(7);
+    (fraction << 5) as u8 | base_log as u8
+}
+
+fn lzma1_options(dict_size: u32, level: u32) -> std::io::Result<LzmaOptions> {
+    let mut options = LzmaOptions::new_preset(level)?;
+    options.dict_size(dict_size)
+        .literal_context_bits(3)
+        .literal_position_bits(0)
+        .position_bits(2);
+    Ok(options)
+}
+
+/// Returns the decoder of the LZMA stream, which has already read the `.lzma` header of the unknown size.
+fn lzma1_decoder(dict_size: u32) -> std::io::Result<Stream> {
+    let mut header = [0xffu8; ALONE_HEADER_SIZE];
+    header[0] = PROPERTIES;
+    header[1..5].copy_from_slice(&dict_size.to_le_bytes());
+    let mut stream = Stream::new_lzma_decoder(u64::MAX)?;
+    let mut out = [0u8; 1];
+    while (stream.total_in() as usize) < ALONE_HEADER_SIZE {
+        let total_in = stream.total_in() as usize;
+        stream.process(&header[total_in..], &mut out, Action::Run)?;
+        if stream.total_out() > 0 || stream.total_in() as usize == total_in {
+            return Err(invalid_data("invalid lzma header"));
+        }
+    }
+    Ok(stream)
+}
+
+fn le_u64(bytes: &[u8]) -> u64 {
+    bytes.iter().rev().fold(0, |acc, &b| (acc << 8) | b as u64)
+}
+
+/// The member of lzip file in decoding.
+struct Member {
+    stream: Stream,
+    digest: crc::Digest<'static, u32>,
+    size: u64,
+}
+
+/// The decoder of the lzip stream. The multiple members are decoded in sequence.
+pub(crate) struct LzipDecoder<R: Read> {
+    reader: R,
+    buf: Vec<u8>,
+    start: usize,
+    end: usize,
+    member: Option<Member>,
+    members: usize,
+    done: bool,
+}
+
+impl<R: Read> LzipDecoder<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buf: vec![0; BUFFER_SIZE],
+            start: 0,
+            end: 0,
+            member: None,
+            members: 0,
+            done: false,
+        }
+    }
+
+    /// Buffers at least `n` bytes, and returns `false` if the stream ends before that.
+    fn fill(&mut self, n: usize) -> std::io::Result<bool> {
+        if self.end - self.start >= n {
+            return Ok(true);
+        }
+        self.buf.copy_within(self.start..self.end, 0);
+        self.end -= self.start;
+        self.start = 0;
+        while self.end < n {
+            match self.reader.read(&mut self.buf[self.end..]) {
+                Ok(0) => return Ok(false),
+                Ok(len) => self.end += len,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(true)
+    }
+
+    /// Starts decoding the next member, and returns `false` if no more members exist.
+    /// The trailing data after the last member is ignored, as `lzip` does.
+    fn start_member(&mut self) -> std::io::Result<bool> {
+        let filled = self.fill(HEADER_SIZE)?;
+        let header = &self.buf[self.start..self.end];
+        if !filled || header[..4] != MAGIC {
+            if self.members > 0 {
+                return Ok(false);
+            }
+            return Err(invalid_data("not a lzip stream"));
+        }
+        if header[4] != VERSION {
+            return Err(invalid_data(format!("unsupported lzip version: {}", header[4])));
+        }
+        let dict_size = decode_dict_size(header[5])
+            .ok_or_else(|| invalid_data("invalid dictionary size"))?;
+        self.start += HEADER_SIZE;
+        let stream = lzma1_decoder(dict_size)?;
+        self.member = Some(Member {
+            stream,
+            digest: CRC32.digest(),
+            size: 0,
+        });
+        Ok(true)
+    }
+
+    fn finish_member(&mut self, member: Member) -> std::io::Result<()> {
+        if !self.fill(TRAILER_SIZE)? {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "truncated lzip trailer",
+            ));
+        }
+        let trailer = &self.buf[self.start..self.start + TRAILER_SIZE];
+        let crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+        if crc != member.digest.finalize() {
+            return Err(invalid_data("CRC mismatch of lzip member"));
+        }
+        if le_u64(&trailer[4..12]) != member.size {
+            return Err(invalid_data("data size mismatch of lzip member"));
+        }
+        self.start += TRAILER_SIZE;
+        self.members += 1;
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for LzipDecoder<R> {
+    fn read(&mut self, out: &mut [u8]) -> std::io::Result<usize> {
+        if out.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            if self.done {
+                return Ok(0);
+            }
+            if self.member.is_none() && !self.start_member()? {
+                self.done = true;
+                return Ok(0);
+            }
+            let input_ended = self.start == self.end && !self.fill(1)?;
+            let Some(member) = self.member.as_mut() else {
+                continue;
+            };
+            let (total_in, total_out) = (member.stream.total_in(), member.stream.total_out());
+            let status = member.stream.process(&self.buf[self.start..self.end], out, Action::Run)?;
+            let consumed = (member.stream.total_in() - total_in) as usize;
+            let produced = (member.stream.total_out() - total_out) as usize;
+            self.start += consumed;
+            member.digest.update(&out[..produced]);
+            member.size += produced as u64;
+            let stream_end = matches!(status, Status::StreamEnd);
+            if stream_end && let Some(member) = self.member.take() {
+                self.finish_member(member)?;
+            }
+            if produced > 0 {
+                return Ok(produced);
+            }
+            if input_ended && consumed == 0 && !stream_end {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    "truncated lzip member",
+                ));
+            }
+        }
+    }
+}
+
+/// The encoder of the lzip stream which writes a single member.
+/// The stream is finished by [`LzipEncoder::finish`], or when this encoder is dropped
+/// (the errors are only logged in this case).
+pub(crate) struct LzipEncoder<W: Write> {
+    writer: W,
+    stream: Stream,
+    digest: crc::Digest<'static, u32>,
+    size: u64,
+    buf: Vec<u8>,
+    /// The remaining bytes of the `.lzma` header to drop from the output.
+    skip: usize,
+    finished: bool,
+}
+
+impl<W: Write> LzipEncoder<W> {
+    /// Creates the encoder with the compression level (0-9).
+    pub(crate) fn new(mut writer: W, level: u32) -> std::io::Result<Self> {
+        let level = level.min(9);
+        let dict_size = DICT_SIZES[level as usize];
+        let stream = Stream::new_lzma_encoder(&lzma1_options(dict_size, level)?)?;
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&[VERSION, encode_dict_size(dict_size)])?;
+        Ok(Self {
+            writer,
+            stream,
+            digest: CRC32.digest(),
+            size: 0,
+            buf: vec![0; BUFFER_SIZE],
+            skip: ALONE_HEADER_SIZE,
+            finished: false,
+        })
+    }
+
+    /// Writes the first `produced` bytes of the buffer except the `.lzma` header.
+    fn write_out(&mut self, produced: usize) -> std::io::Result<()> {
+        let skipped = self.skip.min(produced);
+        self.skip -= skipped;
+        self.writer.write_all(&self.buf[skipped..produced])
+    }
+
+    /// Writes the rest of the compressed data and the trailer of the member.
+    pub(crate) fn finish(&mut self) -> std::io::Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+        loop {
+            let total_out = self.stream.total_out();
+            let status = self.stream.process(&[], &mut self.buf, Action::Finish)?;
+            let produced = (self.stream.total_out() - total_out) as usize;
+            self.write_out(produced)?;
+            if matches!(status, Status::StreamEnd) {
+                break;
+            }
+        }
+        let digest = std::mem::replace(&mut self.digest, CRC32.digest());
+        let member_size = (HEADER_SIZE + TRAILER_SIZE) as u64 + self.stream.total_out() - ALONE_HEADER_SIZE as u64;
+        self.writer.write_all(&digest.finalize().to_le_bytes())?;
+        self.writer.write_all(&self.size.to_le_bytes())?;
+        self.writer.write_all(&member_size.to_le_bytes())?;
+        self.writer.flush()?;
+        self.finished = true;
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for LzipEncoder<W> {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        if data.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let (total_in, total_out) = (self.stream.total_in(), self.stream.total_out());
+            self.stream.process(data, &mut self.buf, Action::Run)?;
+            let consumed = (self.stream.total_in() - total_in) as usize;
+            let produced = (self.stream.total_out() - total_out) as usize;
+            self.write_out(produced)?;
+            if consumed > 0 {
+                self.digest.update(&data[..consumed]);
+                self.size += consumed as u64;
+                return Ok(consumed);
+            }
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl<W: Write> Drop for LzipEncoder<W> {
+    fn drop(&mut self) {
+        if let Err(e) = self.finish() {
+            log::error!("failed to finish the lzip stream: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dict_size() {
+        assert_eq!(decode_dict_size(20), Some(1 << 20));
+        assert_eq!(decode_dict_size(0xd4), Some((1 << 20) - (1 << 16) * 6));
+        assert_eq!(decode_dict_size(11), None);
+        for size in DICT_SIZES {
+            assert_eq!(decode_dict_size(encode_dict_size(size)), Some(size));
+        }
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let data = std::fs::read("../testdata/test.tar").unwrap();
+        let mut compressed = vec![];
+        {
+            let mut encoder = LzipEncoder::new(&mut compressed, 5).unwrap();
+            encoder.write_all(&data).unwrap();
+        }
+        assert_eq!(compressed[..4], MAGIC);
+        // two members are decoded in sequence.
+        let mut twice = compressed.clone();
+        twice.extend_from_slice(&compressed);
+        let mut decoded = vec![];
+        LzipDecoder::new(twice.as_slice()).read_to_end(&mut decoded).unwrap();
+        assert!(decoded[..data.len()] == data[..] && decoded[data.len()..] == data[..]);
+    }
+
+    #[test]
+    fn test_corrupted_trailer() {
+        let mut compressed = std::fs::read("../testdata/test.tar.lz").unwrap();
+        let len = compressed.len();
+        compressed[len - TRAILER_SIZE] ^= 0xff;
+        let mut decoded = vec![];
+        let r = LzipDecoder::new(compressed.as_slice()).read_to_end(&mut decoded);
+        assert_eq!(r.unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+    }
+}
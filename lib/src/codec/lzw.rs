@@ -0,0 +1,397 @@
+//! The LZW codec of the unix `compress` command (`.Z`).
+//!
+//! The codes are packed in the groups of eight codes, and the rest of the group is skipped
+//! when the code width grows or the code table is cleared, as `compress` does.
+use std::collections::HashMap;
+use std::io::{BufReader, Read, Write};
+
+use super::invalid_data;
+
+const MAGIC: [u8; 2] = [0x1f, 0x9d];
+const BLOCK_MODE: u8 = 0x80;
+const BITS_MASK: u8 = 0x1f;
+const INIT_BITS: u32 = 9;
+const MAX_BITS: u32 = 16;
+const CLEAR: u32 = 256;
+const FIRST: u32 = 257;
+const GROUP: u32 = 8;
+
+/// Returns the largest code of the given width.
+fn max_code(bits: u32, max_bits: u32) -> u32 {
+    if bits == max_bits {
+        1 << max_bits
+    } else {
+        (1 << bits) - 1
+    }
+}
+
+/// The decoder of the `compress` stream.
+pub(crate) struct LzwDecoder<R: Read> {
+    reader: BufReader<R>,
+    header_read: bool,
+    block_mode: bool,
+    max_bits: u32,
+    bits: u32,
+    max_code: u32,
+    free: u32,
+    /// the number of the codes read in the current group.
+    count: u32,
+    acc: u32,
+    acc_bits: u32,
+    prefix: Vec<u16>,
+    suffix: Vec<u8>,
+    old: Option<u32>,
+    last: u8,
+    pending: Vec<u8>,
+    pos: usize,
+    eof: bool,
+}
+
+impl<R: Read> LzwDecoder<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        Self {
+            reader: BufReader::new(reader),
+            header_read: false,
+            block_mode: true,
+            max_bits: MAX_BITS,
+            bits: INIT_BITS,
+            max_code: max_code(INIT_BITS, MAX_BITS),
+            free: FIRST,
+            count: 0,
+            acc: 0,
+            acc_bits: 0,
+            prefix: vec![],
+            suffix: vec![],
+            old: None,
+            last: 0,
+            pending: vec![],
+            pos: 0,
+            eof: false,
+        }
+    }
+
+    fn read_header(&mut self) -> std::io::Result<()> {
+        let mut header = [0u8; 3];
+        self.reader.read_exact(&mut header)
+            .map_err(|_| invalid_data("not a compress (.Z) stream"))?;
+        if header[..2] != MAGIC {
+            return Err(invalid_data("not a compress (.Z) stream"));
+        }
+        self.max_bits = (header[2] & BITS_MASK) as u32;
+        if !(INIT_BITS..=MAX_BITS).contains(&self.max_bits) {
+            return Err(invalid_data(format!("unsupported code width: {}", self.max_bits)));
+        }
+        self.block_mode = header[2] & BLOCK_MODE != 0;
+        self.free = if self.block_mode { FIRST } else { CLEAR };
+        self.max_code = max_code(INIT_BITS, self.max_bits);
+        self.prefix = vec![0; 1 << self.max_bits];
+        self.suffix = vec![0; 1 << self.max_bits];
+        self.header_read = true;
+        Ok(())
+    }
+
+    /// Reads the next code, or returns `None` at the end of the stream.
+    fn read_code(&mut self) -> std::io::Result<Option<u32>> {
+        while self.acc_bits < self.bits {
+            let mut byte = [0u8];
+            if self.reader.read(&mut byte)? == 0 {
+                self.eof = true;
+                return Ok(None);
+            }
+            self.acc |= (byte[0] as u32) << self.acc_bits;
+            self.acc_bits += 8;
+        }
+        let code = self.acc & ((1 << self.bits) - 1);
+        self.acc >>= self.bits;
+        self.acc_bits -= self.bits;
+        self.count += 1;
+        Ok(Some(code))
+    }
+
+    /// Skips the rest of the current group.
+    fn skip_group(&mut self) -> std::io::Result<()> {
+        while !self.count.is_multiple_of(GROUP) && !self.eof {
+            self.read_code()?;
+        }
+        self.count = 0;
+        Ok(())
+    }
+
+    /// Decodes the next code into `pending`, and returns `false` at the end of the stream.
+    fn decode_next(&mut self) -> std::io::Result<bool> {
+        loop {
+            if self.free > self.max_code {
+                self.skip_group()?;
+                self.bits += 1;
+                self.max_code = max_code(self.bits, self.max_bits);
+                continue;
+            }
+            if self.eof {
+                return Ok(false);
+            }
+            let Some(code) = self.read_code()? else {
+                return Ok(false);
+            };
+            let Some(old) = self.old else {
+                if code >= CLEAR {
+                    return Err(invalid_data("the first code is not a literal"));
+                }
+                self.old = Some(code);
+                self.last = code as u8;
+                self.pending.push(self.last);
+                return Ok(true);
+            };
+            if code == CLEAR && self.block_mode {
+                self.free = CLEAR;
+                self.skip_group()?;
+                self.bits = INIT_BITS;
+                self.max_code = max_code(INIT_BITS, self.max_bits);
+                continue;
+            }
+            let mut current = code;
+            if code >= self.free {
+                if code > self.free {
+                    return Err(invalid_data(format!("invalid code: {code}")));
+                }
+                self.pending.push(self.last);
+                current = old;
+            }
+            while current >= CLEAR {
+                self.pending.push(self.suffix[current as usize]);
+                current = self.prefix[current as usize] as u32;
+            }
+            self.last = current as u8;
+            self.pending.push(self.last);
+            self.pending.reverse();
+            if self.free < 1 << self.max_bits {
+                self.prefix[self.free as usize] = old as u16;
+                self.suffix[self.free as usize] = self.last;
+                self.free += 1;
+            }
+            self.old = Some(code);
+            return Ok(true);
+        }
+    }
+}
+
+impl<R: Read> Read for LzwDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if !self.header_read {
+            self.read_header()?;
+        }
+        while self.pos == self.pending.len() {
+            self.pending.clear();
+            self.pos = 0;
+            if !self.decode_next()? {
+                return Ok(0);
+            }
+        }
+        let n = buf.len().min(self.pending.len() - self.pos);
+        buf[..n].copy_from_slice(&self.pending[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// The encoder of the `compress` stream with the 16 bits codes in the block mode.
+/// The code table is cleared when it is full.
+/// The stream is finished by [`LzwEncoder::finish`], or when this encoder is dropped
+/// (the errors are only logged in this case).
+pub(crate) struct LzwEncoder<W: Write> {
+    writer: W,
+    table: HashMap<u32, u32>,
+    free: u32,
+    current: Option<u32>,
+    bits: u32,
+    /// the number of the codes written in the current group.
+    count: u32,
+    /// the next free code in the decoder, which determines the code width.
+    decoder_free: u32,
+    first: bool,
+    acc: u32,
+    acc_bits: u32,
+    out: Vec<u8>,
+    finished: bool,
+}
+
+impl<W: Write> LzwEncoder<W> {
+    pub(crate) fn new(writer: W) -> Self {
+        let mut out = MAGIC.to_vec();
+        out.push(BLOCK_MODE | MAX_BITS as u8);
+        Self {
+            writer,
+            table: HashMap::new(),
+            free: FIRST,
+            current: None,
+            bits: INIT_BITS,
+            count: 0,
+            decoder_free: FIRST,
+            first: true,
+            acc: 0,
+            acc_bits: 0,
+            out,
+            finished: false,
+        }
+    }
+
+    fn put(&mut self, code: u32) {
+        self.acc |= code << self.acc_bits;
+        self.acc_bits += self.bits;
+        while self.acc_bits >= 8 {
+            self.out.push(self.acc as u8);
+            self.acc >>= 8;
+            self.acc_bits -= 8;
+        }
+        self.count += 1;
+    }
+
+    /// Pads the rest of the current group.
+    fn pad_group(&mut self) {
+        while !self.count.is_multiple_of(GROUP) {
+            self.put(0);
+        }
+        self.count = 0;
+    }
+
+    /// Grows the code width as the decoder does before reading the next code.
+    fn grow_if_needed(&mut self) {
+        if self.bits < MAX_BITS && self.decoder_free > (1 << self.bits) - 1 {
+            self.pad_group();
+            self.bits += 1;
+        }
+    }
+
+    fn emit(&mut self, code: u32) {
+        self.grow_if_needed();
+        self.put(code);
+        if !self.first && self.decoder_free < 1 << MAX_BITS {
+            self.decoder_free += 1;
+        }
+        self.first = false;
+    }
+
+    fn emit_clear(&mut self) {
+        self.grow_if_needed();
+        self.put(CLEAR);
+        self.pad_group();
+        self.bits = INIT_BITS;
+        self.decoder_free = CLEAR;
+        self.table.clear();
+        self.free = FIRST;
+    }
+
+    fn flush_out(&mut self) -> std::io::Result<()> {
+        self.writer.write_all(&self.out)?;
+        self.out.clear();
+        Ok(())
+    }
+
+    /// Writes the rest of the codes.
+    pub(crate) fn finish(&mut self) -> std::io::Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+        if let Some(code) = self.current.take() {
+            self.emit(code);
+        }
+        if self.acc_bits > 0 {
+            self.out.push(self.acc as u8);
+            self.acc = 0;
+            self.acc_bits = 0;
+        }
+        self.flush_out()?;
+        self.writer.flush()?;
+        self.finished = true;
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for LzwEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        for &c in buf {
+            let Some(current) = self.current else {
+                self.current = Some(c as u32);
+                continue;
+            };
+            let key = (current << 8) | c as u32;
+            if let Some(&code) = self.table.get(&key) {
+                self.current = Some(code);
+                continue;
+            }
+            self.emit(current);
+            if self.free < 1 << MAX_BITS {
+                self.table.insert(key, self.free);
+                self.free += 1;
+            } else {
+                self.emit_clear();
+            }
+            self.current = Some(c as u32);
+        }
+        if self.out.len() >= 64 * 1024 {
+            self.flush_out()?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.flush_out()?;
+        self.writer.flush()
+    }
+}
+
+impl<W: Write> Drop for LzwEncoder<W> {
+    fn drop(&mut self) {
+        if let Err(e) = self.finish() {
+            log::error!("failed to finish the compress (.Z) stream: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(data: &[u8]) -> Vec<u8> {
+        let mut compressed = vec![];
+        {
+            let mut encoder = LzwEncoder::new(&mut compressed);
+            encoder.write_all(data).unwrap();
+        }
+        let mut decoded = vec![];
+        LzwDecoder::new(compressed.as_slice()).read_to_end(&mut decoded).unwrap();
+        decoded
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        assert_eq!(roundtrip(b""), b"");
+        assert_eq!(roundtrip(b"a"), b"a");
+        assert_eq!(roundtrip(b"abababababababab"), b"abababababababab");
+        // enough data to grow the code width up to 16 bits and to clear the code table.
+        let mut data = std::fs::read("../testdata/test.tar").unwrap();
+        let mut state = 0x2545f491u32;
+        data.extend((0..400_000).map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        }));
+        assert!(roundtrip(&data) == data);
+    }
+
+    #[test]
+    fn test_decode_compress_output() {
+        // test.tar.Z is also decompressed into test.tar by `gzip -d`.
+        let mut decoded = vec![];
+        let file = std::fs::File::open("../testdata/test.tar.Z").unwrap();
+        LzwDecoder::new(file).read_to_end(&mut decoded).unwrap();
+        assert!(decoded == std::fs::read("../testdata/test.tar").unwrap());
+    }
+
+    #[test]
+    fn test_invalid_header() {
+        let mut decoded = vec![];
+        let r = LzwDecoder::new(&b"\x1f\x8bxxxx"[..]).read_to_end(&mut decoded);
+        assert_eq!(r.unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+    }
+}
@@ -1,5 +1,7 @@
 //! This module provides the extractor for the archive file.
-//! The supported formats are `cab`, `lha`, `rar`, `7z`, `tar`, `tar.gz`, `tar.bz2`, `tar.xz`, `tar.zst`, and `zip`.
+//! The supported formats are `cab`, `iso`, `lha`, `rar`, `7z`, `tar`, `tar.gz`, `tar.bz2`, `tar.xz`, `tar.zst`, `tar.lz4`,
+//! `tar.lzma`, `tar.lz`, `tar.Z`, `tar.br`, `deb`, `rpm`, `squashfs`, and `zip`,
+//! and the single file compression formats, `gz`, `bz2`, `xz`, `zst`, and `lz4`.
 //! 
 //! # Example: listing the entries in the archive file
 //! 
@@ -36,19 +38,33 @@
 use chrono::NaiveDateTime;
 use serde::Serialize;
 use std::fmt::Display;
+use std::fs::File;
+use std::io::{Read, Seek};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use typed_builder::TypedBuilder;
 
-use crate::format::Format;
+use crate::format::{ExtractorFactory, Format};
 use crate::{Result, Error};
 
+pub use destination::{Attributes, Destination};
+pub use transform::Transform;
+
 mod ar;
 mod cab;
+mod compressed;
 mod cpio;
+mod deb;
+mod destination;
+mod iso;
 mod lha;
 mod rar;
+mod rpm;
+mod selection;
 mod sevenz;
+mod squashfs;
 mod tar;
+mod transform;
 mod zip;
 
 /// This struct represents an entry in the archive file.
@@ -124,11 +140,40 @@ impl Entry {
 pub struct Entries {
     pub path: PathBuf,
     pub entries: Vec<Entry>,
+    /// The properties of the archive file itself, e.g., the name and the version of the package.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub properties: Vec<Property>,
+}
+
+/// The property of the archive file, e.g., the tag in the header of `.rpm`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Property {
+    pub name: String,
+    pub value: String,
+}
+
+impl Property {
+    pub fn new<S: Into<String>, T: Into<String>>(name: S, value: T) -> Self {
+        Self { name: name.into(), value: value.into() }
+    }
 }
 
 impl Entries {
     pub fn new(path: PathBuf, entries: Vec<Entry>) -> Self {
-        Self { path, entries }
+        Self { path, entries, properties: vec![] }
+    }
+
+    /// Sets the properties of the archive file.
+    pub fn with_properties(mut self, properties: Vec<Property>) -> Self {
+        self.properties = properties;
+        self
+    }
+
+    /// Returns the value of the property of the given name.
+    pub fn property(&self, name: &str) -> Option<&str> {
+        self.properties.iter()
+            .find(|p| p.name == name)
+            .map(|p| p.value.as_str())
     }
 
     pub fn iter(&self) -> impl Iterator<Item = &Entry> {
@@ -144,14 +189,44 @@ impl Entries {
     }
 }
 
+/// The source of the archive, which is readable and seekable, e.g., [`File`] and [`Cursor`](std::io::Cursor).
+pub trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek + ?Sized> ReadSeek for T {}
+
 /// The trait for extracting the archive file.
 /// If you want to support a new format for extraction, you need to implement the `ToteExtractor` trait.
 /// Then, the call [`perform`](ToteExtractor::perform) and/or [`list`](ToteExtractor::list) method of [`ToteExtractor`].
+///
+/// The implementations read the archive from any [`ReadSeek`] (e.g., the in-memory buffer) by
+/// [`list_from`](ToteExtractor::list_from) and [`perform_from`](ToteExtractor::perform_from),
+/// and [`list`](ToteExtractor::list) and [`perform`](ToteExtractor::perform) open the archive file for them.
+///
+/// The implementations should resolve the entry names and write the entries through the given [`Destination`],
+/// so that the extracted files never escape the destination directory.
 pub trait ToteExtractor {
     /// returns the entry list of the given archive file.
-    fn list(&self, archive_file: PathBuf) -> Result<Entries>;
-    /// extract the given archive file into the specified directory with the given options.
-    fn perform(&self, archive_file: PathBuf, opts: PathBuf) -> Result<()>;
+    fn list(&self, archive_file: PathBuf) -> Result<Entries> {
+        let file = File::open(&archive_file).map_err(Error::IO)?;
+        self.list_from(Box::new(file), archive_file)
+    }
+    /// extract the given archive file into the given destination.
+    fn perform(&self, archive_file: PathBuf, dest: &mut Destination) -> Result<()> {
+        let file = File::open(&archive_file).map_err(Error::IO)?;
+        self.perform_from(Box::new(file), dest)
+    }
+    /// returns the entry list of the archive read from the given reader.
+    /// `archive_file` is the name of the archive for the resultant [`Entries`] and the errors.
+    fn list_from(&self, reader: Box<dyn ReadSeek + '_>, archive_file: PathBuf) -> Result<Entries>;
+    /// extract the archive read from the given reader into the given destination.
+    /// The name of the archive for the errors is [`Destination::archive_file`].
+    fn perform_from(&self, reader: Box<dyn ReadSeek + '_>, dest: &mut Destination) -> Result<()>;
+    /// extract the archive read from the given stream (e.g., stdin) into the given destination.
+    /// The formats requiring the random access to the archive return [`Error::UnsupportedFormat`].
+    fn perform_stream(&self, reader: Box<dyn Read + '_>, dest: &mut Destination) -> Result<()> {
+        let _ = (reader, dest);
+        Err(Error::UnsupportedFormat("extraction from the stream".to_string()))
+    }
 }
 
 /// Returns the extractor for the given archive file.
@@ -164,31 +239,58 @@ pub(super) fn create<P: AsRef<Path>>(file: P) -> Result<Box<dyn ToteExtractor>>
 }
 
 /// Returns the extractor for the given archive file.
-/// The supported format is `cab`, `lha`, `rar`, `7z`, `tar`, `tar.gz`, `tar.bz2`, `tar.xz`, `tar.zst`, and `zip`.
+/// The supported format is `cab`, `iso`, `lha`, `rar`, `7z`, `tar`, `tar.gz`, `tar.bz2`, `tar.xz`, `tar.zst`, `tar.lz4`,
+/// `tar.lzma`, `tar.lz`, `tar.Z`, `tar.br`, `deb`, `rpm`, `squashfs`, `zip`, `gz`, `bz2`, `xz`, `zst`, and `lz4`, and the formats registered by [`Registration`](crate::format::Registration).
 pub(super) fn create_with<P: AsRef<Path>>(file: P, format: Option<&Format>) -> Result<Box<dyn ToteExtractor>> {
     let file = file.as_ref();
     match format {
-        Some(format) => match format.name.as_str() {
-            "Ar" => Ok(Box::new(ar::Extractor {})),
-            "Cab" => Ok(Box::new(cab::Extractor {})),
-            "Cpio" => Ok(Box::new(cpio::Extractor {})),
-            "Lha" => Ok(Box::new(lha::Extractor {})),
-            "Rar" => Ok(Box::new(rar::Extractor {})),
-            "SevenZ" => Ok(Box::new(sevenz::Extractor {})),
-            "Tar" => Ok(Box::new(tar::Extractor {})),
-            "TarBz2" => Ok(Box::new(tar::Bz2Extractor {})),
-            "TarGz" => Ok(Box::new(tar::GzExtractor {})),
-            "TarXz" => Ok(Box::new(tar::XzExtractor {})),
-            "TarZstd" => Ok(Box::new(tar::ZstdExtractor {})),
-            "Zip" => Ok(Box::new(zip::Extractor {})),
-            s => Err(Error::UnknownFormat(format!("{s}: unknown format"))),
-        },
+        Some(format) => crate::format::create_extractor(format)
+            .unwrap_or_else(|| Err(Error::UnknownFormat(format!("{}: unknown format", format.name)))),
         None => Err(Error::Extractor(format!(
             "{file:?} no suitable extractor"
         ))),
     }
 }
 
+/// Returns the factories of the extractors for the built-in formats with their names,
+/// which are registered in the same registry as [`Registration`](crate::format::Registration).
+pub(crate) fn builtin_factories() -> Vec<(&'static str, ExtractorFactory)> {
+    fn factory<F>(f: F) -> ExtractorFactory
+    where
+        F: Fn() -> Box<dyn ToteExtractor> + Send + Sync + 'static,
+    {
+        Arc::new(f)
+    }
+    vec![
+        ("Ar", factory(|| Box::new(ar::Extractor {}))),
+        ("Bz2", factory(|| Box::new(compressed::Bz2Extractor {}))),
+        ("Cab", factory(|| Box::new(cab::Extractor {}))),
+        ("Cpio", factory(|| Box::new(cpio::Extractor {}))),
+        ("Deb", factory(|| Box::new(deb::Extractor {}))),
+        ("Gz", factory(|| Box::new(compressed::GzExtractor {}))),
+        ("Iso", factory(|| Box::new(iso::Extractor {}))),
+        ("Lz4", factory(|| Box::new(compressed::Lz4Extractor {}))),
+        ("Lha", factory(|| Box::new(lha::Extractor {}))),
+        ("Rar", factory(|| Box::new(rar::Extractor {}))),
+        ("Rpm", factory(|| Box::new(rpm::Extractor {}))),
+        ("SevenZ", factory(|| Box::new(sevenz::Extractor {}))),
+        ("SquashFs", factory(|| Box::new(squashfs::Extractor {}))),
+        ("Tar", factory(|| Box::new(tar::Extractor {}))),
+        ("TarBr", factory(|| Box::new(tar::BrExtractor {}))),
+        ("TarBz2", factory(|| Box::new(tar::Bz2Extractor {}))),
+        ("TarGz", factory(|| Box::new(tar::GzExtractor {}))),
+        ("TarLz", factory(|| Box::new(tar::LzExtractor {}))),
+        ("TarLz4", factory(|| Box::new(tar::Lz4Extractor {}))),
+        ("TarLzma", factory(|| Box::new(tar::LzmaExtractor {}))),
+        ("TarXz", factory(|| Box::new(tar::XzExtractor {}))),
+        ("TarZ", factory(|| Box::new(tar::ZExtractor {}))),
+        ("TarZstd", factory(|| Box::new(tar::ZstdExtractor {}))),
+        ("Xz", factory(|| Box::new(compressed::XzExtractor {}))),
+        ("Zip", factory(|| Box::new(zip::Extractor {}))),
+        ("Zstd", factory(|| Box::new(compressed::ZstdExtractor {}))),
+    ]
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -212,15 +314,53 @@ mod tests {
         let opts1 = crate::ExtractConfig::builder()
             .use_archive_name_dir(true)
             .build();
-        let dest = opts1.dest(&archive_file).unwrap();
+        let dest = opts1.dest(&archive_file, create(&archive_file).unwrap().as_ref()).unwrap();
         assert_eq!(dest, PathBuf::from("./archive"));
     }
 
+    #[test]
+    fn test_auto_archive_name_dir() {
+        let config = crate::ExtractConfig::builder()
+            .auto_archive_name_dir(true)
+            .build();
+        let archive_file = PathBuf::from("../testdata/test.zip");
+        let extractor = create(&archive_file).unwrap();
+        assert!(config.uses_archive_name_dir(&archive_file, extractor.as_ref()).unwrap());
+        let archive_file = PathBuf::from("../testdata/single_root.tar.gz");
+        let extractor = create(&archive_file).unwrap();
+        assert!(!config.uses_archive_name_dir(&archive_file, extractor.as_ref()).unwrap());
+
+        // the roots are counted after stripping and selecting the entries.
+        let config = crate::ExtractConfig::builder()
+            .auto_archive_name_dir(true)
+            .strip_components(1)
+            .build();
+        assert!(config.uses_archive_name_dir(&archive_file, extractor.as_ref()).unwrap());
+        let config = crate::ExtractConfig::builder()
+            .auto_archive_name_dir(true)
+            .includes(vec!["src/**".to_string()])
+            .build();
+        let archive_file = PathBuf::from("../testdata/test.zip");
+        let extractor = create(&archive_file).unwrap();
+        assert!(!config.uses_archive_name_dir(&archive_file, extractor.as_ref()).unwrap());
+
+        let config = crate::ExtractConfig::builder()
+            .dest("results/auto_archive_name_dir")
+            .overwrite(true)
+            .auto_archive_name_dir(true)
+            .build();
+        crate::extract("../testdata/test.zip", &config).unwrap();
+        assert!(PathBuf::from("results/auto_archive_name_dir/test/Cargo.toml").exists());
+        crate::extract("../testdata/single_root.tar.gz", &config).unwrap();
+        assert!(PathBuf::from("results/auto_archive_name_dir/project-1.0/src/main.rs").exists());
+        let _ = std::fs::remove_dir_all("results/auto_archive_name_dir");
+    }
+
     #[test]
     fn test_destination2() {
         let archive_file = PathBuf::from("/tmp/archive.zip");
         let opts2 = crate::ExtractConfig::builder().build();
-        let dest = opts2.dest(&archive_file).unwrap();
+        let dest = opts2.dest(&archive_file, create(&archive_file).unwrap().as_ref()).unwrap();
         assert_eq!(dest, PathBuf::from("."));
     }
 
@@ -240,4 +380,169 @@ mod tests {
         let entries = extractor.list(archive_file).unwrap();
         assert_eq!(entries.len(), 19);
     }
+
+    #[test]
+    fn test_broken_archives_never_panic() {
+        let config = crate::ExtractConfig::builder().build();
+        let mut files = std::fs::read_dir("../testdata/broken").unwrap()
+            .map(|e| e.unwrap().path())
+            .collect::<Vec<_>>();
+        files.sort();
+        for file in files {
+            let extractor = create(&file).unwrap();
+            let dest_dir = PathBuf::from("results/broken").join(file.file_name().unwrap());
+            let r = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                let _ = extractor.list(file.clone());
+                let mut dest = Destination::new(&dest_dir, &config).with_archive_file(&file);
+                extractor.perform(file.clone(), &mut dest)
+            }));
+            match r {
+                Ok(r) => assert!(r.is_err(), "{file:?}: broken archive was extracted"),
+                Err(_) => panic!("{file:?}: panicked"),
+            }
+        }
+        let _ = std::fs::remove_dir_all("results/broken");
+    }
+
+    #[test]
+    fn test_selective_extraction() {
+        for ext in ["7z", "ar", "cab", "cpio", "iso", "lzh", "rar", "tar", "tar.gz", "zip"] {
+            let archive_file = PathBuf::from(format!("../testdata/test.{ext}"));
+            let dest_dir = PathBuf::from(format!("results/selective/{ext}"));
+            let config = crate::ExtractConfig::builder()
+                .dest(&dest_dir)
+                .overwrite(true)
+                .entries(vec!["Cargo.toml".to_string()])
+                .includes(vec!["*.rs".to_string()])
+                .excludes(vec!["tar.rs".to_string(), "build.rs".to_string()])
+                .build();
+            crate::extract(&archive_file, &config).unwrap();
+            let files = ignore::WalkBuilder::new(&dest_dir).standard_filters(false).build()
+                .flatten()
+                .filter(|e| e.file_type().is_some_and(|t| t.is_file()))
+                .map(|e| e.file_name().to_string_lossy().to_string())
+                .collect::<Vec<_>>();
+            assert!(files.contains(&"Cargo.toml".to_string()), "{ext}: {files:?}");
+            assert!(files.contains(&"zip.rs".to_string()), "{ext}: {files:?}");
+            assert!(files.iter().all(|f| f == "Cargo.toml" || (f.ends_with(".rs") && f != "tar.rs" && f != "build.rs")), "{ext}: {files:?}");
+        }
+        let _ = std::fs::remove_dir_all("results/selective");
+    }
+
+    #[test]
+    fn test_extract_stream() {
+        let files_in = |dir: &Path| {
+            let mut files = ignore::WalkBuilder::new(dir).standard_filters(false).build()
+                .flatten()
+                .filter(|e| e.file_type().is_some_and(|t| t.is_file()))
+                .map(|e| (e.path().strip_prefix(dir).unwrap().to_path_buf(), std::fs::read(e.path()).unwrap()))
+                .collect::<Vec<_>>();
+            files.sort();
+            files
+        };
+        for (ext, name) in [("ar", "Ar"), ("cpio", "Cpio"), ("deb", "Deb"), ("rpm", "Rpm"), ("tar", "Tar"), ("tar.br", "TarBr"), ("tar.bz2", "TarBz2"),
+                ("tar.gz", "TarGz"), ("tar.lz", "TarLz"), ("tar.lz4", "TarLz4"), ("tar.lzma", "TarLzma"),
+                ("tar.xz", "TarXz"), ("tar.Z", "TarZ"), ("tar.zst", "TarZstd"), ("zip", "Zip")] {
+            let archive_file = PathBuf::from(format!("../testdata/test.{ext}"));
+            let file_dir = PathBuf::from(format!("results/stream/{ext}/file"));
+            let stream_dir = PathBuf::from(format!("results/stream/{ext}/stream"));
+            let config = |dest: &Path| crate::ExtractConfig::builder().dest(dest).overwrite(true).build();
+            crate::extract(&archive_file, &config(&file_dir)).unwrap();
+            let format = crate::format::find_format_by_name(name).unwrap();
+            let reader = std::fs::File::open(&archive_file).unwrap();
+            crate::extract_stream(reader, format, &config(&stream_dir)).unwrap();
+            let files = files_in(&file_dir);
+            assert!(!files.is_empty(), "{ext}: no files");
+            assert!(files == files_in(&stream_dir), "{ext}: differ from the file extraction");
+        }
+        let _ = std::fs::remove_dir_all("results/stream");
+    }
+
+    #[test]
+    fn test_extract_from() {
+        for (ext, name) in [("7z", "SevenZ"), ("ar", "Ar"), ("cab", "Cab"), ("cpio", "Cpio"), ("iso", "Iso"), ("lzh", "Lha"),
+                ("squashfs", "SquashFs"), ("tar", "Tar"), ("tar.gz", "TarGz"), ("tar.zst", "TarZstd"), ("zip", "Zip")] {
+            let blob = std::fs::read(format!("../testdata/test.{ext}")).unwrap();
+            let format = crate::format::find_format_by_name(name).unwrap();
+            let entries = crate::entries_from(std::io::Cursor::new(&blob), format).unwrap();
+            let expected = crate::entries(format!("../testdata/test.{ext}"), crate::format::default_format_detector().as_ref()).unwrap();
+            assert_eq!(entries.len(), expected.len(), "{ext}");
+            assert_eq!(entries.path, PathBuf::from("-"));
+
+            let dest_dir = PathBuf::from(format!("results/from/{ext}"));
+            let config = crate::ExtractConfig::builder().dest(&dest_dir).overwrite(true).build();
+            crate::extract_from(std::io::Cursor::new(&blob), format, &config).unwrap();
+            let toml = ignore::WalkBuilder::new(&dest_dir).standard_filters(false).build()
+                .flatten()
+                .any(|e| e.file_name() == "Cargo.toml");
+            assert!(toml, "{ext}: Cargo.toml not found");
+        }
+        let _ = std::fs::remove_dir_all("results/from");
+
+        let blob = std::fs::read("../testdata/test.rar").unwrap();
+        let format = crate::format::find_format_by_name("Rar").unwrap();
+        match crate::entries_from(std::io::Cursor::new(blob), format) {
+            Err(Error::UnsupportedFormat(_)) => {}
+            r => panic!("unexpected result: {r:?}"),
+        }
+    }
+
+    #[test]
+    fn test_extract_stream_unsupported() {
+        let config = crate::ExtractConfig::builder()
+            .dest("results/stream_unsupported")
+            .build();
+        let file = std::fs::File::open("../testdata/test.7z").unwrap();
+        let format = crate::format::find_format_by_name("SevenZ").unwrap();
+        match crate::extract_stream(file, format, &config) {
+            Err(Error::UnsupportedFormat(_)) => {}
+            r => panic!("unexpected result: {r:?}"),
+        }
+    }
+
+    #[test]
+    fn test_extract_stream_ratio() {
+        // the ratio is checked against the bytes consumed from the stream.
+        let config = crate::ExtractConfig::builder()
+            .dest("results/stream_ratio")
+            .max_ratio(1.0)
+            .build();
+        let file = std::fs::File::open("../testdata/test.tar.gz").unwrap();
+        let format = crate::format::find_format_by_name("TarGz").unwrap();
+        match crate::extract_stream(file, format, &config) {
+            Err(Error::LimitExceeded(m)) => assert!(m.ends_with("the compression ratio exceeds 1"), "{m}"),
+            r => panic!("unexpected result: {r:?}"),
+        }
+        let _ = std::fs::remove_dir_all("results/stream_ratio");
+    }
+
+    #[test]
+    fn test_entry_not_found() {
+        let config = crate::ExtractConfig::builder()
+            .dest("results/not_found")
+            .entries(vec!["src".to_string(), "missing.txt".to_string()])
+            .build();
+        match crate::extract("../testdata/test.zip", &config) {
+            Err(Error::EntryNotFound(_, name)) => assert_eq!(name, "missing.txt"),
+            r => panic!("unexpected result: {r:?}"),
+        }
+        assert!(PathBuf::from("results/not_found/src/main.rs").exists());
+        assert!(!PathBuf::from("results/not_found/Cargo.toml").exists());
+        let _ = std::fs::remove_dir_all("results/not_found");
+    }
+
+    #[test]
+    fn test_strip_components() {
+        let config = crate::ExtractConfig::builder()
+            .dest("results/strip_components")
+            .overwrite(true)
+            .strip_components(1)
+            .transforms(vec![Transform::new("s/^extractor/ext/").unwrap()])
+            .build();
+        crate::extract("../testdata/test.tar", &config).unwrap();
+        assert!(PathBuf::from("results/strip_components/main.rs").exists());
+        assert!(PathBuf::from("results/strip_components/ext/tar.rs").exists());
+        assert!(!PathBuf::from("results/strip_components/Cargo.toml").exists());
+        let _ = std::fs::remove_dir_all("results/strip_components");
+    }
 }
@@ -1,43 +1,45 @@
-use std::fs::create_dir_all;
 use std::io::Read;
 use std::path::Path;
-use std::{fs::File, path::PathBuf};
+use std::path::PathBuf;
 
 use crate::{Result, Error};
 use ar::Archive;
 
-use crate::extractor::{Entry as ToteEntry, Entries, ToteExtractor};
+use crate::extractor::{Attributes, Destination, Entry as ToteEntry, Entries, ReadSeek, ToteExtractor};
 
 /// AR ormat extractor implementation.
 pub(super) struct Extractor {}
 
 impl ToteExtractor for Extractor {
-    fn list(&self, archive_file: PathBuf) -> Result<Entries> {
-        File::open(&archive_file)
-            .map_err(Error::IO)
-            .map(Archive::new)
-            .and_then(|archive| list_ar(archive, archive_file))
+    fn list_from(&self, reader: Box<dyn ReadSeek + '_>, archive_file: PathBuf) -> Result<Entries> {
+        list_ar(Archive::new(reader), archive_file)
     }
-    fn perform(&self, archive_file: PathBuf, base: PathBuf) -> Result<()> {
-        File::open(&archive_file)
-            .map_err(Error::IO)
-            .map(Archive::new)
-            .and_then(|archive| extract_ar(archive, base))
+
+    fn perform_from(&self, reader: Box<dyn ReadSeek + '_>, dest: &mut Destination) -> Result<()> {
+        self.perform_stream(reader, dest)
+    }
+
+    fn perform_stream(&self, reader: Box<dyn Read + '_>, dest: &mut Destination) -> Result<()> {
+        let archive_file = dest.archive_file().to_path_buf();
+        extract_ar(Archive::new(reader), &archive_file, dest)
     }
 }
 
-fn extract_ar<R: Read>(mut archive: ar::Archive<R>, base: PathBuf) -> Result<()> {
+fn extract_ar<R: Read>(mut archive: ar::Archive<R>, archive_file: &Path, dest: &mut Destination) -> Result<()> {
     let mut errs = vec![];
     while let Some(entry) = archive.next_entry() {
         let mut entry = match entry {
             Ok(e) => e,
-            Err(e) => { errs.push(Error::IO(e)); continue; }
+            Err(e) => {
+                errs.push(Error::corrupted(archive_file, None, e));
+                break;
+            }
         };
         let header = entry.header();
         let path = match str::from_utf8(header.identifier()) {
             Ok(p) => PathBuf::from(p),
             Err(e) => {
-                errs.push(Error::Archiver(e.to_string()));
+                errs.push(Error::corrupted(archive_file, None, e));
                 continue;
             }
         };
@@ -47,21 +49,41 @@ fn extract_ar<R: Read>(mut archive: ar::Archive<R>, base: PathBuf) -> Result<()>
         let size = header.size();
         log::info!("extracting {path:?} ({size} bytes)");
 
-        let dest = base.join(&path);
-        if is_file(header.mode()) {
-            if let Err(e) = write_to(&mut entry, &dest, &mut errs) {
-                errs.push(e);
+        if !is_file(header.mode()) {
+            continue;
+        }
+        let Some(dest_path) = dest.path_of(path.to_string_lossy())? else {
+            continue;
+        };
+        let attrs = Attributes::builder()
+            .mode(header.mode())
+            .mtime(header.mtime() as i64)
+            .uid(header.uid())
+            .gid(header.gid())
+            .build();
+        match dest.write(&dest_path, &mut entry, &attrs) {
+            Err(e @ Error::LimitExceeded(_)) => return Err(e),
+            Err(e) => errs.push(e),
+            Ok(written) if written != size => {
+                errs.push(Error::corrupted(archive_file, Some(&path.to_string_lossy()), "unexpected end of the entry"));
             }
+            Ok(_) => {}
         }
     }
-    Ok(())
+    Error::error_or((), errs)
 }
 
-fn write_to<R: Read>(entry: &mut ar::Entry<R>, dest: &Path, errs: &mut Vec<Error>) -> Result<()> {
-    create_dir_all(dest.parent().unwrap()).unwrap();
-    let mut dest_file = File::create(dest).map_err(Error::IO)?;
-    if let Err(e) = std::io::copy(entry, &mut dest_file) {
-        errs.push(Error::IO(e));
+/// Calls `f` with the name and the data of each member in the ar archive,
+/// for the formats based on ar (e.g., `.deb`).
+pub(super) fn for_each_member<R: Read, F>(reader: R, archive_file: &Path, mut f: F) -> Result<()>
+where
+    F: FnMut(&str, &mut ar::Entry<'_, R>) -> Result<()>,
+{
+    let mut archive = Archive::new(reader);
+    while let Some(entry) = archive.next_entry() {
+        let mut entry = entry.map_err(|e| Error::corrupted(archive_file, None, e))?;
+        let name = String::from_utf8_lossy(entry.header().identifier()).to_string();
+        f(&name, &mut entry)?;
     }
     Ok(())
 }
@@ -71,8 +93,10 @@ fn is_file(mode: u32) -> bool {
 }
 
 fn is_filename_mac_finder_file(path: &Path) -> bool {
-    let filename = path.file_name().unwrap().to_str().unwrap();
-    filename == ".DS_Store" || filename.starts_with("._")
+    match path.file_name().and_then(|n| n.to_str()) {
+        Some(filename) => filename == ".DS_Store" || filename.starts_with("._"),
+        None => false,
+    }
 }
 
 fn list_ar<R: Read>(mut archive: ar::Archive<R>, path: PathBuf) -> Result<Entries> {
@@ -81,7 +105,10 @@ fn list_ar<R: Read>(mut archive: ar::Archive<R>, path: PathBuf) -> Result<Entrie
     while let Some(entry) = archive.next_entry() {
         let entry = match entry {
             Ok(e) => e,
-            Err(e) => { errs.push(Error::IO(e)); continue; }
+            Err(e) => {
+                errs.push(Error::corrupted(&path, None, e));
+                break;
+            }
         };
         result.push(convert_to_entry(entry.header()));
     }
@@ -89,11 +116,11 @@ fn list_ar<R: Read>(mut archive: ar::Archive<R>, path: PathBuf) -> Result<Entrie
 }
 
 fn convert_to_entry(e: &ar::Header) -> ToteEntry {
-    let path = str::from_utf8(e.identifier()).unwrap();
+    let path = String::from_utf8_lossy(e.identifier());
     let size = e.size();
     let mode = e.mode();
     let mtime = e.mtime();
-    let datetime = chrono::DateTime::from_timestamp_millis(mtime as i64);
+    let datetime = chrono::DateTime::from_timestamp(mtime as i64, 0);
     ToteEntry::builder()
         .name(path)
         .original_size(size)
@@ -114,12 +141,12 @@ mod tests {
             Ok(r) => {
                 let r = r.iter().map(|e| e.name.clone()).collect::<Vec<_>>();
                 assert_eq!(r.len(), 16);
-                assert_eq!(r.get(0), Some("Cargo.toml".to_string()).as_ref());
+                assert_eq!(r.first(), Some("Cargo.toml".to_string()).as_ref());
                 assert_eq!(r.get(1), Some("build.rs".to_string()).as_ref());
                 assert_eq!(r.get(2), Some("LICENSE".to_string()).as_ref());
                 assert_eq!(r.get(3), Some("README.md".to_string()).as_ref());
             }
-            Err(_) => assert!(false),
+            Err(_) => panic!("unexpected result"),
         }
     }
 
@@ -134,11 +161,10 @@ mod tests {
 
         match crate::extract(archive_file, &opts) {
             Ok(_) => {
-                assert!(true);
                 assert!(PathBuf::from("results/ar/test/Cargo.toml").exists());
                 std::fs::remove_dir_all(PathBuf::from("results/ar")).unwrap();
             }
-            Err(_) => assert!(false),
+            Err(_) => panic!("unexpected result"),
         };
     }
 }
@@ -1,9 +1,10 @@
-use std::fs::{File, create_dir_all};
+use std::io::{Read, Seek};
 use std::path::{Path, PathBuf};
 
 use cab::{Cabinet, FileEntry};
 
-use crate::extractor::{Entries, Entry, ToteExtractor};
+use crate::extractor::destination::local_timestamp;
+use crate::extractor::{Attributes, Destination, Entries, Entry, ReadSeek, ToteExtractor};
 use crate::{Result, Error};
 
 /// CAB (Cabinet) format extractor implementation.
@@ -12,75 +13,63 @@ use crate::{Result, Error};
 pub(super) struct Extractor {}
 
 impl ToteExtractor for Extractor {
-    fn list(&self, target: PathBuf) -> Result<Entries> {
-        list_impl(&target, convert)
-            .map(|r| Entries::new(target, r))
+    fn list_from(&self, reader: Box<dyn ReadSeek + '_>, target: PathBuf) -> Result<Entries> {
+        let cabinet = open_cabinet(reader, &target)?;
+        Ok(Entries::new(target, list_impl(&cabinet, convert)))
     }
 
-    fn perform(&self, target: PathBuf, base: PathBuf) -> Result<()> {
-        let list = list_impl(&target, |file| {
-            (file.name().to_string(), file.uncompressed_size())
-        })?;
+    fn perform_from(&self, reader: Box<dyn ReadSeek + '_>, dest: &mut Destination) -> Result<()> {
+        let target = dest.archive_file().to_path_buf();
+        let mut cabinet = open_cabinet(reader, &target)?;
+        let list = list_impl(&cabinet, |file| {
+            (file.name().to_string(), file.uncompressed_size(), attributes(file))
+        });
         let mut errs = vec![];
-        let mut cabinet = open_cabinet(&target)?;
         for file in list {
-            if let Err(e) = write_file_impl(&mut cabinet, file, &base) {
-                errs.push(e);
+            match write_file_impl(&mut cabinet, &target, file, dest) {
+                Err(e @ Error::LimitExceeded(_)) => return Err(e),
+                Err(e) => errs.push(e),
+                Ok(_) => {}
             }
         }
         Error::error_or((), errs)
     }
 }
 
-fn write_file_impl(cabinet: &mut Cabinet<File>, file: (String, u32), base: &Path) -> Result<()> {
+fn write_file_impl<R: Read + Seek>(cabinet: &mut Cabinet<R>, archive_file: &Path, file: (String, u32, Attributes), dest: &mut Destination) -> Result<()> {
     let file_name = file.0.clone();
-    let dest_file = base.join(&file_name);
+    let Some(dest_file) = dest.path_of(&file_name)? else {
+        return Ok(());
+    };
     log::info!("extracting {file_name} ({} bytes)", file.1);
-    match create_dir_all(dest_file.parent().unwrap()) {
-        Ok(_) => {}
-        Err(e) => return Err(Error::IO(e)),
-    }
-    match File::create(dest_file) {
-        Ok(mut dest) => {
-            let mut file_from = cabinet.read_file(&file_name).unwrap();
-            match std::io::copy(&mut file_from, &mut dest) {
-                Ok(_) => Ok(()),
-                Err(e) => Err(Error::IO(e)),
-            }
-        }
-        Err(e) => Err(Error::IO(e)),
-    }
+    let mut file_from = cabinet.read_file(&file_name)
+        .map_err(|e| Error::corrupted(archive_file, Some(&file_name), e))?;
+    dest.write(&dest_file, &mut file_from, &file.2)
+        .map(|_| ())
 }
 
-fn open_cabinet(archive_file: &PathBuf) -> Result<Cabinet<File>> {
-    let cab_file = match File::open(archive_file) {
-        Ok(f) => f,
-        Err(e) => return Err(Error::IO(e)),
-    };
-    match Cabinet::new(cab_file) {
-        Ok(c) => Ok(c),
-        Err(e) => Err(Error::IO(e)),
-    }
+fn open_cabinet<R: Read + Seek>(reader: R, archive_file: &Path) -> Result<Cabinet<R>> {
+    Cabinet::new(reader)
+        .map_err(|e| Error::corrupted(archive_file, None, e))
 }
 
-fn list_impl<F, T>(archive_file: &PathBuf, mapper: F) -> Result<Vec<T>>
+fn list_impl<R: Read + Seek, F, T>(cabinet: &Cabinet<R>, mapper: F) -> Vec<T>
 where
     F: Fn(&cab::FileEntry) -> T,
 {
-    let cabinet = open_cabinet(archive_file)?;
     let mut result = vec![];
     for folder in cabinet.folder_entries() {
         for file in folder.file_entries() {
             result.push(mapper(file));
         }
     }
-    Ok(result)
+    result
 }
 
 fn convert(f: &FileEntry) -> Entry {
     let name = f.name().to_string();
     let uncompressed_size = f.uncompressed_size();
-    let mtime = f.datetime().map(to_naive_datetime);
+    let mtime = f.datetime().and_then(to_naive_datetime);
     Entry::builder()
         .name(name)
         .original_size(uncompressed_size as u64)
@@ -88,11 +77,20 @@ fn convert(f: &FileEntry) -> Entry {
         .build()
 }
 
-fn to_naive_datetime(t: time::PrimitiveDateTime) -> chrono::NaiveDateTime {
+/// The cabinet files have no unix permissions, but the local time stamps.
+fn attributes(f: &FileEntry) -> Attributes {
+    let mtime = f.datetime()
+        .and_then(to_naive_datetime)
+        .and_then(|dt| local_timestamp(&dt));
+    Attributes::builder()
+        .mtime(mtime)
+        .build()
+}
+
+fn to_naive_datetime(t: time::PrimitiveDateTime) -> Option<chrono::NaiveDateTime> {
     let timestamp = t.assume_utc().unix_timestamp();
     chrono::DateTime::from_timestamp(timestamp, 0)
-        .unwrap()
-        .naive_local()
+        .map(|dt| dt.naive_utc())
 }
 
 #[cfg(test)]
@@ -107,12 +105,12 @@ mod tests {
             Ok(r) => {
                 let r = r.iter().map(|e| e.name.clone()).collect::<Vec<_>>();
                 assert_eq!(r.len(), 16);
-                assert_eq!(r.get(0), Some("Cargo.toml".to_string()).as_ref());
+                assert_eq!(r.first(), Some("Cargo.toml".to_string()).as_ref());
                 assert_eq!(r.get(1), Some("LICENSE".to_string()).as_ref());
                 assert_eq!(r.get(2), Some("build.rs".to_string()).as_ref());
                 assert_eq!(r.get(3), Some("README.md".to_string()).as_ref());
             }
-            Err(_) => assert!(false),
+            Err(_) => panic!("unexpected result"),
         }
     }
 
@@ -127,11 +125,10 @@ mod tests {
 
         match crate::extract(archive_file, &opts) {
             Ok(_) => {
-                assert!(true);
                 assert!(PathBuf::from("results/cab/test/Cargo.toml").exists());
                 std::fs::remove_dir_all(PathBuf::from("results/cab")).unwrap();
             }
-            Err(_) => assert!(false),
+            Err(_) => panic!("unexpected result"),
         };
     }
 }
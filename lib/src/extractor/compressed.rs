@@ -0,0 +1,224 @@
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use crate::{Result, Error};
+
+use crate::extractor::{Attributes, Destination, Entry as ToteEntry, Entries, ReadSeek, ToteExtractor};
+
+/// GZIP format extractor implementation, which decompresses a single file.
+pub(super) struct GzExtractor {}
+
+/// BZIP2 format extractor implementation, which decompresses a single file.
+pub(super) struct Bz2Extractor {}
+
+/// XZ format extractor implementation, which decompresses a single file.
+pub(super) struct XzExtractor {}
+
+/// ZSTD format extractor implementation, which decompresses a single file.
+pub(super) struct ZstdExtractor {}
+
+/// LZ4 format extractor implementation, which decompresses a single file.
+pub(super) struct Lz4Extractor {}
+
+/// The name and the modification time of the original file recorded in the compressed file.
+#[derive(Default)]
+struct Original {
+    name: Option<String>,
+    mtime: Option<i64>,
+}
+
+impl ToteExtractor for GzExtractor {
+    fn list_from(&self, mut reader: Box<dyn ReadSeek + '_>, archive_file: PathBuf) -> Result<Entries> {
+        let size = stream_size(&mut reader);
+        let decoder = flate2::read::MultiGzDecoder::new(reader);
+        let original = gz_original(decoder.header());
+        list_single(decoder, archive_file, "Gz", original, size)
+    }
+
+    fn perform_from(&self, reader: Box<dyn ReadSeek + '_>, dest: &mut Destination) -> Result<()> {
+        self.perform_stream(reader, dest)
+    }
+
+    fn perform_stream(&self, reader: Box<dyn Read + '_>, dest: &mut Destination) -> Result<()> {
+        let decoder = flate2::read::MultiGzDecoder::new(reader);
+        let original = gz_original(decoder.header());
+        extract_single(decoder, dest, "Gz", original)
+    }
+}
+
+impl ToteExtractor for Bz2Extractor {
+    fn list_from(&self, mut reader: Box<dyn ReadSeek + '_>, archive_file: PathBuf) -> Result<Entries> {
+        let size = stream_size(&mut reader);
+        let decoder = bzip2::read::MultiBzDecoder::new(reader);
+        list_single(decoder, archive_file, "Bz2", Original::default(), size)
+    }
+
+    fn perform_from(&self, reader: Box<dyn ReadSeek + '_>, dest: &mut Destination) -> Result<()> {
+        self.perform_stream(reader, dest)
+    }
+
+    fn perform_stream(&self, reader: Box<dyn Read + '_>, dest: &mut Destination) -> Result<()> {
+        let decoder = bzip2::read::MultiBzDecoder::new(reader);
+        extract_single(decoder, dest, "Bz2", Original::default())
+    }
+}
+
+impl ToteExtractor for XzExtractor {
+    fn list_from(&self, mut reader: Box<dyn ReadSeek + '_>, archive_file: PathBuf) -> Result<Entries> {
+        let size = stream_size(&mut reader);
+        let decoder = xz2::read::XzDecoder::new_multi_decoder(reader);
+        list_single(decoder, archive_file, "Xz", Original::default(), size)
+    }
+
+    fn perform_from(&self, reader: Box<dyn ReadSeek + '_>, dest: &mut Destination) -> Result<()> {
+        self.perform_stream(reader, dest)
+    }
+
+    fn perform_stream(&self, reader: Box<dyn Read + '_>, dest: &mut Destination) -> Result<()> {
+        let decoder = xz2::read::XzDecoder::new_multi_decoder(reader);
+        extract_single(decoder, dest, "Xz", Original::default())
+    }
+}
+
+impl ToteExtractor for ZstdExtractor {
+    fn list_from(&self, mut reader: Box<dyn ReadSeek + '_>, archive_file: PathBuf) -> Result<Entries> {
+        let size = stream_size(&mut reader);
+        let decoder = zstd::Decoder::new(reader)
+            .map_err(|e| Error::corrupted(&archive_file, None, e))?;
+        list_single(decoder, archive_file, "Zstd", Original::default(), size)
+    }
+
+    fn perform_from(&self, reader: Box<dyn ReadSeek + '_>, dest: &mut Destination) -> Result<()> {
+        self.perform_stream(reader, dest)
+    }
+
+    fn perform_stream(&self, reader: Box<dyn Read + '_>, dest: &mut Destination) -> Result<()> {
+        let decoder = zstd::Decoder::new(reader)
+            .map_err(|e| Error::corrupted(dest.archive_file(), None, e))?;
+        extract_single(decoder, dest, "Zstd", Original::default())
+    }
+}
+
+impl ToteExtractor for Lz4Extractor {
+    fn list_from(&self, mut reader: Box<dyn ReadSeek + '_>, archive_file: PathBuf) -> Result<Entries> {
+        let size = stream_size(&mut reader);
+        let decoder = lz4_flex::frame::FrameDecoder::new(reader);
+        list_single(decoder, archive_file, "Lz4", Original::default(), size)
+    }
+
+    fn perform_from(&self, reader: Box<dyn ReadSeek + '_>, dest: &mut Destination) -> Result<()> {
+        self.perform_stream(reader, dest)
+    }
+
+    fn perform_stream(&self, reader: Box<dyn Read + '_>, dest: &mut Destination) -> Result<()> {
+        let decoder = lz4_flex::frame::FrameDecoder::new(reader);
+        extract_single(decoder, dest, "Lz4", Original::default())
+    }
+}
+
+fn gz_original(header: Option<&flate2::GzHeader>) -> Original {
+    let Some(header) = header else {
+        return Original::default();
+    };
+    Original {
+        name: header.filename().map(|name| String::from_utf8_lossy(name).to_string()),
+        mtime: Some(header.mtime() as i64).filter(|&t| t > 0),
+    }
+}
+
+fn stream_size(reader: &mut Box<dyn ReadSeek + '_>) -> Option<u64> {
+    reader.seek(SeekFrom::End(0))
+        .and_then(|size| reader.rewind().map(|_| size))
+        .ok()
+}
+
+/// Returns the name of the decompressed file.
+/// The name recorded in the compressed file (only the file name part) is preferred,
+/// otherwise, the extension of the format is stripped from the archive file name
+/// (e.g., `app.log` for `app.log.gz`), or `.out` is appended if it has no such extension.
+fn original_name(archive_file: &Path, format_name: &str, original: &Original) -> String {
+    if let Some(name) = original.name.as_ref()
+        .and_then(|name| Path::new(name).file_name())
+        .map(|name| name.to_string_lossy().to_string())
+    {
+        return name;
+    }
+    let name = archive_file.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let lower = name.to_lowercase();
+    crate::format::find_format_by_name(format_name)
+        .and_then(|format| format.exts().iter()
+            .find(|ext| lower.ends_with(ext.as_str()) && lower.len() > ext.len())
+            .and_then(|ext| name.get(..name.len() - ext.len()))
+            .map(str::to_string))
+        .unwrap_or_else(|| format!("{name}.out"))
+}
+
+fn list_single<R: Read>(mut decoder: R, archive_file: PathBuf, format_name: &str, original: Original, size: Option<u64>) -> Result<Entries> {
+    let name = original_name(&archive_file, format_name, &original);
+    let original_size = std::io::copy(&mut decoder, &mut std::io::sink())
+        .map_err(|e| Error::corrupted(&archive_file, Some(&name), e))?;
+    let date = original.mtime
+        .and_then(|t| chrono::DateTime::from_timestamp(t, 0))
+        .map(|dt| dt.naive_local());
+    let entry = ToteEntry::new(name, size, Some(original_size), None, date);
+    Ok(Entries::new(archive_file, vec![entry]))
+}
+
+fn extract_single<R: Read>(mut decoder: R, dest: &mut Destination, format_name: &str, original: Original) -> Result<()> {
+    let name = original_name(dest.archive_file(), format_name, &original);
+    let Some(dest_path) = dest.path_of(&name)? else {
+        return Ok(());
+    };
+    log::info!("extracting {name} into {dest_path:?}");
+    let attrs = Attributes::builder()
+        .mtime(original.mtime)
+        .build();
+    dest.write(&dest_path, &mut decoder, &attrs).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_list_compressed_files() {
+        let expected = std::fs::metadata("../testdata/sample/README.md").unwrap().len();
+        for ext in ["gz", "bz2", "xz", "zst", "lz4"] {
+            let archive_file = PathBuf::from(format!("../testdata/README.md.{ext}"));
+            let entries = crate::entries(&archive_file, crate::format::default_format_detector().as_ref()).unwrap();
+            assert_eq!(entries.len(), 1, "{ext}");
+            let entry = entries.iter().next().unwrap();
+            assert_eq!(entry.name, "README.md", "{ext}");
+            assert_eq!(entry.original_size, Some(expected), "{ext}");
+        }
+    }
+
+    #[test]
+    fn test_extract_compressed_files() {
+        for ext in ["gz", "bz2", "xz", "zst", "lz4"] {
+            let dest = PathBuf::from(format!("results/single/{ext}"));
+            let config = crate::ExtractConfig::builder()
+                .dest(&dest)
+                .overwrite(true)
+                .build();
+            crate::extract(format!("../testdata/README.md.{ext}"), &config).unwrap();
+            assert_eq!(
+                std::fs::read(dest.join("README.md")).unwrap(),
+                std::fs::read("../testdata/sample/README.md").unwrap(),
+                "{ext}"
+            );
+        }
+        let _ = std::fs::remove_dir_all("results/single");
+    }
+
+    #[test]
+    fn test_original_name() {
+        let named = Original { name: Some("../../etc/app.log".to_string()), mtime: None };
+        assert_eq!(original_name(Path::new("renamed.gz"), "Gz", &named), "app.log");
+        assert_eq!(original_name(Path::new("logs/app.log.GZ"), "Gz", &Original::default()), "app.log");
+        assert_eq!(original_name(Path::new("app.log.zstd"), "Zstd", &Original::default()), "app.log");
+        assert_eq!(original_name(Path::new("-"), "Xz", &Original::default()), "-.out");
+    }
+}
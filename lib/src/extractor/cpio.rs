@@ -1,7 +1,9 @@
+use std::collections::HashMap;
+use std::io::Read;
 use std::path::{Path, PathBuf};
 
 use crate::Result;
-use crate::extractor::{Entries, Entry, ToteExtractor};
+use crate::extractor::{Attributes, Destination, Entries, Entry, ReadSeek, ToteExtractor};
 
 /// CPIO format extractor implementation.
 ///
@@ -9,75 +11,127 @@ use crate::extractor::{Entries, Entry, ToteExtractor};
 pub(super) struct Extractor {}
 
 impl ToteExtractor for Extractor {
-    fn list(&self, target: PathBuf) -> Result<Entries> {
+    fn list_from(&self, reader: Box<dyn ReadSeek + '_>, target: PathBuf) -> Result<Entries> {
         log::info!("listing CPIO archive: {target:?}");
-        let mut file = std::fs::File::open(&target)
-            .map(cpio::Archive::new)
-            .map_err(crate::Error::IO)?;
-        let mut entries: Vec<Entry> = vec![];
-        loop {
-            let entry = file.read_entry()
-                .map_err(crate::Error::IO)?;
-            match entry {
-                Some(entry) => {
-                    if entry.metadata.is_file() {
-                        entries.push(create_new_entry(&entry.path, &entry.metadata));
-                    }
-                },
-                None => break,
-            }
-        }
+        let entries = list_cpio(cpio::Archive::new(reader), &target)?;
         Ok(Entries::new(target, entries))
     }
 
-    fn perform(&self, target: PathBuf, base: PathBuf) -> Result<()> {
+    fn perform_from(&self, reader: Box<dyn ReadSeek + '_>, dest: &mut Destination) -> Result<()> {
+        self.perform_stream(reader, dest)
+    }
+
+    fn perform_stream(&self, reader: Box<dyn Read + '_>, dest: &mut Destination) -> Result<()> {
+        let target = dest.archive_file().to_path_buf();
         log::info!("extracting CPIO archive: {target:?}");
-        let mut file = std::fs::File::open(&target)
-            .map(cpio::Archive::new)
-            .map_err(crate::Error::IO)?;
-        let mut errs = vec![];
-        loop {
-            let r = file.read_entry();
-            match r {
-                Ok(Some(entry)) => {
-                    if !entry.metadata.is_file() {
-                        continue;
-                    }
-                    match prepare_write(&entry, &base) {
-                        Ok(dest_path) => write_to(entry, &dest_path, &mut errs),
-                        Err(e) => errs.push(e),
-                    }
-                },
-                Ok(None) => break,
-                Err(e) => errs.push(crate::Error::IO(e)),
-            }
-        };
-        crate::Error::error_or((), errs)
+        extract_cpio(cpio::Archive::new(reader), &target, dest)
     }
 }
 
-fn prepare_write(entry: &cpio::Entry<std::fs::File>, base: &Path) -> Result<PathBuf>{
-    let dest_path = base.join(&entry.path);
-    log::info!("extracting {:?} ({} bytes) to {dest_path:?}", &entry.path, entry.metadata.size());
-    if let Some(parent) = dest_path.parent() {
-        std::fs::create_dir_all(parent)
-            .map_err(crate::Error::IO)?;
+/// Returns the file entries in the cpio archive, also used for the payload of `.rpm`.
+pub(super) fn list_cpio<R: Read>(mut archive: cpio::Archive<R>, target: &Path) -> Result<Vec<Entry>> {
+    let mut entries: Vec<Entry> = vec![];
+    loop {
+        let entry = archive.read_entry()
+            .map_err(|e| crate::Error::corrupted(target, None, e))?;
+        match entry {
+            Some(entry) => {
+                if entry.metadata.is_file() {
+                    entries.push(create_new_entry(&entry.path, &entry.metadata));
+                }
+            },
+            None => break,
+        }
+    }
+    Ok(entries)
+}
+
+pub(super) fn extract_cpio<R: Read>(mut archive: cpio::Archive<R>, target: &Path, dest: &mut Destination) -> Result<()> {
+    let mut errs = vec![];
+    let mut links = HashMap::new();
+    loop {
+        let r = archive.read_entry();
+        match r {
+            Ok(Some(entry)) => {
+                let r = if entry.metadata.is_symlink() {
+                    write_symlink(entry, target, dest)
+                } else if entry.metadata.is_file() {
+                    write_to(entry, target, dest, &mut links)
+                } else {
+                    continue;
+                };
+                match r {
+                    Err(e @ crate::Error::LimitExceeded(_)) => return Err(e),
+                    Err(e) => errs.push(e),
+                    Ok(_) => {}
+                }
+            },
+            Ok(None) => break,
+            Err(e) => {
+                errs.push(crate::Error::corrupted(target, None, e));
+                break;
+            }
+        }
+    };
+    crate::Error::error_or((), errs)
+}
+
+/// The hard links share the inode number, and the data is stored in one of them
+/// (the last one in the `newc` format, and each one in the old formats).
+/// The data is always written into the first extracted link, and the others are linked to it.
+fn write_to<R: Read>(mut entry: cpio::Entry<R>, archive_file: &Path, dest: &mut Destination, links: &mut HashMap<u64, PathBuf>) -> Result<()> {
+    let name = entry.path.to_string_lossy().to_string();
+    let Some(dest_path) = dest.path_of(&name)? else {
+        return Ok(());
     };
-    Ok(dest_path)
+    if entry.metadata.nlink() > 1 {
+        match links.get(&entry.metadata.ino()).cloned() {
+            Some(original) => {
+                if entry.metadata.size() > 0 {
+                    write_data(&mut entry, &name, &original, archive_file, dest)?;
+                }
+                return dest.hardlink(&dest_path, &original);
+            }
+            None => {
+                links.insert(entry.metadata.ino(), dest_path.clone());
+            }
+        }
+    }
+    write_data(&mut entry, &name, &dest_path, archive_file, dest)
 }
 
-fn write_to(mut entry: cpio::Entry<std::fs::File>, dest_path: &Path, errs: &mut Vec<crate::Error>) {
-    match std::fs::File::create(dest_path) {
-        Ok(mut dest_file) => if let Err(e) = std::io::copy(&mut entry.reader, &mut dest_file) {
-            errs.push(crate::Error::IO(e));
-        },
+fn write_data<R: Read>(entry: &mut cpio::Entry<R>, name: &str, dest_path: &Path, archive_file: &Path, dest: &mut Destination) -> Result<()> {
+    log::info!("extracting {name} ({} bytes) to {dest_path:?}", entry.metadata.size());
+    let attrs = Attributes::builder()
+        .mode(entry.metadata.mode())
+        .mtime(entry.metadata.mtime() as i64)
+        .uid(entry.metadata.uid())
+        .gid(entry.metadata.gid())
+        .build();
+    match dest.write(dest_path, &mut entry.reader, &attrs) {
+        Ok(written) if written != entry.metadata.size() => {
+            Err(crate::Error::corrupted(archive_file, Some(name), "unexpected end of the entry"))
+        }
+        Ok(_) => Ok(()),
         Err(e) => {
-            log::error!("failed to create file {dest_path:?}: {e}");
-            errs.push(crate::Error::IO(e));
+            log::error!("failed to write file {dest_path:?}: {e}");
+            Err(e)
         }
     }
 }
 
+fn write_symlink<R: Read>(mut entry: cpio::Entry<R>, archive_file: &Path, dest: &mut Destination) -> Result<()> {
+    let name = entry.path.to_string_lossy().to_string();
+    let Some(dest_path) = dest.path_of(&name)? else {
+        return Ok(());
+    };
+    let mut target = vec![];
+    entry.reader.read_to_end(&mut target)
+        .map_err(|e| crate::Error::corrupted(archive_file, Some(&name), e))?;
+    let target = String::from_utf8_lossy(&target);
+    dest.symlink(&dest_path, target.trim_end_matches('\0'))
+}
+
 fn create_new_entry(path: &Path, entry: &cpio::Metadata) -> Entry {
     let timestamp = entry.mtime();
     let ndt = chrono::DateTime::from_timestamp(timestamp as i64, 0)
@@ -102,12 +156,12 @@ mod tests {
             Ok(r) => {
                 let r = r.iter().map(|e| e.name.clone()).collect::<Vec<_>>();
                 assert_eq!(r.len(), 16);
-                assert_eq!(r.get(0), Some("./Cargo.toml".to_string()).as_ref());
+                assert_eq!(r.first(), Some("./Cargo.toml".to_string()).as_ref());
                 assert_eq!(r.get(1), Some("./LICENSE".to_string()).as_ref());
                 assert_eq!(r.get(2), Some("./build.rs".to_string()).as_ref());
                 assert_eq!(r.get(3), Some("./README.md".to_string()).as_ref());
             }
-            Err(_) => assert!(false),
+            Err(_) => panic!("unexpected result"),
         }
     }
 
@@ -121,13 +175,12 @@ mod tests {
             .build();
         match crate::extract(archive_file, &opts) {
             Ok(_) => {
-                assert!(true);
                 assert!(PathBuf::from("results/cpio/test/Cargo.toml").exists());
                 std::fs::remove_dir_all(PathBuf::from("results/cpio")).unwrap();
             }
             Err(e) => {
                 eprintln!("{:?}", e);
-                assert!(false);
+                panic!("unexpected result");
             }
         };
     }
@@ -0,0 +1,176 @@
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::{Result, Error};
+
+use crate::extractor::{Destination, Entries, ReadSeek, ToteExtractor};
+use crate::extractor::{ar, tar};
+
+/// The directory of the control files in the logical tree of the package, the same as `dpkg-deb --raw-extract`.
+const CONTROL_DIR: &str = "DEBIAN";
+
+/// DEB format extractor implementation.
+/// The data files and the control files (under `DEBIAN/`) in the package are treated as one tree.
+/// The control files are extracted only if [`ExtractConfig::package_control`](crate::ExtractConfig::package_control) is `true`.
+pub(super) struct Extractor {}
+
+/// The members of the deb package.
+#[derive(Clone, Copy, PartialEq)]
+enum Member {
+    Control,
+    Data,
+}
+
+impl ToteExtractor for Extractor {
+    fn list_from(&self, reader: Box<dyn ReadSeek + '_>, archive_file: PathBuf) -> Result<Entries> {
+        let mut result = vec![];
+        let mut found = false;
+        ar::for_each_member(reader, &archive_file, |name, entry| {
+            let Some((member, format_name)) = member_of(name) else {
+                return check_member(name, entry, &archive_file);
+            };
+            found |= member == Member::Data;
+            let archive = tar::open_tar_by_format(Box::new(entry), &archive_file, &format_name)?;
+            for mut e in tar::tar_entries(archive, &archive_file)? {
+                if let Some(name) = logical_name(member, &e.name) {
+                    e.name = name;
+                    result.push(e);
+                }
+            }
+            Ok(())
+        })?;
+        if !found {
+            return Err(Error::corrupted(&archive_file, None, "no data.tar member"));
+        }
+        Ok(Entries::new(archive_file, result))
+    }
+
+    fn perform_from(&self, reader: Box<dyn ReadSeek + '_>, dest: &mut Destination) -> Result<()> {
+        self.perform_stream(reader, dest)
+    }
+
+    fn perform_stream(&self, reader: Box<dyn Read + '_>, dest: &mut Destination) -> Result<()> {
+        let archive_file = dest.archive_file().to_path_buf();
+        let control = dest.config().package_control;
+        let mut found = false;
+        ar::for_each_member(reader, &archive_file, |name, entry| {
+            let Some((member, format_name)) = member_of(name) else {
+                return check_member(name, entry, &archive_file);
+            };
+            if member == Member::Control && !control {
+                return Ok(());
+            }
+            found |= member == Member::Data;
+            let archive = tar::open_tar_by_format(Box::new(entry), &archive_file, &format_name)?;
+            tar::extract_tar_with(archive, &archive_file, dest, |name| logical_name(member, name))
+        })?;
+        if !found {
+            return Err(Error::corrupted(&archive_file, None, "no data.tar member"));
+        }
+        Ok(())
+    }
+}
+
+/// Returns the kind of the given member and the format name of its tarball,
+/// e.g., `(Member::Data, "TarXz")` for `data.tar.xz`.
+fn member_of(name: &str) -> Option<(Member, String)> {
+    // GNU ar terminates the member names with '/'.
+    let name = name.trim_end_matches('/');
+    let (member, ext) = if let Some(ext) = name.strip_prefix("control") {
+        (Member::Control, ext)
+    } else if let Some(ext) = name.strip_prefix("data") {
+        (Member::Data, ext)
+    } else {
+        return None;
+    };
+    crate::format::find_format_by_ext(ext)
+        .filter(|format| format.name.starts_with("Tar"))
+        .map(|format| (member, format.name.clone()))
+}
+
+/// Checks the version in `debian-binary`, and ignores the other unknown members (e.g., the signatures).
+fn check_member<R: Read>(name: &str, entry: &mut R, archive_file: &Path) -> Result<()> {
+    if name.trim_end_matches('/') != "debian-binary" {
+        log::info!("{name}: unknown member in the deb package, skipped");
+        return Ok(());
+    }
+    let mut version = String::new();
+    entry.read_to_string(&mut version)
+        .map_err(|e| Error::corrupted(archive_file, Some(name), e))?;
+    if version.trim().starts_with("2.") {
+        Ok(())
+    } else {
+        Err(Error::corrupted(archive_file, Some(name), format!("unsupported package version: {}", version.trim())))
+    }
+}
+
+/// Returns the name in the logical tree for the entry name in the given member,
+/// or `None` for the root directory of the member.
+fn logical_name(member: Member, name: &str) -> Option<String> {
+    let name = name.trim_start_matches("./").trim_end_matches('/');
+    if name.is_empty() || name == "." {
+        return None;
+    }
+    match member {
+        Member::Control => Some(format!("{CONTROL_DIR}/{name}")),
+        Member::Data => Some(name.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_list_deb_file() {
+        let file = PathBuf::from("../testdata/test.deb");
+        let extractor = Extractor {};
+        let entries = extractor.list(file).unwrap();
+        let names = entries.iter().map(|e| e.name.clone()).collect::<Vec<_>>();
+        assert!(names.contains(&"DEBIAN/control".to_string()), "{names:?}");
+        assert!(names.contains(&"DEBIAN/postinst".to_string()), "{names:?}");
+        assert!(names.contains(&"usr/bin/totebag-sample".to_string()), "{names:?}");
+        assert!(names.contains(&"usr/share/doc/totebag-sample/README.md".to_string()), "{names:?}");
+        assert!(!names.iter().any(|name| name.starts_with("./") || name.is_empty()), "{names:?}");
+    }
+
+    #[test]
+    fn test_extract_deb_archive() {
+        let config = crate::ExtractConfig::builder()
+            .dest("results/deb/data")
+            .overwrite(true)
+            .build();
+        crate::extract("../testdata/test.deb", &config).unwrap();
+        let dest = PathBuf::from("results/deb/data");
+        assert_eq!(
+            std::fs::read(dest.join("usr/share/doc/totebag-sample/README.md")).unwrap(),
+            std::fs::read("../testdata/sample/README.md").unwrap()
+        );
+        assert!(dest.join("usr/bin/totebag-sample").exists());
+        assert!(!dest.join("DEBIAN").exists());
+        #[cfg(unix)]
+        assert_eq!(
+            std::fs::read_link(dest.join("usr/bin/totebag-hello")).unwrap(),
+            PathBuf::from("totebag-sample")
+        );
+
+        let config = crate::ExtractConfig::builder()
+            .dest("results/deb/raw")
+            .overwrite(true)
+            .package_control(true)
+            .build();
+        crate::extract("../testdata/test.deb", &config).unwrap();
+        let control = std::fs::read_to_string("results/deb/raw/DEBIAN/control").unwrap();
+        assert!(control.contains("Package: totebag-sample"));
+        let _ = std::fs::remove_dir_all("results/deb");
+    }
+
+    #[test]
+    fn test_member_of() {
+        assert!(member_of("data.tar.xz") == Some((Member::Data, "TarXz".to_string())));
+        assert!(member_of("control.tar.gz/") == Some((Member::Control, "TarGz".to_string())));
+        assert!(member_of("data.tar") == Some((Member::Data, "Tar".to_string())));
+        assert!(member_of("data.zip").is_none());
+        assert!(member_of("_gpgorigin").is_none());
+    }
+}
@@ -0,0 +1,921 @@
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, create_dir_all};
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, SystemTime};
+
+use chrono::{Local, NaiveDateTime, TimeZone};
+use typed_builder::TypedBuilder;
+
+use crate::{Error, ExtractConfig, OverwritePolicy, Result, UnsafePathPolicy};
+
+use super::selection::Selection;
+use super::Entries;
+
+/// The attributes of an archive entry, which are restored by [`Destination::write`].
+/// Each field is `None` if the archive format does not record it.
+#[derive(Debug, Clone, Default, TypedBuilder)]
+pub struct Attributes {
+    /// The unix permission bits (the file type bits are ignored).
+    #[builder(default, setter(into))]
+    pub mode: Option<u32>,
+    /// The last modified time in seconds since the unix epoch.
+    #[builder(default, setter(into))]
+    pub mtime: Option<i64>,
+    /// The user id of the owner.
+    #[builder(default, setter(into))]
+    pub uid: Option<u32>,
+    /// The group id of the owner.
+    #[builder(default, setter(into))]
+    pub gid: Option<u32>,
+}
+
+/// The destination directory of the extraction.
+///
+/// Each [`ToteExtractor`](crate::extractor::ToteExtractor) resolves the entry names and writes
+/// the entry data through this struct, so that the policies in [`ExtractConfig`] are applied uniformly
+/// regardless of the archive format.
+///
+/// The size limits in [`ExtractConfig`] are checked against the actually written bytes.
+/// When any limit is exceeded, the files and directories created by this struct are removed.
+///
+/// # Example
+///
+/// ```
+/// use totebag::ExtractConfig;
+/// use totebag::extractor::Destination;
+///
+/// let config = ExtractConfig::builder().build();
+/// let dest = Destination::new("results", &config);
+/// assert!(dest.path_of("../../etc/passwd").is_err());
+/// ```
+pub struct Destination<'a> {
+    base: PathBuf,
+    config: &'a ExtractConfig,
+    archive_file: PathBuf,
+    archive_size: Option<u64>,
+    /// the bytes consumed from the archive stream, which is used instead of `archive_size`.
+    consumed: Option<Rc<Cell<u64>>>,
+    selection: std::result::Result<Selection, String>,
+    total: u64,
+    count: usize,
+    created: Vec<PathBuf>,
+    extracted: HashSet<PathBuf>,
+    renamed: HashMap<PathBuf, PathBuf>,
+}
+
+impl<'a> Destination<'a> {
+    pub fn new<P: AsRef<Path>>(base: P, config: &'a ExtractConfig) -> Self {
+        Self {
+            base: base.as_ref().to_path_buf(),
+            config,
+            archive_file: PathBuf::new(),
+            archive_size: None,
+            consumed: None,
+            selection: Selection::new(config).map_err(|e| e.to_string()),
+            total: 0,
+            count: 0,
+            created: vec![],
+            extracted: HashSet::new(),
+            renamed: HashMap::new(),
+        }
+    }
+
+    /// Sets the archive file for reporting the errors,
+    /// and its size for checking [`ExtractConfig::max_ratio`].
+    pub fn with_archive_file<P: AsRef<Path>>(mut self, archive_file: P) -> Self {
+        self.archive_file = archive_file.as_ref().to_path_buf();
+        if let Ok(metadata) = self.archive_file.metadata() {
+            self.archive_size = Some(metadata.len());
+        }
+        self
+    }
+
+    /// Sets the size of the archive file for checking [`ExtractConfig::max_ratio`].
+    pub fn with_archive_size(mut self, size: u64) -> Self {
+        self.archive_size = Some(size);
+        self
+    }
+
+    /// Sets the archive stream (`-`), and returns the reader counting the bytes consumed from it,
+    /// so that [`ExtractConfig::max_ratio`] is checked against the consumed bytes.
+    pub(crate) fn with_stream<R: Read>(mut self, reader: R) -> (Self, CountingReader<R>) {
+        let consumed = Rc::new(Cell::new(0));
+        self.archive_file = PathBuf::from("-");
+        self.consumed = Some(consumed.clone());
+        (self, CountingReader { reader, consumed })
+    }
+
+    /// Returns the base directory of the extraction.
+    pub fn base(&self) -> &Path {
+        &self.base
+    }
+
+    /// Returns the archive file given by [`Destination::with_archive_file`].
+    pub fn archive_file(&self) -> &Path {
+        &self.archive_file
+    }
+
+    /// Returns the configuration of the extraction.
+    pub fn config(&self) -> &ExtractConfig {
+        self.config
+    }
+
+    /// Returns the path in the destination directory for the given entry name.
+    ///
+    /// The absolute paths, the drive prefixes, and the `..` components in the entry name
+    /// are handled by [`ExtractConfig::unsafe_path`].
+    /// Then, the entry name is rewritten by [`ExtractConfig::strip_components`] and [`ExtractConfig::transforms`].
+    /// Returns `Ok(None)` if the entry should not be extracted, e.g., it is not selected by
+    /// [`ExtractConfig::entries`], [`ExtractConfig::includes`], and [`ExtractConfig::excludes`].
+    pub fn path_of<S: AsRef<str>>(&self, name: S) -> Result<Option<PathBuf>> {
+        let name = name.as_ref();
+        let selection = self.selection.as_ref()
+            .map_err(|message| Error::Extractor(message.clone()))?;
+        let (path, safe) = sanitize(name);
+        if !safe && !self.accept_unsafe(name, &path)? {
+            return Ok(None);
+        }
+        if path.as_os_str().is_empty() || !selection.is_selected(&path, name.ends_with('/')) {
+            return Ok(None);
+        }
+        let mut path = path.components()
+            .skip(self.config.strip_components)
+            .collect::<PathBuf>();
+        if !self.config.transforms.is_empty() && !path.as_os_str().is_empty() {
+            let slashed = path.components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            let rewritten = self.config.transforms.iter()
+                .fold(slashed, |name, t| t.apply(&name));
+            log::debug!("{name}: rewritten to {rewritten}");
+            let (rewritten_path, safe) = sanitize(&rewritten);
+            if !safe && !self.accept_unsafe(&rewritten, &rewritten_path)? {
+                return Ok(None);
+            }
+            path = rewritten_path;
+        }
+        if path.as_os_str().is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(self.base.join(path)))
+        }
+    }
+
+    /// Returns the top-level names of the given entries in the destination directory,
+    /// i.e., the first components of the paths returned by [`Destination::path_of`].
+    /// The entries which are not extracted, or rejected as the unsafe paths, are ignored.
+    pub(crate) fn roots(&self, entries: &Entries) -> HashSet<PathBuf> {
+        entries.iter()
+            .filter_map(|e| self.path_of(&e.name).ok().flatten())
+            .filter_map(|path| path.strip_prefix(&self.base).ok()
+                .and_then(|rel| rel.components().next())
+                .map(|c| PathBuf::from(c.as_os_str())))
+            .collect()
+    }
+
+    /// Applies [`ExtractConfig::unsafe_path`] to the unsafe entry name,
+    /// and returns `Ok(false)` if the entry should be skipped.
+    fn accept_unsafe(&self, name: &str, path: &Path) -> Result<bool> {
+        match self.config.unsafe_path {
+            UnsafePathPolicy::Reject => Err(Error::UnsafePath(name.to_string())),
+            UnsafePathPolicy::Skip => {
+                log::warn!("{name}: skip the unsafe path");
+                Ok(false)
+            }
+            UnsafePathPolicy::Strip => {
+                log::warn!("{name}: strip the unsafe path to {path:?}");
+                Ok(true)
+            }
+        }
+    }
+
+    /// Returns the names in [`ExtractConfig::entries`] which matched no entries so far.
+    pub fn unmatched_entries(&self) -> Vec<PathBuf> {
+        match &self.selection {
+            Ok(selection) => selection.unmatched(),
+            Err(_) => vec![],
+        }
+    }
+
+    /// Creates the given directory (returned by [`Destination::path_of`]) and its parents.
+    pub fn create_dir(&mut self, path: &Path) -> Result<()> {
+        self.ensure_inside(path)?;
+        let mut missing = path.ancestors()
+            .take_while(|p| !p.as_os_str().is_empty() && !p.exists())
+            .map(Path::to_path_buf)
+            .collect::<Vec<_>>();
+        create_dir_all(path).map_err(Error::IO)?;
+        missing.reverse();
+        self.created.extend(missing);
+        self.ensure_inside(path)
+    }
+
+    /// Restores the attributes of the given directory (created by [`Destination::create_dir`]).
+    /// Call this after writing the entries in the directory, because writing them updates its mtime.
+    pub fn restore_dir(&self, path: &Path, attrs: &Attributes) -> Result<()> {
+        self.ensure_inside(path)?;
+        self.restore(path, attrs)
+    }
+
+    /// Confirms that writing an entry of the given size does not exceed the limits in [`ExtractConfig`].
+    /// This is useful for the formats that read the whole entry into memory before writing it.
+    pub fn reserve(&mut self, path: &Path, size: u64) -> Result<()> {
+        match self.exceeded_limit(size, self.total + size) {
+            Some(message) => self.abort(path, message),
+            None => Ok(()),
+        }
+    }
+
+    /// Writes the data from `reader` into the given file (returned by [`Destination::path_of`]),
+    /// and restores the given attributes of the file.
+    /// The parent directories are created if needed.
+    /// The existing file is handled by [`ExtractConfig::overwrite_policy`], and the skipped entry
+    /// is read through without writing so that the returned size can be verified by the extractors.
+    /// The existing file is replaced after the whole entry is written, and is kept if any limit is exceeded.
+    /// The errors on reading `reader` are reported as [`Error::Corrupted`].
+    pub fn write<R: Read + ?Sized>(&mut self, path: &Path, reader: &mut R, attrs: &Attributes) -> Result<u64> {
+        let Some(path) = self.resolve_existing(path, attrs.mtime) else {
+            return match self.copy_limited(path, reader, &mut std::io::sink())? {
+                (written, None) => Ok(written),
+                (_, Some(message)) => self.abort(path, message),
+            };
+        };
+        self.prepare(&path)?;
+        let existing = path.symlink_metadata().is_ok();
+        let target = if existing {
+            partial_path(&path)
+        } else {
+            path.clone()
+        };
+        let mut file = File::create(&target).map_err(Error::IO)?;
+        self.created.push(target.clone());
+        let r = self.copy_limited(&path, reader, &mut file);
+        drop(file);
+        match r {
+            Ok((_, Some(message))) => self.abort(&path, message),
+            Ok((written, None)) => {
+                if existing {
+                    self.created.pop();
+                    remove_file_if_exists(&path)?;
+                    std::fs::rename(&target, &path).map_err(Error::IO)?;
+                }
+                self.restore(&path, attrs).map(|_| written)
+            }
+            Err(e) => {
+                if existing {
+                    self.created.pop();
+                    let _ = std::fs::remove_file(&target);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Copies the data from `reader` into `writer` while counting the bytes against the limits,
+    /// and returns the copied size, and the message of the exceeded limit if any.
+    fn copy_limited<R: Read + ?Sized, W: Write>(&mut self, path: &Path, reader: &mut R, writer: &mut W) -> Result<(u64, Option<String>)> {
+        let mut buffer = vec![0u8; 64 * 1024];
+        let mut written = 0u64;
+        loop {
+            let n = match reader.read(&mut buffer) {
+                Ok(0) => return Ok((written, None)),
+                Ok(n) => n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(self.corrupted(path, e)),
+            };
+            written += n as u64;
+            self.total += n as u64;
+            if let Some(message) = self.exceeded_limit(written, self.total) {
+                return Ok((written, Some(message)));
+            }
+            writer.write_all(&buffer[..n]).map_err(Error::IO)?;
+        }
+    }
+
+    fn corrupted(&self, path: &Path, e: std::io::Error) -> Error {
+        let name = path.strip_prefix(&self.base).unwrap_or(path);
+        Error::corrupted(&self.archive_file, Some(&name.to_string_lossy()), e)
+    }
+
+    /// Applies [`ExtractConfig::overwrite_policy`] if the given path already exists,
+    /// and returns the path to write the entry, or `None` if the entry should be skipped.
+    /// The files extracted from the same archive are always overwritten by the later entries.
+    fn resolve_existing(&mut self, path: &Path, mtime: Option<i64>) -> Option<PathBuf> {
+        if let Some(renamed) = self.renamed.get(path) {
+            return Some(renamed.clone());
+        }
+        if self.extracted.contains(path) {
+            return Some(path.to_path_buf());
+        }
+        let Ok(metadata) = path.symlink_metadata() else {
+            self.extracted.insert(path.to_path_buf());
+            return Some(path.to_path_buf());
+        };
+        let name = path.strip_prefix(&self.base).unwrap_or(path).display();
+        let overwrite = match self.config.overwrite_policy {
+            OverwritePolicy::Always => true,
+            OverwritePolicy::Never => false,
+            OverwritePolicy::IfNewer => {
+                let existing = metadata.modified().ok();
+                mtime.and_then(to_system_time)
+                    .zip(existing)
+                    .is_some_and(|(entry, existing)| entry > existing)
+            }
+            OverwritePolicy::Ask => self.config.overwrite_prompt.as_ref()
+                .is_some_and(|prompt| prompt(path)),
+            OverwritePolicy::Rename => {
+                let renamed = (1..).map(|i| {
+                    let mut name = path.as_os_str().to_os_string();
+                    name.push(format!(".{i}"));
+                    PathBuf::from(name)
+                }).find(|p| p.symlink_metadata().is_err())?;
+                log::info!("{name}: already exists, extract to {renamed:?}");
+                self.renamed.insert(path.to_path_buf(), renamed.clone());
+                self.extracted.insert(renamed.clone());
+                return Some(renamed);
+            }
+        };
+        if overwrite {
+            self.extracted.insert(path.to_path_buf());
+            Some(path.to_path_buf())
+        } else {
+            log::info!("{name}: already exists, skip");
+            None
+        }
+    }
+
+    /// Creates the symbolic link at the given path (returned by [`Destination::path_of`]) pointing to `target`.
+    /// The link whose target escapes the destination directory is handled by [`ExtractConfig::unsafe_path`],
+    /// and [`UnsafePathPolicy::Strip`] skips such link as [`UnsafePathPolicy::Skip`] does.
+    pub fn symlink(&mut self, path: &Path, target: &str) -> Result<()> {
+        let Some(path) = self.resolve_existing(path, None) else {
+            return Ok(());
+        };
+        let path = path.as_path();
+        self.prepare(path)?;
+        if !self.is_link_target_inside(path, target)? {
+            let name = path.strip_prefix(&self.base).unwrap_or(path).display();
+            match self.config.unsafe_path {
+                UnsafePathPolicy::Reject => return Err(Error::UnsafePath(format!("{name} -> {target}"))),
+                UnsafePathPolicy::Skip | UnsafePathPolicy::Strip => {
+                    log::warn!("{name} -> {target}: skip the link escaping the destination");
+                    return Ok(());
+                }
+            }
+        }
+        let existing = path.symlink_metadata().is_ok();
+        remove_file_if_exists(path)?;
+        create_symlink(target, path).map_err(Error::IO)?;
+        if !existing {
+            self.created.push(path.to_path_buf());
+        }
+        Ok(())
+    }
+
+    /// Creates the hard link at the given path pointing to the previously extracted `original` file.
+    /// Both paths should be returned by [`Destination::path_of`].
+    pub fn hardlink(&mut self, path: &Path, original: &Path) -> Result<()> {
+        let Some(path) = self.resolve_existing(path, None) else {
+            return Ok(());
+        };
+        let path = path.as_path();
+        let original = self.renamed.get(original).cloned()
+            .unwrap_or_else(|| original.to_path_buf());
+        let original = original.as_path();
+        self.prepare(path)?;
+        self.ensure_inside(original)?;
+        if !original.symlink_metadata().is_ok_and(|m| m.is_file()) {
+            let name = path.strip_prefix(&self.base).unwrap_or(path).display();
+            return Err(Error::Extractor(format!("{name}: the link target {original:?} is not extracted")));
+        }
+        let existing = path.symlink_metadata().is_ok();
+        remove_file_if_exists(path)?;
+        std::fs::hard_link(original, path).map_err(Error::IO)?;
+        if !existing {
+            self.created.push(path.to_path_buf());
+        }
+        Ok(())
+    }
+
+    /// Counts the entry, and creates the parent directory of the given path.
+    fn prepare(&mut self, path: &Path) -> Result<()> {
+        self.count += 1;
+        if let Some(max) = self.config.max_entries
+            && self.count > max
+        {
+            return self.abort(path, format!("the number of entries exceeds {max}"));
+        }
+        if let Some(parent) = path.parent() {
+            self.create_dir(parent)?;
+        }
+        self.ensure_inside(path)
+    }
+
+    /// Resolves the link target from the (canonicalized) parent of the link.
+    /// The `..` components are allowed only just after the existing directories,
+    /// so that the links extracted later cannot change the resolved path.
+    fn is_link_target_inside(&self, path: &Path, target: &str) -> Result<bool> {
+        let base = self.base.canonicalize().map_err(Error::IO)?;
+        let Some(parent) = path.parent() else {
+            return Ok(false);
+        };
+        let mut current = parent.canonicalize().map_err(Error::IO)?;
+        let (target, safe) = strip_drive_prefix(target);
+        if !safe {
+            return Ok(false);
+        }
+        for c in Path::new(target).components() {
+            match c {
+                Component::Normal(name) => {
+                    current.push(name);
+                    if let Ok(resolved) = current.canonicalize() {
+                        current = resolved;
+                    }
+                }
+                Component::ParentDir => {
+                    if !current.symlink_metadata().is_ok_and(|m| m.is_dir()) {
+                        return Ok(false);
+                    }
+                    current.pop();
+                }
+                Component::CurDir => {}
+                Component::Prefix(_) | Component::RootDir => return Ok(false),
+            }
+        }
+        Ok(current.starts_with(&base))
+    }
+
+    /// Restores the attributes of the given file (written by [`Destination::write`]) or directory
+    /// according to [`ExtractConfig::preserve_mtime`], [`ExtractConfig::preserve_permissions`],
+    /// and [`ExtractConfig::preserve_ownership`].
+    fn restore(&self, path: &Path, attrs: &Attributes) -> Result<()> {
+        let config = self.config;
+        if config.preserve_mtime
+            && let Some(mtime) = attrs.mtime.and_then(to_system_time)
+        {
+            let file = if path.is_dir() {
+                File::open(path)
+            } else {
+                File::options().write(true).open(path)
+            };
+            file.and_then(|f| f.set_modified(mtime))
+                .map_err(Error::IO)?;
+        }
+        #[cfg(unix)]
+        if config.preserve_ownership
+            && (attrs.uid.is_some() || attrs.gid.is_some())
+            && is_root()
+        {
+            std::os::unix::fs::chown(path, attrs.uid, attrs.gid).map_err(Error::IO)?;
+        }
+        if config.preserve_permissions
+            && let Some(mode) = attrs.mode
+        {
+            let mode = if config.strip_setuid {
+                mode & 0o1777
+            } else {
+                mode & 0o7777
+            };
+            set_mode(path, mode)?;
+        }
+        Ok(())
+    }
+
+    fn exceeded_limit(&self, entry_size: u64, total: u64) -> Option<String> {
+        let config = self.config;
+        if let Some(max) = config.max_entry_size
+            && entry_size > max
+        {
+            Some(format!("the entry size exceeds {max} bytes"))
+        } else if let Some(max) = config.max_total_size
+            && total > max
+        {
+            Some(format!("the total size exceeds {max} bytes"))
+        } else if let (Some(max), Some(size)) = (config.max_ratio, self.archive_size())
+            && size > 0
+            && total as f64 / size as f64 > max
+        {
+            Some(format!("the compression ratio exceeds {max}"))
+        } else {
+            None
+        }
+    }
+
+    fn archive_size(&self) -> Option<u64> {
+        match &self.consumed {
+            Some(consumed) => Some(consumed.get()),
+            None => self.archive_size,
+        }
+    }
+
+    /// Removes the files and directories created so far, and returns [`Error::LimitExceeded`].
+    /// The files existing before the extraction are not removed.
+    fn abort<T>(&mut self, path: &Path, message: String) -> Result<T> {
+        let name = path.strip_prefix(&self.base).unwrap_or(path);
+        log::error!("{name:?}: {message}, remove the extracted files");
+        for created in self.created.drain(..).rev() {
+            let r = if created.is_dir() {
+                std::fs::remove_dir(&created)
+            } else {
+                std::fs::remove_file(&created)
+            };
+            if let Err(e) = r {
+                log::warn!("{created:?}: failed to remove ({e})");
+            }
+        }
+        Err(Error::LimitExceeded(format!("{}: {message}", name.display())))
+    }
+
+    /// Confirms that no symbolic link on the way from the base directory to the given path
+    /// points to the outside of the base directory.
+    fn ensure_inside(&self, path: &Path) -> Result<()> {
+        let Ok(rel) = path.strip_prefix(&self.base) else {
+            return Err(Error::UnsafePath(path.display().to_string()));
+        };
+        let mut current = self.base.clone();
+        for c in rel.components() {
+            current.push(c);
+            match current.symlink_metadata() {
+                Ok(m) if m.file_type().is_symlink() => {
+                    let base = self.base.canonicalize().map_err(Error::IO)?;
+                    match current.canonicalize() {
+                        Ok(target) if target.starts_with(&base) => {}
+                        _ => return Err(Error::UnsafePath(rel.display().to_string())),
+                    }
+                }
+                Ok(_) => {}
+                Err(_) => break,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The reader counting the bytes read from the archive stream.
+pub(crate) struct CountingReader<R: Read> {
+    reader: R,
+    consumed: Rc<Cell<u64>>,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.reader.read(buf)?;
+        self.consumed.set(self.consumed.get() + n as u64);
+        Ok(n)
+    }
+}
+
+/// Returns the temporary path to write the entry replacing the existing file.
+fn partial_path(path: &Path) -> PathBuf {
+    let mut partial = path.as_os_str().to_os_string();
+    partial.push(".totebag-partial");
+    PathBuf::from(partial)
+}
+
+fn remove_file_if_exists(path: &Path) -> Result<()> {
+    match path.symlink_metadata() {
+        Ok(m) if !m.is_dir() => std::fs::remove_file(path).map_err(Error::IO),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(unix)]
+fn create_symlink(target: &str, path: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(target, path)
+}
+
+#[cfg(windows)]
+fn create_symlink(target: &str, path: &Path) -> std::io::Result<()> {
+    std::os::windows::fs::symlink_file(target, path)
+}
+
+/// Converts the given local date time (e.g., MS-DOS time stamps in zip) into seconds since the unix epoch.
+pub(crate) fn local_timestamp(datetime: &NaiveDateTime) -> Option<i64> {
+    Local.from_local_datetime(datetime)
+        .earliest()
+        .map(|dt| dt.timestamp())
+}
+
+fn to_system_time(mtime: i64) -> Option<SystemTime> {
+    if mtime >= 0 {
+        SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(mtime as u64))
+    } else {
+        SystemTime::UNIX_EPOCH.checked_sub(Duration::from_secs(mtime.unsigned_abs()))
+    }
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+        .map_err(Error::IO)
+}
+
+#[cfg(not(unix))]
+fn set_mode(path: &Path, mode: u32) -> Result<()> {
+    let mut permissions = std::fs::metadata(path).map_err(Error::IO)?.permissions();
+    permissions.set_readonly(mode & 0o222 == 0);
+    std::fs::set_permissions(path, permissions).map_err(Error::IO)
+}
+
+#[cfg(unix)]
+fn is_root() -> bool {
+    // SAFETY: geteuid has no preconditions and never fails.
+    unsafe { libc::geteuid() == 0 }
+}
+
+/// Removes the root, the drive prefix, the `.` and `..` components from the given entry name.
+/// The second value of the result is `false` if the name contains any unsafe component.
+pub(crate) fn sanitize(name: &str) -> (PathBuf, bool) {
+    let (name, mut safe) = strip_drive_prefix(name);
+    let mut result = PathBuf::new();
+    for c in Path::new(name).components() {
+        match c {
+            Component::Normal(s) => result.push(s),
+            Component::CurDir => {}
+            Component::Prefix(_) | Component::RootDir | Component::ParentDir => safe = false,
+        }
+    }
+    (result, safe)
+}
+
+/// Strips the drive prefix (e.g., `C:`) from the name even if the running platform is not Windows.
+fn strip_drive_prefix(name: &str) -> (&str, bool) {
+    let bytes = name.as_bytes();
+    if bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' {
+        (&name[2..], false)
+    } else {
+        (name, true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extractor::Transform;
+
+    #[test]
+    fn test_sanitize() {
+        assert_eq!(sanitize("a/b/c.txt"), (PathBuf::from("a/b/c.txt"), true));
+        assert_eq!(sanitize("./a/./b"), (PathBuf::from("a/b"), true));
+        assert_eq!(sanitize("../../etc/x"), (PathBuf::from("etc/x"), false));
+        assert_eq!(sanitize("a/../../x"), (PathBuf::from("a/x"), false));
+        assert_eq!(sanitize("/abs/path"), (PathBuf::from("abs/path"), false));
+        assert_eq!(sanitize("C:/windows/x"), (PathBuf::from("windows/x"), false));
+        assert_eq!(sanitize(".."), (PathBuf::new(), false));
+    }
+
+    #[test]
+    fn test_path_of() {
+        let reject = ExtractConfig::builder().build();
+        let dest = Destination::new("results", &reject);
+        assert_eq!(dest.path_of("a/b").unwrap(), Some(PathBuf::from("results/a/b")));
+        assert!(matches!(dest.path_of("../x"), Err(Error::UnsafePath(n)) if n == "../x"));
+
+        let skip = ExtractConfig::builder().unsafe_path(UnsafePathPolicy::Skip).build();
+        let dest = Destination::new("results", &skip);
+        assert_eq!(dest.path_of("/etc/passwd").unwrap(), None);
+
+        let strip = ExtractConfig::builder().unsafe_path(UnsafePathPolicy::Strip).build();
+        let dest = Destination::new("results", &strip);
+        assert_eq!(dest.path_of("/etc/passwd").unwrap(), Some(PathBuf::from("results/etc/passwd")));
+        assert_eq!(dest.path_of("../").unwrap(), None);
+    }
+
+    #[test]
+    fn test_rewrite_path() {
+        let config = ExtractConfig::builder().strip_components(1).build();
+        let dest = Destination::new("results", &config);
+        assert_eq!(dest.path_of("project-1.2.3/src/main.rs").unwrap(), Some(PathBuf::from("results/src/main.rs")));
+        assert_eq!(dest.path_of("./project-1.2.3/README.md").unwrap(), Some(PathBuf::from("results/README.md")));
+        assert_eq!(dest.path_of("project-1.2.3/").unwrap(), None);
+
+        let config = ExtractConfig::builder()
+            .strip_components(1)
+            .transforms(vec![
+                Transform::new("s/^src/lib/").unwrap(),
+                Transform::new(r"s/\.txt$/.md/").unwrap(),
+            ])
+            .build();
+        let dest = Destination::new("results", &config);
+        assert_eq!(dest.path_of("project/src/main.rs").unwrap(), Some(PathBuf::from("results/lib/main.rs")));
+        assert_eq!(dest.path_of("project/docs/readme.txt").unwrap(), Some(PathBuf::from("results/docs/readme.md")));
+
+        let config = ExtractConfig::builder()
+            .transforms(vec![Transform::new("s,^,../,").unwrap()])
+            .build();
+        let dest = Destination::new("results", &config);
+        assert!(matches!(dest.path_of("a/b"), Err(Error::UnsafePath(n)) if n == "../a/b"));
+        let config = ExtractConfig::builder()
+            .transforms(vec![Transform::new("s/.*//").unwrap()])
+            .build();
+        let dest = Destination::new("results", &config);
+        assert_eq!(dest.path_of("a/b").unwrap(), None);
+    }
+
+    #[test]
+    fn test_overwrite_policy() {
+        let base = PathBuf::from("results/destination_overwrite");
+        let _ = std::fs::remove_dir_all(&base);
+        let setup = || {
+            std::fs::create_dir_all(&base).unwrap();
+            std::fs::write(base.join("a.txt"), "old").unwrap();
+            File::options().write(true).open(base.join("a.txt")).unwrap()
+                .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(2_000_000_000)).unwrap();
+        };
+        let extract = |config: &ExtractConfig, mtime: i64| {
+            let mut dest = Destination::new(&base, config);
+            let path = dest.path_of("a.txt").unwrap().unwrap();
+            let attrs = Attributes::builder().mtime(mtime).build();
+            assert_eq!(dest.write(&path, &mut "new".as_bytes(), &attrs).unwrap(), 3);
+            std::fs::read_to_string(&path).unwrap()
+        };
+
+        setup();
+        let config = ExtractConfig::builder().overwrite_policy(OverwritePolicy::Never).build();
+        assert_eq!(extract(&config, 2_100_000_000), "old");
+        let config = ExtractConfig::builder().overwrite_policy(OverwritePolicy::IfNewer).build();
+        assert_eq!(extract(&config, 1_900_000_000), "old");
+        assert_eq!(extract(&config, 2_100_000_000), "new");
+        let config = ExtractConfig::builder().overwrite_policy(OverwritePolicy::Always).build();
+        setup();
+        assert_eq!(extract(&config, 1_900_000_000), "new");
+
+        setup();
+        let config = ExtractConfig::builder()
+            .overwrite_policy(OverwritePolicy::Ask)
+            .overwrite_prompt(Box::new(|path: &Path| path.ends_with("b.txt")))
+            .build();
+        assert_eq!(extract(&config, 2_100_000_000), "old");
+
+        let config = ExtractConfig::builder().overwrite_policy(OverwritePolicy::Rename).build();
+        let mut dest = Destination::new(&base, &config);
+        let path = dest.path_of("a.txt").unwrap().unwrap();
+        dest.write(&path, &mut "new1".as_bytes(), &Attributes::default()).unwrap();
+        // the later entry of the same name overwrites the renamed file.
+        dest.write(&path, &mut "new2".as_bytes(), &Attributes::default()).unwrap();
+        assert_eq!(std::fs::read_to_string(base.join("a.txt")).unwrap(), "old");
+        assert_eq!(std::fs::read_to_string(base.join("a.txt.1")).unwrap(), "new2");
+        let mut dest = Destination::new(&base, &config);
+        dest.write(&path, &mut "new3".as_bytes(), &Attributes::default()).unwrap();
+        assert_eq!(std::fs::read_to_string(base.join("a.txt.2")).unwrap(), "new3");
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn test_limits() {
+        let base = PathBuf::from("results/destination_limits");
+        let config = ExtractConfig::builder()
+            .max_entry_size(8)
+            .max_total_size(12)
+            .max_entries(3)
+            .build();
+        let mut dest = Destination::new(&base, &config);
+        let path = dest.path_of("a/b.txt").unwrap().unwrap();
+        assert_eq!(dest.write(&path, &mut "12345678".as_bytes(), &Attributes::default()).unwrap(), 8);
+        let path = dest.path_of("c.txt").unwrap().unwrap();
+        let r = dest.write(&path, &mut "12345".as_bytes(), &Attributes::default());
+        assert!(matches!(r, Err(Error::LimitExceeded(m)) if m == "c.txt: the total size exceeds 12 bytes"));
+        assert!(!base.exists());
+
+        let mut dest = Destination::new(&base, &config);
+        let path = dest.path_of("d.txt").unwrap().unwrap();
+        let r = dest.write(&path, &mut "123456789".as_bytes(), &Attributes::default());
+        assert!(matches!(r, Err(Error::LimitExceeded(m)) if m == "d.txt: the entry size exceeds 8 bytes"));
+        assert!(!base.exists());
+
+        let mut dest = Destination::new(&base, &config);
+        for name in ["e", "f", "g"] {
+            let path = dest.path_of(name).unwrap().unwrap();
+            dest.write(&path, &mut "1".as_bytes(), &Attributes::default()).unwrap();
+        }
+        let path = dest.path_of("h").unwrap().unwrap();
+        let r = dest.write(&path, &mut "1".as_bytes(), &Attributes::default());
+        assert!(matches!(r, Err(Error::LimitExceeded(m)) if m == "h: the number of entries exceeds 3"));
+        assert!(!base.exists());
+
+        let config = ExtractConfig::builder().max_ratio(2.0).build();
+        let mut dest = Destination::new(&base, &config).with_archive_size(2);
+        let path = dest.path_of("i").unwrap().unwrap();
+        assert!(matches!(dest.reserve(&path, 5), Err(Error::LimitExceeded(_))));
+        assert!(dest.reserve(&path, 4).is_ok());
+    }
+
+    #[test]
+    fn test_limits_keep_existing_files() {
+        let base = PathBuf::from("results/destination_limits_existing");
+        let _ = std::fs::remove_dir_all(&base);
+        std::fs::create_dir_all(&base).unwrap();
+        std::fs::write(base.join("a.txt"), "old").unwrap();
+        let config = ExtractConfig::builder().max_entry_size(8).build();
+
+        // the existing file is neither removed nor truncated by the aborted extraction.
+        let mut dest = Destination::new(&base, &config);
+        let path = dest.path_of("a.txt").unwrap().unwrap();
+        let r = dest.write(&path, &mut "123456789".as_bytes(), &Attributes::default());
+        assert!(matches!(r, Err(Error::LimitExceeded(m)) if m == "a.txt: the entry size exceeds 8 bytes"));
+        assert_eq!(std::fs::read_to_string(base.join("a.txt")).unwrap(), "old");
+        assert!(!base.join("a.txt.totebag-partial").exists());
+
+        // the skipped entry is counted, too.
+        let config = ExtractConfig::builder()
+            .max_entry_size(8)
+            .overwrite_policy(OverwritePolicy::Never)
+            .build();
+        let mut dest = Destination::new(&base, &config);
+        let r = dest.write(&path, &mut "123456789".as_bytes(), &Attributes::default());
+        assert!(matches!(r, Err(Error::LimitExceeded(m)) if m == "a.txt: the entry size exceeds 8 bytes"));
+        assert_eq!(std::fs::read_to_string(base.join("a.txt")).unwrap(), "old");
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_restore() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let base = PathBuf::from("results/destination_restore");
+        let attrs = Attributes::builder()
+            .mode(0o104755)
+            .mtime(1_000_000_000)
+            .build();
+        let config = ExtractConfig::builder().build();
+        let mut dest = Destination::new(&base, &config);
+        let path = dest.path_of("setuid.sh").unwrap().unwrap();
+        dest.write(&path, &mut "hoge".as_bytes(), &attrs).unwrap();
+        let metadata = path.metadata().unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o7777, 0o755);
+        assert_eq!(metadata.modified().unwrap(), SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000));
+
+        let config = ExtractConfig::builder().strip_setuid(false).build();
+        let dest = Destination::new(&base, &config);
+        dest.restore(&path, &attrs).unwrap();
+        assert_eq!(path.metadata().unwrap().permissions().mode() & 0o7777, 0o4755);
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_links() {
+        let base = PathBuf::from("results/destination_links");
+        let _ = std::fs::remove_dir_all(&base);
+        let config = ExtractConfig::builder().build();
+        let mut dest = Destination::new(&base, &config);
+        let file = dest.path_of("dir/file.txt").unwrap().unwrap();
+        dest.write(&file, &mut "hoge".as_bytes(), &Attributes::default()).unwrap();
+
+        let link = dest.path_of("dir/link").unwrap().unwrap();
+        dest.symlink(&link, "file.txt").unwrap();
+        assert_eq!(std::fs::read_link(&link).unwrap(), PathBuf::from("file.txt"));
+        let link = dest.path_of("up").unwrap().unwrap();
+        dest.symlink(&link, "dir/../dir/file.txt").unwrap();
+
+        let hard = dest.path_of("hard.txt").unwrap().unwrap();
+        dest.hardlink(&hard, &file).unwrap();
+        assert_eq!(std::fs::read_to_string(&hard).unwrap(), "hoge");
+
+        for (name, target) in [("a", "../outside"), ("b", "/etc/passwd"), ("dir/c", "../../x")] {
+            let link = dest.path_of(name).unwrap().unwrap();
+            assert!(matches!(dest.symlink(&link, target), Err(Error::UnsafePath(_))), "{name} -> {target}");
+            assert!(link.symlink_metadata().is_err());
+        }
+        // `..` after the link, or the missing directory, may escape after the later links are extracted.
+        let link = dest.path_of("current").unwrap().unwrap();
+        dest.symlink(&link, ".").unwrap();
+        for (name, target) in [("d", "current/.."), ("e", "missing/..")] {
+            let link = dest.path_of(name).unwrap().unwrap();
+            assert!(matches!(dest.symlink(&link, target), Err(Error::UnsafePath(_))), "{name} -> {target}");
+        }
+
+        let config = ExtractConfig::builder().unsafe_path(UnsafePathPolicy::Skip).build();
+        let mut dest = Destination::new(&base, &config);
+        let link = dest.path_of("f").unwrap().unwrap();
+        dest.symlink(&link, "../outside").unwrap();
+        assert!(link.symlink_metadata().is_err());
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_escape() {
+        let base = PathBuf::from("results/destination_symlink");
+        let _ = std::fs::remove_dir_all(&base);
+        create_dir_all(&base).unwrap();
+        std::os::unix::fs::symlink("/tmp", base.join("link")).unwrap();
+
+        let config = ExtractConfig::builder().build();
+        let mut dest = Destination::new(&base, &config);
+        let path = dest.path_of("link/escaped.txt").unwrap().unwrap();
+        let r = dest.write(&path, &mut "hoge".as_bytes(), &Attributes::default());
+        assert!(matches!(r, Err(Error::UnsafePath(_))));
+        assert!(!PathBuf::from("/tmp/escaped.txt").exists());
+
+        let path = dest.path_of("dir/inside.txt").unwrap().unwrap();
+        assert_eq!(dest.write(&path, &mut "hoge".as_bytes(), &Attributes::default()).unwrap(), 4);
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+}
@@ -0,0 +1,563 @@
+use std::collections::HashSet;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::PathBuf;
+
+use crate::{Error, Result};
+use crate::extractor::{Attributes, Destination, Entries, Entry, ReadSeek, ToteExtractor};
+
+/// ISO 9660 image extractor implementation.
+/// The names are taken from the Rock Ridge extension if the image has it, then from the Joliet extension,
+/// and the plain ISO 9660 names without the version (e.g., `;1`) otherwise.
+/// The permissions, the owners, and the symbolic links are available only with the Rock Ridge extension.
+pub(super) struct Extractor {}
+
+const SECTOR_SIZE: u64 = 2048;
+const FIRST_DESCRIPTOR: u64 = 16;
+const MAGIC: &[u8] = b"CD001";
+/// The limit of the volume descriptors to read, for the images without the terminator.
+const MAX_DESCRIPTORS: u64 = 64;
+/// The limit of the size of a directory and a continuation area, for the broken images.
+const MAX_DIRECTORY_SIZE: u32 = 16 * 1024 * 1024;
+/// The limit of the depth of the directories, for the images whose directories make a loop.
+const MAX_DEPTH: usize = 128;
+/// The limit of the continuation areas of a directory record.
+const MAX_CONTINUATIONS: usize = 16;
+
+const TYPE_PRIMARY: u8 = 1;
+const TYPE_SUPPLEMENTARY: u8 = 2;
+const TYPE_TERMINATOR: u8 = 255;
+/// The escape sequences of the Joliet extension (UCS-2 level 1, 2, and 3).
+const JOLIET_ESCAPES: [&[u8]; 3] = [b"%/@", b"%/C", b"%/E"];
+
+const FLAG_DIRECTORY: u8 = 0x02;
+const FLAG_ASSOCIATED: u8 = 0x04;
+const FLAG_MULTI_EXTENT: u8 = 0x80;
+
+impl ToteExtractor for Extractor {
+    fn list_from(&self, mut reader: Box<dyn ReadSeek + '_>, archive_file: PathBuf) -> Result<Entries> {
+        let files = read_files(&mut reader)
+            .map_err(|e| Error::corrupted(&archive_file, None, e))?;
+        let entries = files.iter()
+            .map(IsoFile::to_entry)
+            .collect();
+        Ok(Entries::new(archive_file, entries))
+    }
+
+    fn perform_from(&self, mut reader: Box<dyn ReadSeek + '_>, dest: &mut Destination) -> Result<()> {
+        let archive_file = dest.archive_file().to_path_buf();
+        let files = read_files(&mut reader)
+            .map_err(|e| Error::corrupted(&archive_file, None, e))?;
+        for file in files {
+            let Some(path) = dest.path_of(&file.name)? else {
+                continue;
+            };
+            match &file.kind {
+                Kind::Directory => dest.create_dir(&path)?,
+                Kind::Symlink(target) => dest.symlink(&path, target)?,
+                Kind::File => {
+                    log::info!("extracting {} ({} bytes)", file.name, file.size());
+                    let attrs = Attributes::builder()
+                        .mode(file.mode)
+                        .mtime(file.mtime)
+                        .uid(file.uid)
+                        .gid(file.gid)
+                        .build();
+                    let mut data = ExtentReader::new(&mut reader, &file.extents);
+                    let written = dest.write(&path, &mut data, &attrs)?;
+                    if written != file.size() {
+                        return Err(Error::corrupted(&archive_file, Some(&file.name), "unexpected end of the entry"));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Kind {
+    Directory,
+    File,
+    Symlink(String),
+}
+
+/// The file in the image. The name of the directory ends with `/`.
+struct IsoFile {
+    name: String,
+    kind: Kind,
+    /// The location (the sector number) and the length of the data, the large files have multiple extents.
+    extents: Vec<(u32, u32)>,
+    mode: Option<u32>,
+    uid: Option<u32>,
+    gid: Option<u32>,
+    mtime: Option<i64>,
+}
+
+impl IsoFile {
+    fn size(&self) -> u64 {
+        self.extents.iter().map(|&(_, len)| len as u64).sum()
+    }
+
+    fn to_entry(&self) -> Entry {
+        let date = self.mtime
+            .and_then(|t| chrono::DateTime::from_timestamp(t, 0))
+            .map(|d| d.naive_utc());
+        let size = (self.kind == Kind::File).then(|| self.size());
+        Entry::new(self.name.clone(), None, size, self.mode, date)
+    }
+}
+
+/// The naming scheme of the directory tree in use.
+#[derive(Clone, Copy, PartialEq)]
+enum Names {
+    Plain,
+    Joliet,
+    /// Rock Ridge, with the number of bytes to skip at the head of the system use area.
+    RockRidge(usize),
+}
+
+/// The directory record, see ECMA-119 9.1.
+struct Record<'a> {
+    extent: u32,
+    size: u32,
+    date: &'a [u8],
+    flags: u8,
+    ident: &'a [u8],
+    system_use: &'a [u8],
+}
+
+impl<'a> Record<'a> {
+    fn parse(data: &'a [u8]) -> std::io::Result<Self> {
+        let ident_len = *data.get(32).ok_or_else(|| invalid_data("too short directory record"))? as usize;
+        let system_use = 33 + ident_len + (1 - ident_len % 2);
+        if data.len() < 33 + ident_len {
+            return Err(invalid_data("too short directory record"));
+        }
+        Ok(Self {
+            extent: le_u32(&data[2..6]),
+            size: le_u32(&data[10..14]),
+            date: &data[18..25],
+            flags: data[25],
+            ident: &data[33..33 + ident_len],
+            system_use: data.get(system_use..).unwrap_or_default(),
+        })
+    }
+
+    /// Returns `true` for the records of the directory itself (`.`) and its parent (`..`).
+    fn is_self_or_parent(&self) -> bool {
+        self.ident == [0] || self.ident == [1]
+    }
+
+    fn name(&self, names: Names) -> String {
+        let name = match names {
+            Names::Joliet => {
+                let units = self.ident.chunks_exact(2)
+                    .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                    .collect::<Vec<_>>();
+                String::from_utf16_lossy(&units)
+            }
+            _ => String::from_utf8_lossy(self.ident).to_string(),
+        };
+        let name = match name.rfind(';') {
+            Some(index) => &name[..index],
+            None => name.as_str(),
+        };
+        if self.flags & FLAG_DIRECTORY == 0 {
+            name.trim_end_matches('.').to_string()
+        } else {
+            name.to_string()
+        }
+    }
+}
+
+/// The fields of the Rock Ridge extension (RRIP) in the system use area.
+#[derive(Default)]
+struct RockRidge {
+    name: Option<String>,
+    mode: Option<u32>,
+    uid: Option<u32>,
+    gid: Option<u32>,
+    mtime: Option<i64>,
+    symlink: Option<String>,
+    /// The location of the relocated directory (`CL`).
+    child: Option<u32>,
+    /// `true` if this directory is relocated from the deep place (`RE`).
+    relocated: bool,
+}
+
+impl RockRidge {
+    fn read<R: Read + Seek + ?Sized>(reader: &mut R, system_use: &[u8]) -> std::io::Result<Self> {
+        let mut rr = RockRidge::default();
+        let mut area = system_use.to_vec();
+        let mut link = SymlinkBuilder::default();
+        for _ in 0..MAX_CONTINUATIONS {
+            match rr.parse(&area, &mut link) {
+                Some((block, offset, length)) => {
+                    if length > MAX_DIRECTORY_SIZE {
+                        return Err(invalid_data("too large continuation area"));
+                    }
+                    area = vec![0u8; length as usize];
+                    reader.seek(SeekFrom::Start(block as u64 * SECTOR_SIZE + offset as u64))?;
+                    reader.read_exact(&mut area)?;
+                }
+                None => break,
+            }
+        }
+        rr.symlink = link.build();
+        Ok(rr)
+    }
+
+    /// Parses the SUSP entries in the given area, and returns the continuation area (`CE`) if exists.
+    fn parse(&mut self, area: &[u8], link: &mut SymlinkBuilder) -> Option<(u32, u32, u32)> {
+        let mut continuation = None;
+        let mut pos = 0;
+        while pos + 4 <= area.len() {
+            let len = area[pos + 2] as usize;
+            if len < 4 || pos + len > area.len() {
+                break;
+            }
+            let body = &area[pos + 4..pos + len];
+            match &area[pos..pos + 2] {
+                b"NM" if !body.is_empty() && body[0] & 0x06 == 0 => {
+                    self.name.get_or_insert_with(String::new)
+                        .push_str(&String::from_utf8_lossy(&body[1..]));
+                }
+                b"PX" if body.len() >= 28 => {
+                    self.mode = Some(le_u32(&body[0..4]));
+                    self.uid = Some(le_u32(&body[16..20]));
+                    self.gid = Some(le_u32(&body[24..28]));
+                }
+                b"SL" if !body.is_empty() => link.push(&body[1..]),
+                b"TF" if !body.is_empty() => self.mtime = modified_time(body),
+                b"CE" if body.len() >= 20 => {
+                    continuation = Some((le_u32(&body[0..4]), le_u32(&body[8..12]), le_u32(&body[16..20])));
+                }
+                b"CL" if body.len() >= 4 => self.child = Some(le_u32(&body[0..4])),
+                b"RE" => self.relocated = true,
+                b"ST" => break,
+                _ => {}
+            }
+            pos += len;
+        }
+        continuation
+    }
+}
+
+/// Builds the target of the symbolic link from the components in the `SL` entries.
+#[derive(Default)]
+struct SymlinkBuilder {
+    components: Vec<String>,
+    continued: bool,
+    found: bool,
+}
+
+impl SymlinkBuilder {
+    fn push(&mut self, mut components: &[u8]) {
+        self.found = true;
+        while components.len() >= 2 {
+            let flags = components[0];
+            let len = (components[1] as usize).min(components.len() - 2);
+            let text = match flags {
+                f if f & 0x02 != 0 => ".".to_string(),
+                f if f & 0x04 != 0 => "..".to_string(),
+                f if f & 0x08 != 0 => String::new(),
+                _ => String::from_utf8_lossy(&components[2..2 + len]).to_string(),
+            };
+            match self.components.last_mut() {
+                Some(last) if self.continued => last.push_str(&text),
+                _ => self.components.push(text),
+            }
+            self.continued = flags & 0x01 != 0;
+            components = &components[2 + len..];
+        }
+    }
+
+    fn build(self) -> Option<String> {
+        match self.components.as_slice() {
+            _ if !self.found => None,
+            [root] if root.is_empty() => Some("/".to_string()),
+            components => Some(components.join("/")),
+        }
+    }
+}
+
+/// Returns the modified time in the `TF` entry, which records the time stamps in the order of the flags.
+fn modified_time(body: &[u8]) -> Option<i64> {
+    let flags = body[0];
+    let size = if flags & 0x80 != 0 { 17 } else { 7 };
+    let offset = 1 + if flags & 0x01 != 0 { size } else { 0 };
+    if flags & 0x02 == 0 {
+        return None;
+    }
+    let stamp = body.get(offset..offset + size)?;
+    if size == 17 {
+        long_form_time(stamp)
+    } else {
+        short_form_time(stamp)
+    }
+}
+
+/// Converts the date and time of the directory record (7 bytes) into the unix time.
+fn short_form_time(date: &[u8]) -> Option<i64> {
+    let time = chrono::NaiveDate::from_ymd_opt(1900 + date[0] as i32, date[1] as u32, date[2] as u32)?
+        .and_hms_opt(date[3] as u32, date[4] as u32, date[5] as u32)?;
+    Some(time.and_utc().timestamp() - date[6] as i8 as i64 * 15 * 60)
+}
+
+/// Converts the date and time of the volume descriptor format (17 bytes, `YYYYMMDDhhmmsscc` and the offset) into the unix time.
+fn long_form_time(date: &[u8]) -> Option<i64> {
+    let digits = std::str::from_utf8(&date[..16]).ok()?;
+    let field = |range: std::ops::Range<usize>| digits.get(range)?.parse::<u32>().ok();
+    let time = chrono::NaiveDate::from_ymd_opt(field(0..4)? as i32, field(4..6)?, field(6..8)?)?
+        .and_hms_opt(field(8..10)?, field(10..12)?, field(12..14)?)?;
+    Some(time.and_utc().timestamp() - date[16] as i8 as i64 * 15 * 60)
+}
+
+/// Reads the volume descriptors, and returns the root directory record and the naming scheme.
+/// The primary volume is preferred if it has the Rock Ridge extension, otherwise, the Joliet volume is.
+fn read_volume<R: Read + Seek + ?Sized>(reader: &mut R) -> std::io::Result<((u32, u32), Names)> {
+    let mut primary = None;
+    let mut joliet = None;
+    let mut sector = vec![0u8; SECTOR_SIZE as usize];
+    for index in FIRST_DESCRIPTOR..FIRST_DESCRIPTOR + MAX_DESCRIPTORS {
+        reader.seek(SeekFrom::Start(index * SECTOR_SIZE))?;
+        reader.read_exact(&mut sector)?;
+        if &sector[1..6] != MAGIC {
+            return Err(invalid_data("invalid volume descriptor"));
+        }
+        let root = Record::parse(&sector[156..190])?;
+        match sector[0] {
+            TYPE_PRIMARY if primary.is_none() => primary = Some((root.extent, root.size)),
+            TYPE_SUPPLEMENTARY if JOLIET_ESCAPES.contains(&&sector[88..91]) => joliet = Some((root.extent, root.size)),
+            TYPE_TERMINATOR => break,
+            _ => {}
+        }
+    }
+    let primary = primary.ok_or_else(|| invalid_data("no primary volume descriptor"))?;
+    if let Some(skip) = rock_ridge_skip(reader, primary)? {
+        return Ok((primary, Names::RockRidge(skip)));
+    }
+    Ok(match joliet {
+        Some(root) => (root, Names::Joliet),
+        None => (primary, Names::Plain),
+    })
+}
+
+/// Returns the bytes to skip in the system use areas if the `.` record of the root directory has the `SP` entry.
+fn rock_ridge_skip<R: Read + Seek + ?Sized>(reader: &mut R, (extent, _): (u32, u32)) -> std::io::Result<Option<usize>> {
+    let mut sector = vec![0u8; SECTOR_SIZE as usize];
+    reader.seek(SeekFrom::Start(extent as u64 * SECTOR_SIZE))?;
+    reader.read_exact(&mut sector)?;
+    let len = sector[0] as usize;
+    if len < 34 {
+        return Err(invalid_data("invalid root directory"));
+    }
+    let record = Record::parse(&sector[..len])?;
+    let sp = record.system_use;
+    Ok((sp.len() >= 7 && &sp[0..2] == b"SP" && sp[4..6] == [0xbe, 0xef]).then(|| sp[6] as usize))
+}
+
+/// Reads the volume, and returns the files in the image in the order of the directory records.
+fn read_files<R: Read + Seek + ?Sized>(reader: &mut R) -> std::io::Result<Vec<IsoFile>> {
+    let (root, names) = read_volume(reader)?;
+    let mut walker = Walker { names, files: vec![], visited: HashSet::new() };
+    walker.walk(reader, root, "", 0)?;
+    Ok(walker.files)
+}
+
+struct Walker {
+    names: Names,
+    files: Vec<IsoFile>,
+    visited: HashSet<u32>,
+}
+
+impl Walker {
+    fn walk<R: Read + Seek + ?Sized>(&mut self, reader: &mut R, (extent, size): (u32, u32), prefix: &str, depth: usize) -> std::io::Result<()> {
+        if depth > MAX_DEPTH || !self.visited.insert(extent) {
+            return Err(invalid_data("too deep or looped directories"));
+        }
+        if size > MAX_DIRECTORY_SIZE {
+            return Err(invalid_data("too large directory"));
+        }
+        let mut data = vec![0u8; size as usize];
+        reader.seek(SeekFrom::Start(extent as u64 * SECTOR_SIZE))?;
+        reader.read_exact(&mut data)?;
+        let mut pos = 0;
+        let mut continued: Option<IsoFile> = None;
+        while pos < data.len() {
+            let len = data[pos] as usize;
+            if len == 0 {
+                // the records never cross the sector boundaries, and the rest of the sector is padded with zeros.
+                pos = (pos / SECTOR_SIZE as usize + 1) * SECTOR_SIZE as usize;
+                continue;
+            }
+            let record = Record::parse(data.get(pos..pos + len).ok_or_else(|| invalid_data("broken directory record"))?)?;
+            pos += len;
+            if record.is_self_or_parent() || record.flags & FLAG_ASSOCIATED != 0 {
+                continue;
+            }
+            if let Some(file) = continued.as_mut() {
+                file.extents.push((record.extent, record.size));
+                if record.flags & FLAG_MULTI_EXTENT == 0 {
+                    self.files.extend(continued.take());
+                }
+                continue;
+            }
+            let rr = match self.names {
+                Names::RockRidge(skip) => RockRidge::read(reader, record.system_use.get(skip..).unwrap_or_default())?,
+                _ => RockRidge::default(),
+            };
+            if rr.relocated {
+                continue;
+            }
+            let name = rr.name.clone().unwrap_or_else(|| record.name(self.names));
+            let kind = match (&rr.symlink, rr.child) {
+                (Some(target), _) => Kind::Symlink(target.clone()),
+                (None, Some(_)) => Kind::Directory,
+                _ if record.flags & FLAG_DIRECTORY != 0 => Kind::Directory,
+                _ => Kind::File,
+            };
+            let path = format!("{prefix}{name}{}", if kind == Kind::Directory { "/" } else { "" });
+            let file = IsoFile {
+                name: path.clone(),
+                extents: if kind == Kind::File { vec![(record.extent, record.size)] } else { vec![] },
+                kind,
+                mode: rr.mode,
+                uid: rr.uid,
+                gid: rr.gid,
+                mtime: rr.mtime.or_else(|| short_form_time(record.date)),
+            };
+            if file.kind == Kind::File && record.flags & FLAG_MULTI_EXTENT != 0 {
+                continued = Some(file);
+                continue;
+            }
+            let is_dir = file.kind == Kind::Directory;
+            self.files.push(file);
+            if is_dir {
+                let location = match rr.child {
+                    Some(child) => (child, self.directory_size(reader, child)?),
+                    None => (record.extent, record.size),
+                };
+                self.walk(reader, location, &path, depth + 1)?;
+            }
+        }
+        match continued {
+            Some(file) => Err(invalid_data(format!("{}: missing the last extent", file.name))),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the size of the directory at the given location from its `.` record, for the relocated directories.
+    fn directory_size<R: Read + Seek + ?Sized>(&self, reader: &mut R, extent: u32) -> std::io::Result<u32> {
+        let mut sector = vec![0u8; SECTOR_SIZE as usize];
+        reader.seek(SeekFrom::Start(extent as u64 * SECTOR_SIZE))?;
+        reader.read_exact(&mut sector)?;
+        let len = (sector[0] as usize).max(34);
+        Record::parse(&sector[..len]).map(|r| r.size)
+    }
+}
+
+/// Reads the data of the file stored in the extents in sequence.
+struct ExtentReader<'a, R: Read + Seek + ?Sized> {
+    reader: &'a mut R,
+    extents: std::slice::Iter<'a, (u32, u32)>,
+    remaining: u64,
+}
+
+impl<'a, R: Read + Seek + ?Sized> ExtentReader<'a, R> {
+    fn new(reader: &'a mut R, extents: &'a [(u32, u32)]) -> Self {
+        Self { reader, extents: extents.iter(), remaining: 0 }
+    }
+}
+
+impl<R: Read + Seek + ?Sized> Read for ExtentReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.remaining == 0 {
+            let Some(&(extent, len)) = self.extents.next() else {
+                return Ok(0);
+            };
+            self.reader.seek(SeekFrom::Start(extent as u64 * SECTOR_SIZE))?;
+            self.remaining = len as u64;
+        }
+        let max = buf.len().min(self.remaining as usize);
+        let n = self.reader.read(&mut buf[..max])?;
+        if n == 0 && max > 0 {
+            return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "truncated image"));
+        }
+        self.remaining -= n as u64;
+        Ok(n)
+    }
+}
+
+fn le_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+fn invalid_data<S: Into<String>>(message: S) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_list_rock_ridge() {
+        let extractor = Extractor {};
+        let entries = extractor.list(PathBuf::from("../testdata/test.iso")).unwrap();
+        let names = entries.iter().map(|e| e.name.clone()).collect::<Vec<_>>();
+        assert_eq!(names.len(), 21);
+        assert!(names.contains(&"Cargo.toml".to_string()));
+        assert!(names.contains(&"src/".to_string()));
+        assert!(names.contains(&"src/extractor/tar.rs".to_string()));
+        let long = names.iter().find(|n| n.ends_with("end.txt")).unwrap();
+        assert_eq!(long.len(), 211);
+        let cargo = entries.iter().find(|e| e.name == "Cargo.toml").unwrap();
+        assert_eq!(cargo.unix_mode, Some(0o100644));
+        assert_eq!(cargo.original_size, Some(723));
+        assert_eq!(cargo.date.map(|d| d.to_string()), Some("2024-05-01 03:34:56".to_string()));
+    }
+
+    #[test]
+    fn test_list_joliet() {
+        let extractor = Extractor {};
+        let entries = extractor.list(PathBuf::from("../testdata/test_joliet.iso")).unwrap();
+        let names = entries.iter().map(|e| e.name.clone()).collect::<Vec<_>>();
+        assert_eq!(names.len(), 19);
+        assert!(names.contains(&"README.md".to_string()));
+        assert!(names.contains(&"src/archiver/zip.rs".to_string()));
+        assert!(entries.iter().all(|e| e.unix_mode.is_none()));
+    }
+
+    #[test]
+    fn test_extract_iso() {
+        let config = crate::ExtractConfig::builder()
+            .dest("results/iso")
+            .overwrite(true)
+            .build();
+        crate::extract("../testdata/test.iso", &config).unwrap();
+        assert_eq!(std::fs::read("results/iso/Cargo.toml").unwrap(), std::fs::read("../testdata/sample/Cargo.toml").unwrap());
+        assert!(PathBuf::from("results/iso/src/archiver/tar.rs").exists());
+        #[cfg(unix)]
+        assert_eq!(std::fs::read_link("results/iso/src/lib.rs").unwrap(), PathBuf::from("../src/main.rs"));
+        let _ = std::fs::remove_dir_all("results/iso");
+    }
+
+    #[test]
+    fn test_symlink_components() {
+        let mut link = SymlinkBuilder::default();
+        link.push(&[0x08, 0, 0x01, 2, b'u', b's']);
+        link.push(&[0, 1, b'r', 0, 3, b'b', b'i', b'n', 0x04, 0]);
+        assert_eq!(link.build(), Some("/usr/bin/..".to_string()));
+        assert_eq!(SymlinkBuilder::default().build(), None);
+    }
+
+    #[test]
+    fn test_not_iso() {
+        let extractor = Extractor {};
+        match extractor.list(PathBuf::from("../testdata/test.tar")) {
+            Err(Error::Corrupted { .. }) => {}
+            r => panic!("unexpected result: {r:?}"),
+        }
+    }
+}
@@ -1,11 +1,9 @@
-use std::fs::{File, create_dir_all};
-use std::io::copy;
+use std::io::Read;
 use std::path::{Path, PathBuf};
 
-use chrono::DateTime;
 use delharc::{LhaDecodeReader, LhaHeader};
 
-use crate::extractor::{Entries, Entry, ToteExtractor};
+use crate::extractor::{Attributes, Destination, Entries, Entry, ReadSeek, ToteExtractor};
 use crate::{Result, Error};
 
 /// LHA/LZH format extractor implementation.
@@ -14,9 +12,9 @@ use crate::{Result, Error};
 pub(super) struct Extractor {}
 
 impl ToteExtractor for Extractor {
-    fn list(&self, archive_file: PathBuf) -> Result<Entries> {
+    fn list_from(&self, reader: Box<dyn ReadSeek + '_>, archive_file: PathBuf) -> Result<Entries> {
         let mut result = vec![];
-        let mut reader = delharc::parse_file(&archive_file).map_err(Error::IO)?;
+        let mut reader = open_lha(reader, &archive_file)?;
         loop {
             let header = reader.header();
             if !header.is_directory() {
@@ -28,18 +26,21 @@ impl ToteExtractor for Extractor {
                         break;
                     }
                 }
-                Err(e) => return Err(Error::Fatal(Box::new(e))),
+                Err(e) => return Err(Error::corrupted(&archive_file, None, e)),
             }
         }
         Ok(Entries::new(archive_file, result))
     }
 
-    fn perform(&self, archive_file: PathBuf, base: PathBuf) -> Result<()> {
-        let mut reader = delharc::parse_file(archive_file).map_err(Error::IO)?;
+    fn perform_from(&self, reader: Box<dyn ReadSeek + '_>, dest: &mut Destination) -> Result<()> {
+        let archive_file = dest.archive_file().to_path_buf();
+        let mut reader = open_lha(reader, &archive_file)?;
         let mut errs = vec![];
         loop {
-            if let Err(e) = write_data_impl(&mut reader, &base) {
-                errs.push(e);
+            match write_data_impl(&mut reader, &archive_file, dest) {
+                Err(e @ Error::LimitExceeded(_)) => return Err(e),
+                Err(e) => errs.push(e),
+                Ok(_) => {}
             }
             match reader.next_file() {
                 Ok(r) => {
@@ -47,24 +48,37 @@ impl ToteExtractor for Extractor {
                         break;
                     }
                 }
-                Err(e) => return Err(Error::Fatal(Box::new(e))),
+                Err(e) => {
+                    errs.push(Error::corrupted(&archive_file, None, e));
+                    break;
+                }
             }
         }
         Error::error_or((), errs)
     }
 }
 
-fn write_data_impl(reader: &mut LhaDecodeReader<File>, base: &Path) -> Result<()> {
+fn open_lha<R: Read>(reader: R, archive_file: &Path) -> Result<LhaDecodeReader<R>> {
+    LhaDecodeReader::new(reader).map_err(std::io::Error::from).map_err(|e| match e.kind() {
+        std::io::ErrorKind::InvalidData | std::io::ErrorKind::UnexpectedEof => {
+            Error::corrupted(archive_file, None, e)
+        }
+        _ => Error::IO(e),
+    })
+}
+
+fn write_data_impl<R: Read>(reader: &mut LhaDecodeReader<R>, archive_file: &Path, dest: &mut Destination) -> Result<()> {
     let header = reader.header();
     let name = header.parse_pathname();
-    let dest = base.join(&name);
     if reader.is_decoder_supported() {
+        let Some(dest_path) = dest.path_of(name.to_string_lossy())? else {
+            return Ok(());
+        };
         log::info!("extracting {:?} ({} bytes)", &name, header.original_size);
-        create_dir_all(dest.parent().unwrap()).unwrap();
-        let mut dest = File::create(dest).map_err(Error::IO)?;
-        copy(reader, &mut dest).map_err(Error::IO)?;
+        let attrs = attributes(header);
+        dest.write(&dest_path, reader, &attrs)?;
         if let Err(e) = reader.crc_check() {
-            return Err(Error::Fatal(Box::new(e)));
+            return Err(Error::corrupted(archive_file, Some(&name.to_string_lossy()), e));
         };
     } else if !header.is_directory() {
         log::info!(
@@ -75,12 +89,32 @@ fn write_data_impl(reader: &mut LhaDecodeReader<File>, base: &Path) -> Result<()
     Ok(())
 }
 
+/// Reads the unix permission (0x50) and the uid/gid (0x51) extended headers, if any.
+fn attributes(h: &LhaHeader) -> Attributes {
+    let mut mode = None;
+    let mut owner = None;
+    for extra in h.iter_extra() {
+        match extra {
+            [0x50, lo, hi] => mode = Some(u16::from_le_bytes([*lo, *hi]) as u32),
+            [0x51, g0, g1, u0, u1] => {
+                owner = Some((u16::from_le_bytes([*u0, *u1]) as u32, u16::from_le_bytes([*g0, *g1]) as u32))
+            }
+            _ => {}
+        }
+    }
+    Attributes::builder()
+        .mode(mode)
+        .mtime(h.parse_last_modified().to_utc().map(|dt| dt.timestamp()))
+        .uid(owner.map(|(uid, _)| uid))
+        .gid(owner.map(|(_, gid)| gid))
+        .build()
+}
+
 fn convert(h: &LhaHeader) -> Entry {
-    let name = h.parse_pathname().to_str().unwrap().to_string();
+    let name = h.parse_pathname().to_string_lossy().to_string();
     let compressed_size = h.compressed_size;
     let original_size = h.original_size;
-    let mtime = h.last_modified as i64;
-    let dt = DateTime::from_timestamp(mtime, 0)
+    let dt = h.parse_last_modified().to_utc()
         .map(|dt| dt.naive_local());
     Entry::builder()
         .name(name)
@@ -102,12 +136,12 @@ mod tests {
             Ok(r) => {
                 let r = r.iter().map(|e| e.name.clone()).collect::<Vec<_>>();
                 assert_eq!(r.len(), 23);
-                assert_eq!(r.get(0), Some("Cargo.toml".to_string()).as_ref());
+                assert_eq!(r.first(), Some("Cargo.toml".to_string()).as_ref());
                 assert_eq!(r.get(1), Some("LICENSE".to_string()).as_ref());
                 assert_eq!(r.get(2), Some("README.md".to_string()).as_ref());
                 assert_eq!(r.get(3), Some("build.rs".to_string()).as_ref());
             }
-            Err(_) => assert!(false),
+            Err(_) => panic!("unexpected result"),
         }
     }
 
@@ -121,13 +155,12 @@ mod tests {
             .build();
         match crate::extract(archive_file, &opts) {
             Ok(_) => {
-                assert!(true);
                 assert!(PathBuf::from("results/lha/test/Cargo.toml").exists());
                 std::fs::remove_dir_all(PathBuf::from("results/lha")).unwrap();
             }
             Err(e) => {
                 eprintln!("{:?}", e);
-                assert!(false);
+                panic!("unexpected result");
             }
         };
     }
@@ -1,12 +1,13 @@
-use std::fs::create_dir_all;
-use std::path::PathBuf;
+use std::fs::File;
+use std::path::{Path, PathBuf};
 
-use chrono::DateTime;
-use unrar::FileHeader;
+use delharc::header::parse_msdos_datetime;
+use unrar::{CursorBeforeFile, CursorBeforeHeader, FileHeader, OpenArchive, Process};
 
 use crate::{Result, Error};
 
-use crate::extractor::{Entry, Entries, ToteExtractor};
+use crate::extractor::destination::local_timestamp;
+use crate::extractor::{Attributes, Destination, Entry, Entries, ReadSeek, ToteExtractor};
 
 /// RAR format extractor implementation.
 ///
@@ -16,49 +17,108 @@ pub(super) struct Extractor {}
 impl ToteExtractor for Extractor {
     fn list(&self, archive_file: PathBuf) -> Result<Entries> {
         let mut r = vec![];
-        for entry in unrar::Archive::new(&archive_file)
+        let archive = unrar::Archive::new(&archive_file)
             .open_for_listing()
-            .unwrap()
-        {
-            let header = entry.unwrap();
+            .map_err(|e| Error::corrupted(&archive_file, None, e))?;
+        for entry in archive {
+            let header = entry.map_err(|e| Error::corrupted(&archive_file, None, e))?;
             r.push(convert(header));
         }
         Ok(Entries::new(archive_file, r))
     }
 
-    fn perform(&self, archive_file: PathBuf, base: PathBuf) -> Result<()> {
+    fn perform(&self, archive_file: PathBuf, dest: &mut Destination) -> Result<()> {
         let archive = unrar::Archive::new(&archive_file);
-        let mut file = archive.open_for_processing().unwrap();
-        while let Some(header) = file.read_header().unwrap() {
-            let name = header.entry().filename.to_str().unwrap();
-            let dest = base.join(name);
-            file = if header.entry().is_file() {
-                log::info!(
-                    "extracting {} ({} bytes)",
-                    name,
-                    header.entry().unpacked_size
-                );
-                if let Err(e) = create_dir_all(dest.parent().unwrap()) {
-                    return Err(Error::IO(e));
-                }
-                header.extract_to(&dest).unwrap()
+        let mut file = archive.open_for_processing()
+            .map_err(|e| Error::corrupted(&archive_file, None, e))?;
+        while let Some(header) = file.read_header()
+            .map_err(|e| Error::corrupted(&archive_file, None, e))?
+        {
+            let name = header.entry().filename.to_string_lossy().to_string();
+            let dest_path = if header.entry().is_file() {
+                dest.path_of(&name)?
             } else {
-                header.skip().unwrap()
+                None
+            };
+            file = match dest_path {
+                Some(dest_path) => {
+                    log::info!(
+                        "extracting {} ({} bytes)",
+                        name,
+                        header.entry().unpacked_size
+                    );
+                    dest.reserve(&dest_path, header.entry().unpacked_size)?;
+                    let attrs = attributes(header.entry());
+                    extract_entry(header, &name, &dest_path, &attrs, dest)?
+                }
+                None => header.skip()
+                    .map_err(|e| Error::corrupted(&archive_file, Some(&name), e))?,
             }
         }
         Ok(())
     }
+
+    /// unrar reads the archive file by its path, therefore, the archive in the reader is not supported.
+    fn list_from(&self, _: Box<dyn ReadSeek + '_>, _: PathBuf) -> Result<Entries> {
+        Err(Error::UnsupportedFormat("Rar: listing from the reader".to_string()))
+    }
+
+    /// unrar reads the archive file by its path, therefore, the archive in the reader is not supported.
+    fn perform_from(&self, _: Box<dyn ReadSeek + '_>, _: &mut Destination) -> Result<()> {
+        Err(Error::UnsupportedFormat("Rar: extraction from the reader".to_string()))
+    }
+}
+
+/// unrar decodes the entry only into memory or into a file, therefore, the entry is decoded into
+/// a temporary file next to the destination, and copied through [`Destination::write`] so that
+/// the limits in [`ExtractConfig`](crate::ExtractConfig) apply to the actually decoded bytes.
+fn extract_entry(
+    header: OpenArchive<Process, CursorBeforeFile>,
+    name: &str,
+    dest_path: &Path,
+    attrs: &Attributes,
+    dest: &mut Destination,
+) -> Result<OpenArchive<Process, CursorBeforeHeader>> {
+    if let Some(parent) = dest_path.parent() {
+        dest.create_dir(parent)?;
+    }
+    let mut partial = dest_path.as_os_str().to_os_string();
+    partial.push(".totebag-unrar");
+    let partial = PathBuf::from(partial);
+    let result = header.extract_to(&partial)
+        .map_err(|e| Error::corrupted(dest.archive_file(), Some(name), e))
+        .and_then(|next| {
+            let mut file = File::open(&partial).map_err(Error::IO)?;
+            dest.write(dest_path, &mut file, attrs).map(|_| next)
+        });
+    if let Err(e) = std::fs::remove_file(&partial)
+        && e.kind() != std::io::ErrorKind::NotFound
+    {
+        log::warn!("{partial:?}: failed to remove ({e})");
+    }
+    result
+}
+
+/// The file attributes hold the unix mode only if the archive was created on unix.
+/// The file time is an MS-DOS time stamp in local time.
+fn attributes(fh: &FileHeader) -> Attributes {
+    let mode = (fh.file_attr & 0o170000 == 0o100000).then_some(fh.file_attr);
+    let mtime = parse_msdos_datetime(fh.file_time)
+        .and_then(|dt| local_timestamp(&dt));
+    Attributes::builder()
+        .mode(mode)
+        .mtime(mtime)
+        .build()
 }
 
 fn convert(fh: FileHeader) -> Entry {
-    let name = fh.filename.to_str().unwrap();
+    let name = fh.filename.to_string_lossy();
     let uncompressed_size = fh.unpacked_size;
-    let mtime = fh.file_time as i64;
-    let dt = DateTime::from_timestamp(mtime, 0);
+    let dt = parse_msdos_datetime(fh.file_time);
     Entry::builder()
         .name(name)
         .original_size(uncompressed_size)
-        .date(dt.map(|dt| dt.naive_local()))
+        .date(dt)
         .build()
 }
 
@@ -74,12 +134,12 @@ mod tests {
             Ok(r) => {
                 let r = r.iter().map(|e| e.name.clone()).collect::<Vec<_>>();
                 assert_eq!(r.len(), 18);
-                assert_eq!(r.get(0), Some("Cargo.toml".to_string()).as_ref());
+                assert_eq!(r.first(), Some("Cargo.toml".to_string()).as_ref());
                 assert_eq!(r.get(1), Some("build.rs".to_string()).as_ref());
                 assert_eq!(r.get(2), Some("LICENSE".to_string()).as_ref());
                 assert_eq!(r.get(3), Some("README.md".to_string()).as_ref());
             }
-            Err(_) => assert!(false),
+            Err(_) => panic!("unexpected result"),
         }
     }
 
@@ -93,11 +153,23 @@ mod tests {
             .build();
         match crate::extract(archive_file, &opts) {
             Ok(_) => {
-                assert!(true);
                 assert!(PathBuf::from("results/rar/test/Cargo.toml").exists());
                 std::fs::remove_dir_all(PathBuf::from("results/rar")).unwrap();
             }
             Err(e) => panic!("unexpected error: {:?}", e),
         };
     }
+
+    #[test]
+    fn test_extract_limit() {
+        let opts = crate::ExtractConfig::builder()
+            .dest(PathBuf::from("results/rar_limit"))
+            .max_entry_size(100)
+            .build();
+        let r = crate::extract("../testdata/test.rar", &opts);
+        assert!(matches!(r, Err(Error::LimitExceeded(_))), "{r:?}");
+        let left = std::fs::read_dir("results/rar_limit").map_or(0, |d| d.count());
+        assert_eq!(left, 0);
+        let _ = std::fs::remove_dir_all("results/rar_limit");
+    }
 }
@@ -0,0 +1,239 @@
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::{Result, Error};
+
+use crate::extractor::{cpio as cpio_extractor, Destination, Entries, Property, ReadSeek, ToteExtractor};
+
+/// RPM format extractor implementation.
+/// It skips the lead, the signature, and the header of the package, and reads the payload as a cpio archive,
+/// like `rpm2cpio | cpio`. The tags in the header (e.g., name, version, and arch) are listed as the properties.
+pub(super) struct Extractor {}
+
+const LEAD_SIZE: usize = 96;
+const LEAD_MAGIC: [u8; 4] = [0xed, 0xab, 0xee, 0xdb];
+const HEADER_MAGIC: [u8; 3] = [0x8e, 0xad, 0xe8];
+/// The limits of the header size, the same as rpm.
+const MAX_INDEX_ENTRIES: usize = 0xffff;
+const MAX_DATA_SIZE: usize = 256 * 1024 * 1024;
+
+const TYPE_INT32: u32 = 4;
+const TYPE_STRING: u32 = 6;
+const TYPE_STRING_ARRAY: u32 = 8;
+const TYPE_I18NSTRING: u32 = 9;
+
+const TAG_PAYLOAD_COMPRESSOR: u32 = 1125;
+
+/// The tags listed as the properties in this order.
+const PROPERTY_TAGS: [(u32, &str); 8] = [
+    (1000, "name"),
+    (1003, "epoch"),
+    (1001, "version"),
+    (1002, "release"),
+    (1022, "arch"),
+    (1021, "os"),
+    (1014, "license"),
+    (1004, "summary"),
+];
+
+impl ToteExtractor for Extractor {
+    fn list_from(&self, mut reader: Box<dyn ReadSeek + '_>, archive_file: PathBuf) -> Result<Entries> {
+        let header = skip_to_payload(&mut reader, &archive_file)?;
+        let payload = payload_reader(Box::new(reader), &header, &archive_file)?;
+        let entries = cpio_extractor::list_cpio(cpio::Archive::new(payload), &archive_file)?;
+        Ok(Entries::new(archive_file, entries).with_properties(header.properties()))
+    }
+
+    fn perform_from(&self, reader: Box<dyn ReadSeek + '_>, dest: &mut Destination) -> Result<()> {
+        self.perform_stream(reader, dest)
+    }
+
+    fn perform_stream(&self, mut reader: Box<dyn Read + '_>, dest: &mut Destination) -> Result<()> {
+        let archive_file = dest.archive_file().to_path_buf();
+        let header = skip_to_payload(&mut reader, &archive_file)?;
+        let payload = payload_reader(reader, &header, &archive_file)?;
+        cpio_extractor::extract_cpio(cpio::Archive::new(payload), &archive_file, dest)
+    }
+}
+
+/// The header structure of rpm, which consists of the index entries and the data store.
+struct Header {
+    index: Vec<IndexEntry>,
+    data: Vec<u8>,
+}
+
+struct IndexEntry {
+    tag: u32,
+    kind: u32,
+    offset: usize,
+}
+
+impl Header {
+    /// Reads the header, and skips the padding to 8 bytes boundary if `aligned` (for the signature header).
+    fn read<R: Read + ?Sized>(reader: &mut R, aligned: bool) -> std::io::Result<Self> {
+        let mut intro = [0u8; 16];
+        reader.read_exact(&mut intro)?;
+        if intro[..3] != HEADER_MAGIC || intro[3] != 1 {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "invalid header magic"));
+        }
+        let count = u32::from_be_bytes([intro[8], intro[9], intro[10], intro[11]]) as usize;
+        let size = u32::from_be_bytes([intro[12], intro[13], intro[14], intro[15]]) as usize;
+        if count > MAX_INDEX_ENTRIES || size > MAX_DATA_SIZE {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "too large header"));
+        }
+        let mut index = vec![0u8; count * 16];
+        reader.read_exact(&mut index)?;
+        let mut data = vec![0u8; size];
+        reader.read_exact(&mut data)?;
+        if aligned {
+            let padding = (8 - size % 8) % 8;
+            reader.read_exact(&mut [0u8; 8][..padding])?;
+        }
+        let be = |b: &[u8]| u32::from_be_bytes([b[0], b[1], b[2], b[3]]);
+        let index = index.chunks_exact(16)
+            .map(|e| IndexEntry { tag: be(&e[0..4]), kind: be(&e[4..8]), offset: be(&e[8..12]) as usize })
+            .collect();
+        Ok(Self { index, data })
+    }
+
+    /// Returns the value of the given tag as a string (the first one for the arrays).
+    fn value(&self, tag: u32) -> Option<String> {
+        let entry = self.index.iter().find(|e| e.tag == tag)?;
+        let data = self.data.get(entry.offset..)?;
+        match entry.kind {
+            TYPE_INT32 => data.get(..4)
+                .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]).to_string()),
+            TYPE_STRING | TYPE_STRING_ARRAY | TYPE_I18NSTRING => {
+                let end = data.iter().position(|&b| b == 0)?;
+                Some(String::from_utf8_lossy(&data[..end]).to_string())
+            }
+            _ => None,
+        }
+    }
+
+    fn properties(&self) -> Vec<Property> {
+        PROPERTY_TAGS.iter()
+            .filter_map(|&(tag, name)| self.value(tag).map(|value| Property::new(name, value)))
+            .collect()
+    }
+}
+
+/// Reads the lead and the signature, and returns the header. Then, the reader points the payload.
+fn skip_to_payload<R: Read + ?Sized>(reader: &mut R, archive_file: &Path) -> Result<Header> {
+    let mut lead = [0u8; LEAD_SIZE];
+    reader.read_exact(&mut lead)
+        .map_err(|e| Error::corrupted(archive_file, None, e))?;
+    if lead[..4] != LEAD_MAGIC {
+        return Err(Error::corrupted(archive_file, None, "not a rpm package"));
+    }
+    Header::read(reader, true)
+        .and_then(|_| Header::read(reader, false))
+        .map_err(|e| Error::corrupted(archive_file, None, e))
+}
+
+/// Returns the decompressed payload.
+/// The compressor is detected by the magic number of the payload, since the old packages have no compressor tag.
+fn payload_reader<'a>(mut reader: Box<dyn Read + 'a>, header: &Header, archive_file: &Path) -> Result<Box<dyn Read + 'a>> {
+    let mut magic = vec![0u8; 6];
+    let len = read_fully(&mut reader, &mut magic)
+        .map_err(|e| Error::corrupted(archive_file, None, e))?;
+    magic.truncate(len);
+    let reader: Box<dyn Read + 'a> = Box::new(std::io::Cursor::new(magic.clone()).chain(reader));
+    let decoder: Box<dyn Read + 'a> = if magic.starts_with(&[0x1f, 0x8b]) {
+        Box::new(flate2::read::MultiGzDecoder::new(reader))
+    } else if magic.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+        Box::new(xz2::read::XzDecoder::new_multi_decoder(reader))
+    } else if magic.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+        Box::new(zstd::Decoder::new(reader).map_err(|e| Error::corrupted(archive_file, None, e))?)
+    } else if magic.starts_with(b"BZh") {
+        Box::new(bzip2::read::MultiBzDecoder::new(reader))
+    } else if magic.starts_with(b"0707") {
+        reader
+    } else if header.value(TAG_PAYLOAD_COMPRESSOR).as_deref() == Some("lzma") {
+        let stream = xz2::stream::Stream::new_lzma_decoder(u64::MAX)
+            .map_err(|e| Error::corrupted(archive_file, None, e))?;
+        Box::new(xz2::read::XzDecoder::new_stream(reader, stream))
+    } else {
+        let compressor = header.value(TAG_PAYLOAD_COMPRESSOR).unwrap_or_default();
+        return Err(Error::corrupted(archive_file, None, format!("unknown payload compressor: {compressor}")));
+    };
+    Ok(decoder)
+}
+
+/// Reads the bytes until `buf` is filled or the stream ends, and returns the number of the read bytes.
+fn read_fully<R: Read + ?Sized>(reader: &mut R, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut len = 0;
+    while len < buf.len() {
+        match reader.read(&mut buf[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_list_rpm_file() {
+        let extractor = Extractor {};
+        let entries = extractor.list(PathBuf::from("../testdata/test.rpm")).unwrap();
+        assert_eq!(entries.property("name"), Some("totebag-sample"));
+        assert_eq!(entries.property("version"), Some("0.1.0"));
+        assert_eq!(entries.property("release"), Some("1"));
+        assert_eq!(entries.property("arch"), Some("noarch"));
+        assert_eq!(entries.property("epoch"), None);
+        let names = entries.iter().map(|e| e.name.clone()).collect::<Vec<_>>();
+        assert_eq!(names.len(), 16);
+        assert_eq!(names.first(), Some(&"./Cargo.toml".to_string()));
+    }
+
+    #[test]
+    fn test_extract_rpm_archive() {
+        let config = crate::ExtractConfig::builder()
+            .dest("results/rpm")
+            .overwrite(true)
+            .build();
+        crate::extract("../testdata/test.rpm", &config).unwrap();
+        assert!(PathBuf::from("results/rpm/Cargo.toml").exists());
+        assert!(PathBuf::from("results/rpm/src/archiver/tar.rs").exists());
+        let _ = std::fs::remove_dir_all("results/rpm");
+    }
+
+    #[test]
+    fn test_payload_compressors() {
+        let rpm = std::fs::read("../testdata/test.rpm").unwrap();
+        let cpio = std::fs::read("../testdata/test_newc.cpio").unwrap();
+        let mut reader = std::io::Cursor::new(&rpm);
+        let header = skip_to_payload(&mut reader, Path::new("test.rpm")).unwrap();
+
+        let mut gz = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+        gz.write_all(&cpio).unwrap();
+        let payloads = [
+            gz.finish().unwrap(),
+            zstd::encode_all(cpio.as_slice(), 3).unwrap(),
+            cpio.clone(),
+        ];
+        for payload in payloads {
+            let mut decoded = vec![];
+            payload_reader(Box::new(payload.as_slice()), &header, Path::new("test.rpm")).unwrap()
+                .read_to_end(&mut decoded).unwrap();
+            assert!(decoded == cpio);
+        }
+        assert!(payload_reader(Box::new(&b"unknown"[..]), &header, Path::new("test.rpm")).is_err());
+    }
+
+    #[test]
+    fn test_not_rpm() {
+        let extractor = Extractor {};
+        match extractor.list(PathBuf::from("../testdata/test.cpio")) {
+            Err(Error::Corrupted { .. }) => {}
+            r => panic!("unexpected result: {r:?}"),
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+
+use crate::{Error, ExtractConfig, Result};
+
+/// Decides which entries are extracted by [`ExtractConfig::entries`],
+/// [`ExtractConfig::includes`], and [`ExtractConfig::excludes`].
+///
+/// The entry is selected if its path or any of its parent directories is selected,
+/// so that naming a directory extracts the whole directory.
+pub(super) struct Selection {
+    entries: Vec<PathBuf>,
+    includes: Gitignore,
+    excludes: Gitignore,
+    matched: RefCell<Vec<bool>>,
+}
+
+impl Selection {
+    pub(super) fn new(config: &ExtractConfig) -> Result<Self> {
+        let entries = config.entries.iter()
+            .map(|name| super::destination::sanitize(name).0)
+            .collect::<Vec<_>>();
+        Ok(Self {
+            matched: RefCell::new(vec![false; entries.len()]),
+            entries,
+            includes: build_matcher(&config.includes)?,
+            excludes: build_matcher(&config.excludes)?,
+        })
+    }
+
+    /// Returns `true` if the entry of the given (sanitized) path should be extracted.
+    pub(super) fn is_selected(&self, path: &Path, is_dir: bool) -> bool {
+        if path.as_os_str().is_empty() {
+            return true;
+        }
+        if self.excludes.matched_path_or_any_parents(path, is_dir).is_ignore() {
+            return false;
+        }
+        if self.entries.is_empty() && self.includes.is_empty() {
+            return true;
+        }
+        let mut selected = false;
+        for (i, entry) in self.entries.iter().enumerate() {
+            if path.starts_with(entry) {
+                self.matched.borrow_mut()[i] = true;
+                selected = true;
+            }
+        }
+        selected || self.includes.matched_path_or_any_parents(path, is_dir).is_ignore()
+    }
+
+    /// Returns the names in [`ExtractConfig::entries`] which matched no entries.
+    pub(super) fn unmatched(&self) -> Vec<PathBuf> {
+        self.entries.iter()
+            .zip(self.matched.borrow().iter())
+            .filter(|(_, matched)| !**matched)
+            .map(|(entry, _)| entry.clone())
+            .collect()
+    }
+}
+
+fn build_matcher(patterns: &[String]) -> Result<Gitignore> {
+    let mut builder = GitignoreBuilder::new("");
+    for pattern in patterns {
+        if builder.add_line(None, pattern).is_err() {
+            return Err(Error::Extractor(format!("{pattern}: invalid glob pattern")));
+        }
+    }
+    builder.build().map_err(|e| Error::Extractor(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn selection(entries: &[&str], includes: &[&str], excludes: &[&str]) -> Selection {
+        let to_vec = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let config = ExtractConfig::builder()
+            .entries(to_vec(entries))
+            .includes(to_vec(includes))
+            .excludes(to_vec(excludes))
+            .build();
+        Selection::new(&config).unwrap()
+    }
+
+    #[test]
+    fn test_select_all() {
+        let s = selection(&[], &[], &[]);
+        assert!(s.is_selected(Path::new("src/main.rs"), false));
+        assert!(s.is_selected(Path::new("src"), true));
+        assert!(s.unmatched().is_empty());
+    }
+
+    #[test]
+    fn test_select_entries() {
+        let s = selection(&["./src/", "README.md", "missing.txt"], &[], &[]);
+        assert!(s.is_selected(Path::new("src"), true));
+        assert!(s.is_selected(Path::new("src/extractor/tar.rs"), false));
+        assert!(s.is_selected(Path::new("README.md"), false));
+        assert!(!s.is_selected(Path::new("README.md.orig"), false));
+        assert!(!s.is_selected(Path::new("docs/README.md"), false));
+        assert_eq!(s.unmatched(), vec![PathBuf::from("missing.txt")]);
+    }
+
+    #[test]
+    fn test_select_globs() {
+        let s = selection(&["LICENSE"], &["*.toml", "src/extractor"], &["*.rs.orig", "target/"]);
+        assert!(s.is_selected(Path::new("LICENSE"), false));
+        assert!(s.is_selected(Path::new("Cargo.toml"), false));
+        assert!(s.is_selected(Path::new("cli/Cargo.toml"), false));
+        assert!(s.is_selected(Path::new("src/extractor/zip.rs"), false));
+        assert!(!s.is_selected(Path::new("src/extractor/zip.rs.orig"), false));
+        assert!(!s.is_selected(Path::new("src/main.rs"), false));
+        assert!(!s.is_selected(Path::new("target/Cargo.toml"), false));
+    }
+
+    #[test]
+    fn test_invalid_pattern() {
+        let config = ExtractConfig::builder()
+            .includes(vec!["src/{a,b".to_string()])
+            .build();
+        match Selection::new(&config) {
+            Err(Error::Extractor(m)) => assert_eq!(m, "src/{a,b: invalid glob pattern"),
+            r => panic!("unexpected result: {:?}", r.err()),
+        }
+    }
+}
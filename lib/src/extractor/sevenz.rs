@@ -1,11 +1,11 @@
-use std::fs::File;
-use std::path::PathBuf;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
 
 use crate::{Result, Error};
 use chrono::DateTime;
 use sevenz_rust::{Archive, BlockDecoder, Password, SevenZArchiveEntry};
 
-use crate::extractor::{Entry, Entries, ToteExtractor};
+use crate::extractor::{Attributes, Destination, Entry, Entries, ReadSeek, ToteExtractor};
 
 /// 7-Zip format extractor implementation.
 ///
@@ -13,25 +13,18 @@ use crate::extractor::{Entry, Entries, ToteExtractor};
 pub(super) struct Extractor {}
 
 impl ToteExtractor for Extractor {
-    fn list(&self, archive_file: PathBuf) -> Result<Entries> {
-        let mut reader = File::open(&archive_file).unwrap();
-        let len = reader.metadata().unwrap().len();
-        match Archive::read(&mut reader, len, Password::empty().as_ref()) {
-            Ok(archive) => {
-                let mut r = vec![];
-                for entry in &archive.files {
-                    r.push(convert(entry));
-                }
-                Ok(Entries::new(archive_file, r))
-            }
-            Err(e) => Err(Error::Extractor(e.to_string())),
+    fn list_from(&self, mut reader: Box<dyn ReadSeek + '_>, archive_file: PathBuf) -> Result<Entries> {
+        let archive = read_archive(&mut reader, &archive_file, &Password::empty())?;
+        let mut r = vec![];
+        for entry in &archive.files {
+            r.push(convert(entry));
         }
+        Ok(Entries::new(archive_file, r))
     }
 
-    fn perform(&self, archive_file: PathBuf, base: PathBuf) -> Result<()> {
-        let file = File::open(archive_file)
-            .map_err(Error::IO)?;
-        extract(&file, base)
+    fn perform_from(&self, mut reader: Box<dyn ReadSeek + '_>, dest: &mut Destination) -> Result<()> {
+        let archive_file = dest.archive_file().to_path_buf();
+        extract(&mut reader, &archive_file, dest)
     }
 }
 
@@ -49,26 +42,71 @@ fn convert(e: &SevenZArchiveEntry) -> Entry {
         .build()
 }
 
-fn extract(mut file: &File, base: PathBuf) -> Result<()> {
-    let len = file.metadata().unwrap().len();
+fn read_archive<R: Read + Seek>(reader: &mut R, archive_file: &Path, password: &Password) -> Result<Archive> {
+    let len = reader.seek(SeekFrom::End(0))
+        .and_then(|len| reader.rewind().map(|_| len))
+        .map_err(Error::IO)?;
+    Archive::read(reader, len, password.as_ref())
+        .map_err(|e| Error::corrupted(archive_file, None, e))
+}
+
+fn extract<R: Read + Seek>(reader: &mut R, archive_file: &Path, dest: &mut Destination) -> Result<()> {
     let password = Password::empty();
-    let archive = match Archive::read(&mut file, len, password.as_ref()) {
-        Ok(reader) => reader,
-        Err(e) => return Err(Error::Fatal(Box::new(e))),
-    };
+    let archive = read_archive(reader, archive_file, &password)?;
     let folder_count = archive.folders.len();
     for findex in 0..folder_count {
-        let folder_decoder = BlockDecoder::new(findex, &archive, password.as_slice(), &mut file);
+        let folder_decoder = BlockDecoder::new(findex, &archive, password.as_slice(), reader);
+        let mut err = None;
+        let mut current = None;
         if let Err(e) = folder_decoder.for_each_entries(&mut |entry, reader| {
-            let d = base.join(&entry.name);
-            sevenz_rust::default_entry_extract_fn(entry, reader, &d)
+            current = Some(entry.name().to_string());
+            match write_entry(entry, reader, dest) {
+                Ok(_) => Ok(true),
+                Err(e) => {
+                    err = Some(e);
+                    Ok(false)
+                }
+            }
         }) {
-            return Err(Error::Fatal(Box::new(e)));
+            return Err(Error::corrupted(archive_file, current.as_deref(), e));
+        }
+        if let Some(e) = err {
+            return Err(e);
         }
     }
     Ok(())
 }
 
+fn write_entry(entry: &SevenZArchiveEntry, reader: &mut dyn Read, dest: &mut Destination) -> Result<()> {
+    let Some(path) = dest.path_of(entry.name())? else {
+        return Ok(());
+    };
+    let attrs = attributes(entry);
+    if entry.is_directory() {
+        dest.create_dir(&path)
+    } else if attrs.mode.is_some_and(|mode| mode & 0o170000 == 0o120000) {
+        let mut target = String::new();
+        reader.read_to_string(&mut target)
+            .map_err(|e| Error::corrupted(dest.archive_file(), Some(entry.name()), e))?;
+        dest.symlink(&path, &target)
+    } else {
+        dest.write(&path, reader, &attrs)
+            .map(|_| ())
+    }
+}
+
+/// 7-Zip stores the unix permission bits in the high 16 bits of the windows attributes
+/// when `FILE_ATTRIBUTE_UNIX_EXTENSION` (0x8000) is set.
+fn attributes(entry: &SevenZArchiveEntry) -> Attributes {
+    let attrs = entry.windows_attributes();
+    let mode = (entry.has_windows_attributes && attrs & 0x8000 != 0).then_some(attrs >> 16);
+    let mtime = entry.has_last_modified_date.then(|| entry.last_modified_date().to_unix_time());
+    Attributes::builder()
+        .mode(mode)
+        .mtime(mtime)
+        .build()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -81,12 +119,12 @@ mod tests {
             Ok(r) => {
                 let r = r.iter().map(|e| e.name.clone()).collect::<Vec<_>>();
                 assert_eq!(r.len(), 21);
-                assert_eq!(r.get(0), Some("Cargo.toml".to_string()).as_ref());
+                assert_eq!(r.first(), Some("Cargo.toml".to_string()).as_ref());
                 assert_eq!(r.get(1), Some("build.rs".to_string()).as_ref());
                 assert_eq!(r.get(2), Some("LICENSE".to_string()).as_ref());
                 assert_eq!(r.get(3), Some("README.md".to_string()).as_ref());
             }
-            Err(_) => assert!(false),
+            Err(_) => panic!("unexpected result"),
         }
     }
 
@@ -98,11 +136,10 @@ mod tests {
             .build();
         match crate::extract(archive_file, &opts) {
             Ok(_) => {
-                assert!(true);
                 assert!(PathBuf::from("results/sevenz/Cargo.toml").exists());
                 std::fs::remove_dir_all(PathBuf::from("results/sevenz")).unwrap();
             }
-            Err(_) => assert!(false),
+            Err(_) => panic!("unexpected result"),
         };
     }
 }
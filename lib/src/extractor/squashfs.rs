@@ -0,0 +1,601 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::PathBuf;
+
+use crate::{Error, Result};
+use crate::extractor::{Attributes, Destination, Entries, Entry, ReadSeek, ToteExtractor};
+
+/// SquashFS image extractor implementation.
+/// It reads the images of SquashFS 4.0 compressed by gzip, lzma, xz, lz4, or zstd,
+/// and restores the directories, the regular files, and the symbolic links with their permissions.
+/// The device files, the named pipes, and the sockets are listed, but are not extracted.
+pub(super) struct Extractor {}
+
+const MAGIC: u32 = 0x7371_7368;
+const SUPERBLOCK_SIZE: usize = 96;
+/// The size of the uncompressed metadata block.
+const METADATA_SIZE: usize = 8192;
+const METADATA_UNCOMPRESSED: u16 = 0x8000;
+const DATA_UNCOMPRESSED: u32 = 0x0100_0000;
+const NO_FRAGMENT: u32 = 0xffff_ffff;
+const NO_TABLE: u64 = 0xffff_ffff_ffff_ffff;
+/// The limit of the depth of the directories, for the images whose directories make a loop.
+const MAX_DEPTH: usize = 256;
+
+const TYPE_DIR: u16 = 1;
+const TYPE_FILE: u16 = 2;
+const TYPE_SYMLINK: u16 = 3;
+const TYPE_EXT_DIR: u16 = 8;
+const TYPE_EXT_FILE: u16 = 9;
+const TYPE_EXT_SYMLINK: u16 = 10;
+
+impl ToteExtractor for Extractor {
+    fn list_from(&self, reader: Box<dyn ReadSeek + '_>, archive_file: PathBuf) -> Result<Entries> {
+        let files = Image::open(reader)
+            .and_then(|mut image| image.files())
+            .map_err(|e| Error::corrupted(&archive_file, None, e))?;
+        let entries = files.iter()
+            .map(SquashFile::to_entry)
+            .collect();
+        Ok(Entries::new(archive_file, entries))
+    }
+
+    fn perform_from(&self, reader: Box<dyn ReadSeek + '_>, dest: &mut Destination) -> Result<()> {
+        let archive_file = dest.archive_file().to_path_buf();
+        let mut image = Image::open(reader)
+            .map_err(|e| Error::corrupted(&archive_file, None, e))?;
+        let files = image.files()
+            .map_err(|e| Error::corrupted(&archive_file, None, e))?;
+        for file in files {
+            let Some(path) = dest.path_of(&file.name)? else {
+                continue;
+            };
+            match &file.kind {
+                Kind::Directory => dest.create_dir(&path)?,
+                Kind::Symlink(target) => dest.symlink(&path, target)?,
+                Kind::File(data) => {
+                    log::info!("extracting {} ({} bytes)", file.name, data.size);
+                    let attrs = Attributes::builder()
+                        .mode(file.mode)
+                        .mtime(file.mtime as i64)
+                        .uid(file.uid)
+                        .gid(file.gid)
+                        .build();
+                    let mut reader = FileReader::new(&mut image, data);
+                    let written = dest.write(&path, &mut reader, &attrs)?;
+                    if written != data.size {
+                        return Err(Error::corrupted(&archive_file, Some(&file.name), "unexpected end of the entry"));
+                    }
+                }
+                Kind::Other => log::warn!("{}: skip the special file", file.name),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The compression algorithms of SquashFS, the ids are the ones in the superblock.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Compressor {
+    Gzip,
+    Lzma,
+    Xz,
+    Lz4,
+    Zstd,
+}
+
+impl Compressor {
+    fn from_id(id: u16) -> std::io::Result<Self> {
+        match id {
+            1 => Ok(Compressor::Gzip),
+            2 => Ok(Compressor::Lzma),
+            4 => Ok(Compressor::Xz),
+            5 => Ok(Compressor::Lz4),
+            6 => Ok(Compressor::Zstd),
+            3 => Err(invalid_data("unsupported compressor: lzo")),
+            id => Err(invalid_data(format!("unknown compressor: {id}"))),
+        }
+    }
+
+    /// Decompresses the given block, whose decompressed size is at most `limit` bytes.
+    fn decompress(&self, data: &[u8], limit: usize) -> std::io::Result<Vec<u8>> {
+        let reader: Box<dyn Read + '_> = match self {
+            // the "gzip" compressor of SquashFS writes the zlib streams.
+            Compressor::Gzip => Box::new(flate2::read::ZlibDecoder::new(data)),
+            Compressor::Lzma => {
+                let stream = xz2::stream::Stream::new_lzma_decoder(u64::MAX)?;
+                Box::new(xz2::read::XzDecoder::new_stream(data, stream))
+            }
+            Compressor::Xz => Box::new(xz2::read::XzDecoder::new_multi_decoder(data)),
+            Compressor::Lz4 => {
+                return lz4_flex::block::decompress(data, limit)
+                    .map_err(|e| invalid_data(e.to_string()));
+            }
+            Compressor::Zstd => Box::new(zstd::Decoder::new(data)?),
+        };
+        let mut out = Vec::with_capacity(limit);
+        reader.take(limit as u64 + 1).read_to_end(&mut out)?;
+        if out.len() > limit {
+            return Err(invalid_data("too large block"));
+        }
+        Ok(out)
+    }
+}
+
+/// The superblock of SquashFS 4.0.
+struct Superblock {
+    block_size: u32,
+    fragment_count: u32,
+    compressor: Compressor,
+    id_count: u16,
+    root_inode: u64,
+    bytes_used: u64,
+    id_table: u64,
+    inode_table: u64,
+    directory_table: u64,
+    fragment_table: u64,
+}
+
+impl Superblock {
+    fn parse(data: &[u8; SUPERBLOCK_SIZE]) -> std::io::Result<Self> {
+        if le_u32(&data[0..4]) != MAGIC {
+            return Err(invalid_data("not a squashfs image"));
+        }
+        let (major, minor) = (le_u16(&data[28..30]), le_u16(&data[30..32]));
+        if (major, minor) != (4, 0) {
+            return Err(invalid_data(format!("unsupported squashfs version: {major}.{minor}")));
+        }
+        let block_size = le_u32(&data[12..16]);
+        if !(4096..=1024 * 1024).contains(&block_size) || !block_size.is_power_of_two() {
+            return Err(invalid_data(format!("invalid block size: {block_size}")));
+        }
+        Ok(Self {
+            block_size,
+            fragment_count: le_u32(&data[16..20]),
+            compressor: Compressor::from_id(le_u16(&data[20..22]))?,
+            id_count: le_u16(&data[26..28]),
+            root_inode: le_u64(&data[32..40]),
+            bytes_used: le_u64(&data[40..48]),
+            id_table: le_u64(&data[48..56]),
+            inode_table: le_u64(&data[64..72]),
+            directory_table: le_u64(&data[72..80]),
+            fragment_table: le_u64(&data[80..88]),
+        })
+    }
+}
+
+#[derive(Debug)]
+enum Kind {
+    Directory,
+    File(FileData),
+    Symlink(String),
+    /// The device files, the named pipes, and the sockets.
+    Other,
+}
+
+/// The location of the data of the regular file.
+#[derive(Debug)]
+struct FileData {
+    blocks_start: u64,
+    size: u64,
+    /// The on-disk sizes of the data blocks, `0` means the sparse block.
+    block_sizes: Vec<u32>,
+    /// The index of the fragment block and the offset in it, which stores the tail of the file.
+    fragment: Option<(u32, u32)>,
+}
+
+/// The file in the image. The name of the directory ends with `/`.
+struct SquashFile {
+    name: String,
+    kind: Kind,
+    /// The unix mode, including the file type bits.
+    mode: u32,
+    uid: u32,
+    gid: u32,
+    mtime: u32,
+}
+
+impl SquashFile {
+    fn to_entry(&self) -> Entry {
+        let date = chrono::DateTime::from_timestamp(self.mtime as i64, 0)
+            .map(|d| d.naive_utc());
+        let size = match &self.kind {
+            Kind::File(data) => Some(data.size),
+            _ => None,
+        };
+        Entry::new(self.name.clone(), None, size, Some(self.mode), date)
+    }
+}
+
+/// The position in the metadata (the inode table or the directory table),
+/// the absolute position of the metadata block and the offset in the uncompressed block.
+#[derive(Clone, Copy)]
+struct Cursor {
+    block: u64,
+    offset: usize,
+}
+
+struct Image<R: Read + Seek> {
+    reader: R,
+    superblock: Superblock,
+    ids: Vec<u32>,
+    fragments: Vec<(u64, u32)>,
+    /// The uncompressed metadata blocks and the positions of the next blocks.
+    metadata: HashMap<u64, (Vec<u8>, u64)>,
+}
+
+impl<R: Read + Seek> Image<R> {
+    fn open(mut reader: R) -> std::io::Result<Self> {
+        let mut data = [0u8; SUPERBLOCK_SIZE];
+        reader.seek(SeekFrom::Start(0))?;
+        reader.read_exact(&mut data)?;
+        let superblock = Superblock::parse(&data)?;
+        let mut image = Self { reader, superblock, ids: vec![], fragments: vec![], metadata: HashMap::new() };
+        let ids = image.read_table(image.superblock.id_table, image.superblock.id_count as usize, 4)?;
+        image.ids = ids.chunks_exact(4).map(le_u32).collect();
+        if image.superblock.fragment_table != NO_TABLE {
+            let fragments = image.read_table(image.superblock.fragment_table, image.superblock.fragment_count as usize, 16)?;
+            image.fragments = fragments.chunks_exact(16)
+                .map(|e| (le_u64(&e[0..8]), le_u32(&e[8..12])))
+                .collect();
+        }
+        Ok(image)
+    }
+
+    /// Reads the lookup table (the id table or the fragment table) of `count` entries of `size` bytes,
+    /// which is the list of the locations of the metadata blocks storing the entries.
+    fn read_table(&mut self, location: u64, count: usize, size: usize) -> std::io::Result<Vec<u8>> {
+        let length = count * size;
+        if length as u64 > self.superblock.bytes_used {
+            return Err(invalid_data("too large table"));
+        }
+        let blocks = length.div_ceil(METADATA_SIZE);
+        let mut pointers = vec![0u8; blocks * 8];
+        self.reader.seek(SeekFrom::Start(location))?;
+        self.reader.read_exact(&mut pointers)?;
+        let mut table = Vec::with_capacity(length);
+        for pointer in pointers.chunks_exact(8) {
+            let len = (length - table.len()).min(METADATA_SIZE);
+            let mut cursor = Cursor { block: le_u64(pointer), offset: 0 };
+            table.extend(self.read_metadata(&mut cursor, len)?);
+        }
+        Ok(table)
+    }
+
+    /// Reads the metadata block at the given position, and returns it and the position of the next block.
+    fn metadata_block(&mut self, position: u64) -> std::io::Result<&(Vec<u8>, u64)> {
+        if !self.metadata.contains_key(&position) {
+            let mut header = [0u8; 2];
+            self.reader.seek(SeekFrom::Start(position))?;
+            self.reader.read_exact(&mut header)?;
+            let header = u16::from_le_bytes(header);
+            let size = (header & !METADATA_UNCOMPRESSED) as usize;
+            let mut data = vec![0u8; size];
+            self.reader.read_exact(&mut data)?;
+            if header & METADATA_UNCOMPRESSED == 0 {
+                data = self.superblock.compressor.decompress(&data, METADATA_SIZE)?;
+            }
+            let next = position.checked_add(2 + size as u64)
+                .ok_or_else(|| invalid_data("too large metadata position"))?;
+            self.metadata.insert(position, (data, next));
+        }
+        Ok(&self.metadata[&position])
+    }
+
+    /// Reads `len` bytes of the metadata from the given cursor, and advances it.
+    fn read_metadata(&mut self, cursor: &mut Cursor, len: usize) -> std::io::Result<Vec<u8>> {
+        if len as u64 > self.superblock.bytes_used.saturating_mul(METADATA_SIZE as u64 / 2) {
+            return Err(invalid_data("too large metadata"));
+        }
+        let bytes_used = self.superblock.bytes_used;
+        let mut out = Vec::with_capacity(len.min(METADATA_SIZE));
+        while out.len() < len {
+            let (data, next) = self.metadata_block(cursor.block)?;
+            let available = data.get(cursor.offset..).unwrap_or_default();
+            let n = available.len().min(len - out.len());
+            out.extend_from_slice(&available[..n]);
+            cursor.offset += n;
+            if out.len() < len {
+                if *next >= bytes_used {
+                    return Err(invalid_data("unexpected end of the metadata"));
+                }
+                cursor.offset -= data.len().min(cursor.offset);
+                cursor.block = *next;
+            }
+        }
+        Ok(out)
+    }
+
+    fn id(&self, index: u16) -> std::io::Result<u32> {
+        self.ids.get(index as usize).copied()
+            .ok_or_else(|| invalid_data(format!("invalid id index: {index}")))
+    }
+
+    /// Returns the files in the image, in the order of the directory entries.
+    fn files(&mut self) -> std::io::Result<Vec<SquashFile>> {
+        let mut files = vec![];
+        let mut visited = HashSet::new();
+        let root = self.superblock.root_inode;
+        let Some(listing) = self.read_inode(root, String::new())?.1 else {
+            return Err(invalid_data("the root inode is not a directory"));
+        };
+        self.walk(root, listing, "", 0, &mut files, &mut visited)?;
+        Ok(files)
+    }
+
+    /// Walks the listing of the directory of the given inode reference.
+    /// The loops are detected by the inode references, since the empty directories share
+    /// the positions of their listings with the next directories.
+    fn walk(&mut self, reference: u64, (mut cursor, size): (Cursor, usize), prefix: &str, depth: usize,
+            files: &mut Vec<SquashFile>, visited: &mut HashSet<u64>) -> std::io::Result<()> {
+        if depth > MAX_DEPTH || !visited.insert(reference) {
+            return Err(invalid_data("too deep or looped directories"));
+        }
+        let listing = self.read_metadata(&mut cursor, size)?;
+        let mut pos = 0;
+        while pos + 12 <= listing.len() {
+            let count = le_u32(&listing[pos..pos + 4]) as usize + 1;
+            let start = le_u32(&listing[pos + 4..pos + 8]) as u64;
+            pos += 12;
+            for _ in 0..count {
+                let header = listing.get(pos..pos + 8).ok_or_else(|| invalid_data("broken directory entry"))?;
+                let offset = le_u16(&header[0..2]) as u64;
+                let name_size = le_u16(&header[6..8]) as usize + 1;
+                let name = listing.get(pos + 8..pos + 8 + name_size).ok_or_else(|| invalid_data("broken directory entry"))?;
+                let name = format!("{prefix}{}", String::from_utf8_lossy(name));
+                pos += 8 + name_size;
+                let reference = start << 16 | offset;
+                let (file, listing) = self.read_inode(reference, name)?;
+                let path = file.name.clone();
+                files.push(file);
+                if let Some(listing) = listing {
+                    self.walk(reference, listing, &path, depth + 1, files, visited)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads the inode of the given reference (the block position relative to the inode table, and the offset in it),
+    /// and returns the file and the location of the directory listing if it is a directory.
+    fn read_inode(&mut self, reference: u64, name: String) -> std::io::Result<(SquashFile, Option<(Cursor, usize)>)> {
+        let block = self.superblock.inode_table.checked_add(reference >> 16)
+            .ok_or_else(|| invalid_data("too large inode position"))?;
+        let mut cursor = Cursor { block, offset: (reference & 0xffff) as usize };
+        let header = self.read_metadata(&mut cursor, 16)?;
+        let inode_type = le_u16(&header[0..2]);
+        let permissions = le_u16(&header[2..4]) as u32;
+        let uid = self.id(le_u16(&header[4..6]))?;
+        let gid = self.id(le_u16(&header[6..8]))?;
+        let mtime = le_u32(&header[8..12]);
+        let mut listing = None;
+        let (kind, file_type, name) = match inode_type {
+            TYPE_DIR | TYPE_EXT_DIR => {
+                let body = self.read_metadata(&mut cursor, if inode_type == TYPE_DIR { 16 } else { 24 })?;
+                let (size, block, offset) = if inode_type == TYPE_DIR {
+                    (le_u16(&body[8..10]) as usize, le_u32(&body[0..4]), le_u16(&body[10..12]))
+                } else {
+                    (le_u32(&body[4..8]) as usize, le_u32(&body[8..12]), le_u16(&body[18..20]))
+                };
+                // the size of the listing is 3 bytes smaller than the recorded one, for `.` and `..`.
+                let block = self.superblock.directory_table.checked_add(block as u64)
+                    .ok_or_else(|| invalid_data("too large directory position"))?;
+                let cursor = Cursor { block, offset: offset as usize };
+                listing = Some((cursor, size.saturating_sub(3)));
+                let name = if name.is_empty() { name } else { format!("{name}/") };
+                (Kind::Directory, 0o040000, name)
+            }
+            TYPE_FILE | TYPE_EXT_FILE => (Kind::File(self.read_file(&mut cursor, inode_type)?), 0o100000, name),
+            TYPE_SYMLINK | TYPE_EXT_SYMLINK => {
+                let body = self.read_metadata(&mut cursor, 8)?;
+                let target_size = le_u32(&body[4..8]) as usize;
+                let target = self.read_metadata(&mut cursor, target_size)?;
+                (Kind::Symlink(String::from_utf8_lossy(&target).to_string()), 0o120000, name)
+            }
+            4 | 11 => (Kind::Other, 0o060000, name),
+            5 | 12 => (Kind::Other, 0o020000, name),
+            6 | 13 => (Kind::Other, 0o010000, name),
+            7 | 14 => (Kind::Other, 0o140000, name),
+            t => return Err(invalid_data(format!("{name}: unknown inode type: {t}"))),
+        };
+        let file = SquashFile { name, kind, mode: file_type | permissions, uid, gid, mtime };
+        Ok((file, listing))
+    }
+
+    fn read_file(&mut self, cursor: &mut Cursor, inode_type: u16) -> std::io::Result<FileData> {
+        let (blocks_start, size, fragment, offset) = if inode_type == TYPE_FILE {
+            let body = self.read_metadata(cursor, 16)?;
+            (le_u32(&body[0..4]) as u64, le_u32(&body[12..16]) as u64, le_u32(&body[4..8]), le_u32(&body[8..12]))
+        } else {
+            let body = self.read_metadata(cursor, 40)?;
+            (le_u64(&body[0..8]), le_u64(&body[8..16]), le_u32(&body[28..32]), le_u32(&body[32..36]))
+        };
+        let block_size = self.superblock.block_size as u64;
+        let fragment = (fragment != NO_FRAGMENT).then_some((fragment, offset));
+        let count = if fragment.is_some() { size / block_size } else { size.div_ceil(block_size) };
+        if count.saturating_mul(4) > self.superblock.bytes_used {
+            return Err(invalid_data("too large file"));
+        }
+        let block_sizes = self.read_metadata(cursor, count as usize * 4)?
+            .chunks_exact(4)
+            .map(le_u32)
+            .collect();
+        Ok(FileData { blocks_start, size, block_sizes, fragment })
+    }
+
+    /// Reads the data block at the given position, whose decompressed size is `len` bytes.
+    fn read_block(&mut self, position: u64, size: u32, len: usize) -> std::io::Result<Vec<u8>> {
+        let on_disk = (size & !DATA_UNCOMPRESSED) as usize;
+        if on_disk > self.superblock.block_size as usize * 2 {
+            return Err(invalid_data("too large block"));
+        }
+        let mut data = vec![0u8; on_disk];
+        self.reader.seek(SeekFrom::Start(position))?;
+        self.reader.read_exact(&mut data)?;
+        if size & DATA_UNCOMPRESSED == 0 {
+            data = self.superblock.compressor.decompress(&data, self.superblock.block_size as usize)?;
+        }
+        if data.len() < len {
+            return Err(invalid_data("too short block"));
+        }
+        data.truncate(len);
+        Ok(data)
+    }
+}
+
+/// Reads the data of the regular file, block by block, and the tail in the fragment block.
+struct FileReader<'a, R: Read + Seek> {
+    image: &'a mut Image<R>,
+    data: &'a FileData,
+    index: usize,
+    position: u64,
+    remaining: u64,
+    buffer: Vec<u8>,
+    consumed: usize,
+}
+
+impl<'a, R: Read + Seek> FileReader<'a, R> {
+    fn new(image: &'a mut Image<R>, data: &'a FileData) -> Self {
+        Self { image, position: data.blocks_start, remaining: data.size, data, index: 0, buffer: vec![], consumed: 0 }
+    }
+
+    /// Fills the buffer with the next block, and returns `false` if the file ends.
+    fn next_block(&mut self) -> std::io::Result<bool> {
+        let block_size = self.image.superblock.block_size as u64;
+        let len = self.remaining.min(block_size) as usize;
+        if len == 0 {
+            return Ok(false);
+        }
+        self.buffer = match self.data.block_sizes.get(self.index) {
+            Some(&size) if size & !DATA_UNCOMPRESSED == 0 => vec![0u8; len],
+            Some(&size) => {
+                let block = self.image.read_block(self.position, size, len)?;
+                self.position = self.position.checked_add((size & !DATA_UNCOMPRESSED) as u64)
+                    .ok_or_else(|| invalid_data("too large data block position"))?;
+                block
+            }
+            None => {
+                let (index, offset) = self.data.fragment
+                    .ok_or_else(|| invalid_data("missing the fragment"))?;
+                let &(start, size) = self.image.fragments.get(index as usize)
+                    .ok_or_else(|| invalid_data(format!("invalid fragment index: {index}")))?;
+                let end = offset as usize + len;
+                let block = self.image.read_block(start, size, end)?;
+                block[offset as usize..end].to_vec()
+            }
+        };
+        self.index += 1;
+        self.remaining -= len as u64;
+        self.consumed = 0;
+        Ok(true)
+    }
+}
+
+impl<R: Read + Seek> Read for FileReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.consumed == self.buffer.len() && !self.next_block()? {
+            return Ok(0);
+        }
+        let n = buf.len().min(self.buffer.len() - self.consumed);
+        buf[..n].copy_from_slice(&self.buffer[self.consumed..self.consumed + n]);
+        self.consumed += n;
+        Ok(n)
+    }
+}
+
+fn le_u16(bytes: &[u8]) -> u16 {
+    u16::from_le_bytes([bytes[0], bytes[1]])
+}
+
+fn le_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+fn le_u64(bytes: &[u8]) -> u64 {
+    u64::from_le_bytes(bytes[..8].try_into().unwrap_or_default())
+}
+
+fn invalid_data<S: Into<String>>(message: S) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_list_squashfs() {
+        let extractor = Extractor {};
+        let entries = extractor.list(PathBuf::from("../testdata/test.squashfs")).unwrap();
+        let names = entries.iter().map(|e| e.name.clone()).collect::<Vec<_>>();
+        assert_eq!(names.len(), 20);
+        assert_eq!(names.first(), Some(&"Cargo.toml".to_string()));
+        assert!(names.contains(&"src/".to_string()));
+        assert!(names.contains(&"src/extractor/tar.rs".to_string()));
+        let build = entries.iter().find(|e| e.name == "build.rs").unwrap();
+        assert_eq!(build.unix_mode, Some(0o100755));
+        let link = entries.iter().find(|e| e.name == "src/lib.rs").unwrap();
+        assert_eq!(link.unix_mode, Some(0o120777));
+        let tar = entries.iter().find(|e| e.name == "src/extractor/tar.rs").unwrap();
+        assert_eq!(tar.original_size, Some(5398));
+        assert_eq!(tar.date.map(|d| d.to_string()), Some("2024-05-01 03:34:56".to_string()));
+    }
+
+    #[test]
+    fn test_extract_compressors() {
+        // the empty directories share the positions of their listings with the next directories.
+        for file in ["test.squashfs", "test_xz.squashfs", "test_zstd.sqfs", "test_lz4.snap",
+                "test_empty_dir.squashfs", "test_empty_dirs.squashfs"] {
+            let dest = PathBuf::from("results/squashfs").join(file);
+            let config = crate::ExtractConfig::builder()
+                .dest(&dest)
+                .overwrite(true)
+                .build();
+            crate::extract(PathBuf::from("../testdata").join(file), &config).unwrap();
+            for name in ["Cargo.toml", "src/extractor/tar.rs", "src/cli.rs"] {
+                let extracted = std::fs::read(dest.join(name)).unwrap();
+                assert!(extracted == std::fs::read(PathBuf::from("../testdata/sample").join(name)).unwrap(), "{file}: {name}");
+            }
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                let mode = std::fs::metadata(dest.join("build.rs")).unwrap().permissions().mode();
+                assert_eq!(mode & 0o777, 0o755, "{file}");
+                assert_eq!(std::fs::read_link(dest.join("src/lib.rs")).unwrap(), PathBuf::from("main.rs"));
+            }
+        }
+        for dir in ["test_empty_dir.squashfs/src/empty", "test_empty_dirs.squashfs/empty1", "test_empty_dirs.squashfs/empty2"] {
+            let dir = PathBuf::from("results/squashfs").join(dir);
+            assert!(dir.is_dir() && std::fs::read_dir(&dir).unwrap().next().is_none(), "{dir:?}");
+        }
+        let _ = std::fs::remove_dir_all("results/squashfs");
+    }
+
+    #[test]
+    fn test_overflowing_positions() {
+        let image = std::fs::read("../testdata/test.squashfs").unwrap();
+        let extractor = Extractor {};
+        // the inode table and the directory table at the end of the address space.
+        for (offset, reference) in [(64, 1u64 << 16), (72, 0)] {
+            let mut image = image.clone();
+            image[offset..offset + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+            if reference != 0 {
+                image[32..40].copy_from_slice(&reference.to_le_bytes());
+            }
+            let reader = Box::new(std::io::Cursor::new(image));
+            match extractor.list_from(reader, PathBuf::from("overflow.squashfs")) {
+                Err(Error::Corrupted { .. }) => {}
+                r => panic!("unexpected result: {r:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_not_squashfs() {
+        let extractor = Extractor {};
+        match extractor.list(PathBuf::from("../testdata/test.iso")) {
+            Err(Error::Corrupted { .. }) => {}
+            r => panic!("unexpected result: {r:?}"),
+        }
+    }
+}
@@ -1,12 +1,12 @@
-use std::fs::create_dir_all;
 use std::io::Read;
-use std::{fs::File, path::PathBuf};
+use std::path::{Path, PathBuf};
 
 use crate::{Result, Error};
 use tar::Archive;
 use xz2::read::XzDecoder;
 
-use crate::extractor::{Entry as ToteEntry, Entries, ToteExtractor};
+use crate::codec::{LzipDecoder, LzwDecoder};
+use crate::extractor::{Attributes, Destination, Entry as ToteEntry, Entries, ReadSeek, ToteExtractor};
 
 /// TAR format extractor implementation.
 pub(super) struct Extractor {}
@@ -23,122 +23,354 @@ pub(super) struct XzExtractor {}
 /// TAR+ZSTD format extractor implementation.
 pub(super) struct ZstdExtractor {}
 
+/// TAR+LZ4 format extractor implementation.
+pub(super) struct Lz4Extractor {}
+
+/// TAR+LZMA (the legacy `.lzma` format) extractor implementation.
+pub(super) struct LzmaExtractor {}
+
+/// TAR+LZIP format extractor implementation.
+pub(super) struct LzExtractor {}
+
+/// TAR+COMPRESS (the unix `compress` command, `.Z`) extractor implementation.
+pub(super) struct ZExtractor {}
+
+/// TAR+BROTLI format extractor implementation.
+pub(super) struct BrExtractor {}
+
 impl ToteExtractor for Extractor {
-    fn list(&self, archive_file: PathBuf) -> Result<Entries> {
-        open_tar_file(&archive_file, |f| f)
+    fn list_from(&self, reader: Box<dyn ReadSeek + '_>, archive_file: PathBuf) -> Result<Entries> {
+        open_tar(reader, &archive_file, Ok)
             .and_then(|archive| list_tar(archive, archive_file))
     }
-    fn perform(&self, archive_file: PathBuf, base: PathBuf) -> Result<()> {
-        open_tar_file(&archive_file, |f| f)
-            .and_then(|archive| extract_tar(archive, base))
+
+    fn perform_from(&self, reader: Box<dyn ReadSeek + '_>, dest: &mut Destination) -> Result<()> {
+        self.perform_stream(reader, dest)
+    }
+
+    fn perform_stream(&self, reader: Box<dyn Read + '_>, dest: &mut Destination) -> Result<()> {
+        let archive_file = dest.archive_file().to_path_buf();
+        open_tar(reader, &archive_file, Ok)
+            .and_then(|archive| extract_tar(archive, &archive_file, dest))
     }
 }
 
 impl ToteExtractor for GzExtractor {
-    fn list(&self, archive_file: PathBuf) -> Result<Entries> {
-        open_tar_file(&archive_file, flate2::read::GzDecoder::new)
+    fn list_from(&self, reader: Box<dyn ReadSeek + '_>, archive_file: PathBuf) -> Result<Entries> {
+        open_tar(reader, &archive_file, |r| Ok(flate2::read::GzDecoder::new(r)))
             .and_then(|archive| list_tar(archive, archive_file))
     }
-    fn perform(&self, archive_file: PathBuf, base: PathBuf) -> Result<()> {
-        open_tar_file(&archive_file, flate2::read::GzDecoder::new)
-            .and_then(|archive| extract_tar(archive, base))
+
+    fn perform_from(&self, reader: Box<dyn ReadSeek + '_>, dest: &mut Destination) -> Result<()> {
+        self.perform_stream(reader, dest)
+    }
+
+    fn perform_stream(&self, reader: Box<dyn Read + '_>, dest: &mut Destination) -> Result<()> {
+        let archive_file = dest.archive_file().to_path_buf();
+        open_tar(reader, &archive_file, |r| Ok(flate2::read::GzDecoder::new(r)))
+            .and_then(|archive| extract_tar(archive, &archive_file, dest))
     }
 }
 
 impl ToteExtractor for Bz2Extractor {
-    fn list(&self, archive_file: PathBuf) -> Result<Entries> {
-        open_tar_file(&archive_file, bzip2::read::BzDecoder::new)
+    fn list_from(&self, reader: Box<dyn ReadSeek + '_>, archive_file: PathBuf) -> Result<Entries> {
+        open_tar(reader, &archive_file, |r| Ok(bzip2::read::BzDecoder::new(r)))
             .and_then(|archive| list_tar(archive, archive_file))
     }
 
-    fn perform(&self, archive_file: PathBuf, base: PathBuf) -> Result<()> {
-        open_tar_file(&archive_file, bzip2::read::BzDecoder::new)
-            .and_then(|archive| extract_tar(archive, base))
+    fn perform_from(&self, reader: Box<dyn ReadSeek + '_>, dest: &mut Destination) -> Result<()> {
+        self.perform_stream(reader, dest)
+    }
+
+    fn perform_stream(&self, reader: Box<dyn Read + '_>, dest: &mut Destination) -> Result<()> {
+        let archive_file = dest.archive_file().to_path_buf();
+        open_tar(reader, &archive_file, |r| Ok(bzip2::read::BzDecoder::new(r)))
+            .and_then(|archive| extract_tar(archive, &archive_file, dest))
     }
 }
 
 impl ToteExtractor for XzExtractor {
-    fn list(&self, archive_file: PathBuf) -> Result<Entries> {
-        open_tar_file(&archive_file, XzDecoder::new)
+    fn list_from(&self, reader: Box<dyn ReadSeek + '_>, archive_file: PathBuf) -> Result<Entries> {
+        open_tar(reader, &archive_file, |r| Ok(XzDecoder::new(r)))
             .and_then(|archive| list_tar(archive, archive_file))
     }
 
-    fn perform(&self, archive_file: PathBuf, base: PathBuf) -> Result<()> {
-        open_tar_file(&archive_file, XzDecoder::new)
-            .and_then(|archive| extract_tar(archive, base))
+    fn perform_from(&self, reader: Box<dyn ReadSeek + '_>, dest: &mut Destination) -> Result<()> {
+        self.perform_stream(reader, dest)
+    }
+
+    fn perform_stream(&self, reader: Box<dyn Read + '_>, dest: &mut Destination) -> Result<()> {
+        let archive_file = dest.archive_file().to_path_buf();
+        open_tar(reader, &archive_file, |r| Ok(XzDecoder::new(r)))
+            .and_then(|archive| extract_tar(archive, &archive_file, dest))
     }
 }
 
 impl ToteExtractor for ZstdExtractor {
-    fn list(&self, archive_file: PathBuf) -> Result<Entries> {
-        open_tar_file(&archive_file, |f| zstd::Decoder::new(f).unwrap())
+    fn list_from(&self, reader: Box<dyn ReadSeek + '_>, archive_file: PathBuf) -> Result<Entries> {
+        open_tar(reader, &archive_file, zstd::Decoder::new)
+            .and_then(|archive| list_tar(archive, archive_file))
+    }
+
+    fn perform_from(&self, reader: Box<dyn ReadSeek + '_>, dest: &mut Destination) -> Result<()> {
+        self.perform_stream(reader, dest)
+    }
+
+    fn perform_stream(&self, reader: Box<dyn Read + '_>, dest: &mut Destination) -> Result<()> {
+        let archive_file = dest.archive_file().to_path_buf();
+        open_tar(reader, &archive_file, zstd::Decoder::new)
+            .and_then(|archive| extract_tar(archive, &archive_file, dest))
+    }
+}
+
+impl ToteExtractor for Lz4Extractor {
+    fn list_from(&self, reader: Box<dyn ReadSeek + '_>, archive_file: PathBuf) -> Result<Entries> {
+        open_tar(reader, &archive_file, |r| Ok(lz4_flex::frame::FrameDecoder::new(r)))
+            .and_then(|archive| list_tar(archive, archive_file))
+    }
+
+    fn perform_from(&self, reader: Box<dyn ReadSeek + '_>, dest: &mut Destination) -> Result<()> {
+        self.perform_stream(reader, dest)
+    }
+
+    fn perform_stream(&self, reader: Box<dyn Read + '_>, dest: &mut Destination) -> Result<()> {
+        let archive_file = dest.archive_file().to_path_buf();
+        open_tar(reader, &archive_file, |r| Ok(lz4_flex::frame::FrameDecoder::new(r)))
+            .and_then(|archive| extract_tar(archive, &archive_file, dest))
+    }
+}
+
+impl ToteExtractor for LzmaExtractor {
+    fn list_from(&self, reader: Box<dyn ReadSeek + '_>, archive_file: PathBuf) -> Result<Entries> {
+        open_tar(reader, &archive_file, lzma_decoder)
+            .and_then(|archive| list_tar(archive, archive_file))
+    }
+
+    fn perform_from(&self, reader: Box<dyn ReadSeek + '_>, dest: &mut Destination) -> Result<()> {
+        self.perform_stream(reader, dest)
+    }
+
+    fn perform_stream(&self, reader: Box<dyn Read + '_>, dest: &mut Destination) -> Result<()> {
+        let archive_file = dest.archive_file().to_path_buf();
+        open_tar(reader, &archive_file, lzma_decoder)
+            .and_then(|archive| extract_tar(archive, &archive_file, dest))
+    }
+}
+
+impl ToteExtractor for LzExtractor {
+    fn list_from(&self, reader: Box<dyn ReadSeek + '_>, archive_file: PathBuf) -> Result<Entries> {
+        open_tar(reader, &archive_file, |r| Ok(LzipDecoder::new(r)))
+            .and_then(|archive| list_tar(archive, archive_file))
+    }
+
+    fn perform_from(&self, reader: Box<dyn ReadSeek + '_>, dest: &mut Destination) -> Result<()> {
+        self.perform_stream(reader, dest)
+    }
+
+    fn perform_stream(&self, reader: Box<dyn Read + '_>, dest: &mut Destination) -> Result<()> {
+        let archive_file = dest.archive_file().to_path_buf();
+        open_tar(reader, &archive_file, |r| Ok(LzipDecoder::new(r)))
+            .and_then(|archive| extract_tar(archive, &archive_file, dest))
+    }
+}
+
+impl ToteExtractor for ZExtractor {
+    fn list_from(&self, reader: Box<dyn ReadSeek + '_>, archive_file: PathBuf) -> Result<Entries> {
+        open_tar(reader, &archive_file, |r| Ok(LzwDecoder::new(r)))
+            .and_then(|archive| list_tar(archive, archive_file))
+    }
+
+    fn perform_from(&self, reader: Box<dyn ReadSeek + '_>, dest: &mut Destination) -> Result<()> {
+        self.perform_stream(reader, dest)
+    }
+
+    fn perform_stream(&self, reader: Box<dyn Read + '_>, dest: &mut Destination) -> Result<()> {
+        let archive_file = dest.archive_file().to_path_buf();
+        open_tar(reader, &archive_file, |r| Ok(LzwDecoder::new(r)))
+            .and_then(|archive| extract_tar(archive, &archive_file, dest))
+    }
+}
+
+impl ToteExtractor for BrExtractor {
+    fn list_from(&self, reader: Box<dyn ReadSeek + '_>, archive_file: PathBuf) -> Result<Entries> {
+        open_tar(reader, &archive_file, |r| Ok(brotli::Decompressor::new(r, 4096)))
             .and_then(|archive| list_tar(archive, archive_file))
     }
-    fn perform(&self, archive_file: PathBuf, base: PathBuf) -> Result<()> {
-        open_tar_file(&archive_file, |f| zstd::Decoder::new(f).unwrap())
-            .and_then(|archive| extract_tar(archive, base))
+
+    fn perform_from(&self, reader: Box<dyn ReadSeek + '_>, dest: &mut Destination) -> Result<()> {
+        self.perform_stream(reader, dest)
+    }
+
+    fn perform_stream(&self, reader: Box<dyn Read + '_>, dest: &mut Destination) -> Result<()> {
+        let archive_file = dest.archive_file().to_path_buf();
+        open_tar(reader, &archive_file, |r| Ok(brotli::Decompressor::new(r, 4096)))
+            .and_then(|archive| extract_tar(archive, &archive_file, dest))
     }
 }
 
-fn open_tar_file<F, R: Read>(file: &PathBuf, opener: F) -> Result<Archive<R>>
+fn lzma_decoder<R: Read>(reader: R) -> std::io::Result<XzDecoder<R>> {
+    let stream = xz2::stream::Stream::new_lzma_decoder(u64::MAX)?;
+    Ok(XzDecoder::new_stream(reader, stream))
+}
+
+fn open_tar<'a, F, R: Read>(reader: Box<dyn Read + 'a>, archive_file: &Path, opener: F) -> Result<Archive<R>>
 where
-    F: FnOnce(File) -> R,
+    F: FnOnce(Box<dyn Read + 'a>) -> std::io::Result<R>,
 {
-    let file = match File::open(file) {
-        Ok(f) => f,
-        Err(e) => return Err(Error::IO(e)),
+    let reader = opener(reader)
+        .map_err(|e| Error::corrupted(archive_file, None, e))?;
+    Ok(Archive::new(reader))
+}
+
+/// Opens the tar archive compressed in the given tar family format (e.g., `TarXz`),
+/// for the formats wrapping the tarballs (e.g., `.deb`).
+pub(super) fn open_tar_by_format<'a>(reader: Box<dyn Read + 'a>, archive_file: &Path, format_name: &str) -> Result<Archive<Box<dyn Read + 'a>>> {
+    let reader: Box<dyn Read + 'a> = match format_name {
+        "Tar" => reader,
+        "TarGz" => Box::new(flate2::read::GzDecoder::new(reader)),
+        "TarBz2" => Box::new(bzip2::read::BzDecoder::new(reader)),
+        "TarXz" => Box::new(XzDecoder::new(reader)),
+        "TarZstd" => Box::new(zstd::Decoder::new(reader)
+            .map_err(|e| Error::corrupted(archive_file, None, e))?),
+        "TarLz4" => Box::new(lz4_flex::frame::FrameDecoder::new(reader)),
+        "TarLzma" => Box::new(lzma_decoder(reader)
+            .map_err(|e| Error::corrupted(archive_file, None, e))?),
+        "TarLz" => Box::new(LzipDecoder::new(reader)),
+        "TarZ" => Box::new(LzwDecoder::new(reader)),
+        "TarBr" => Box::new(brotli::Decompressor::new(reader, 4096)),
+        _ => return Err(Error::UnsupportedFormat(format_name.to_string())),
     };
-    let writer = opener(file);
-    Ok(Archive::new(writer))
+    Ok(Archive::new(reader))
 }
 
-fn extract_tar<R: Read>(mut archive: tar::Archive<R>, base: PathBuf) -> Result<()> {
-    for entry in archive.entries().unwrap() {
-        let mut entry = entry.unwrap();
-        let path = entry.header().path().unwrap();
-        let p = path.clone().to_path_buf();
-        if is_filename_mac_finder_file(p.to_path_buf()) {
-            continue;
-        }
-        let size = entry.header().size().unwrap();
-        log::info!("extracting {path:?} ({size} bytes)");
+fn extract_tar<R: Read>(archive: tar::Archive<R>, archive_file: &Path, dest: &mut Destination) -> Result<()> {
+    extract_tar_with(archive, archive_file, dest, |name| Some(name.to_string()))
+}
 
-        let dest = base.join(&path);
-        if entry.header().entry_type().is_file() {
-            create_dir_all(dest.parent().unwrap()).unwrap();
-            entry.unpack(dest).unwrap();
+/// Extracts the tar archive with renaming each entry name by `rename`, and skips the entries renamed into `None`.
+pub(super) fn extract_tar_with<R: Read, F>(mut archive: tar::Archive<R>, archive_file: &Path, dest: &mut Destination, rename: F) -> Result<()>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let entries = archive.entries()
+        .map_err(|e| Error::corrupted(archive_file, None, e))?;
+    let mut errs = vec![];
+    let mut dirs = vec![];
+    for entry in entries {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                errs.push(Error::corrupted(archive_file, None, e));
+                break;
+            }
+        };
+        match extract_entry(entry, archive_file, dest, &rename, &mut dirs) {
+            Err(e @ Error::LimitExceeded(_)) => return Err(e),
+            Err(e) => errs.push(e),
+            Ok(_) => {}
+        }
+    }
+    // restore the directories after their entries, from the deepest one.
+    for (path, attrs) in dirs.iter().rev() {
+        if let Err(e) = dest.restore_dir(path, attrs) {
+            errs.push(e);
         }
     }
+    Error::error_or((), errs)
+}
+
+fn extract_entry<R: Read, F>(mut entry: tar::Entry<R>, archive_file: &Path, dest: &mut Destination, rename: &F, dirs: &mut Vec<(PathBuf, Attributes)>) -> Result<()>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let path = entry.path()
+        .map_err(|e| Error::corrupted(archive_file, None, e))?
+        .to_path_buf();
+    if is_filename_mac_finder_file(&path) {
+        return Ok(());
+    }
+    let size = entry.size();
+    log::info!("extracting {path:?} ({size} bytes)");
+
+    let entry_type = entry.header().entry_type();
+    if !entry_type.is_file() && !entry_type.is_dir() && !entry_type.is_symlink() && !entry_type.is_hard_link() {
+        return Ok(());
+    }
+    let Some(name) = rename(&path.to_string_lossy()) else {
+        return Ok(());
+    };
+    let Some(dest_path) = dest.path_of(&name)? else {
+        return Ok(());
+    };
+    if entry_type.is_dir() {
+        dest.create_dir(&dest_path)?;
+        dirs.push((dest_path, attributes(entry.header())));
+        return Ok(());
+    }
+    if entry_type.is_file() {
+        let attrs = attributes(entry.header());
+        return dest.write(&dest_path, &mut entry, &attrs).map(|_| ());
+    }
+    let Some(link_name) = entry.link_name()
+        .map_err(|e| Error::corrupted(archive_file, Some(&path.to_string_lossy()), e))?
+    else {
+        return Err(Error::corrupted(archive_file, Some(&path.to_string_lossy()), "no link name"));
+    };
+    let link_name = link_name.to_string_lossy().to_string();
+    if entry_type.is_symlink() {
+        dest.symlink(&dest_path, &link_name)?;
+    } else if let Some(original) = rename(&link_name)
+        && let Some(original) = dest.path_of(original)?
+    {
+        dest.hardlink(&dest_path, &original)?;
+    }
     Ok(())
 }
 
-fn is_filename_mac_finder_file(path: PathBuf) -> bool {
-    let filename = path.file_name().unwrap().to_str().unwrap();
-    filename == ".DS_Store" || filename.starts_with("._")
+fn attributes(header: &tar::Header) -> Attributes {
+    Attributes::builder()
+        .mode(header.mode().ok())
+        .mtime(header.mtime().ok().map(|t| t as i64))
+        .uid(header.uid().ok().map(|u| u as u32))
+        .gid(header.gid().ok().map(|g| g as u32))
+        .build()
+}
+
+fn is_filename_mac_finder_file(path: &Path) -> bool {
+    match path.file_name().map(|n| n.to_string_lossy()) {
+        Some(filename) => filename == ".DS_Store" || filename.starts_with("._"),
+        None => false,
+    }
 }
 
-fn list_tar<R: Read>(mut archive: tar::Archive<R>, path: PathBuf) -> Result<Entries> {
+fn list_tar<R: Read>(archive: tar::Archive<R>, path: PathBuf) -> Result<Entries> {
+    let result = tar_entries(archive, &path)?;
+    Ok(Entries::new(path, result))
+}
+
+/// Returns the entries in the tar archive.
+pub(super) fn tar_entries<R: Read>(mut archive: tar::Archive<R>, archive_file: &Path) -> Result<Vec<ToteEntry>> {
     let mut result = vec![];
-    for entry in archive.entries().unwrap() {
-        let entry = entry.unwrap();
-        result.push(tar_entry_to_entry(entry));
+    let entries = archive.entries()
+        .map_err(|e| Error::corrupted(archive_file, None, e))?;
+    for entry in entries {
+        let entry = entry.map_err(|e| Error::corrupted(archive_file, None, e))?;
+        result.push(tar_entry_to_entry(entry, archive_file)?);
     }
-    Ok(Entries::new(path, result))
+    Ok(result)
 }
 
-fn tar_entry_to_entry<R: Read>(e: tar::Entry<R>) -> ToteEntry {
+fn tar_entry_to_entry<R: Read>(e: tar::Entry<R>, archive_file: &Path) -> Result<ToteEntry> {
     let header = e.header();
-    let path = header.path().unwrap().to_str().unwrap().to_string();
-    let size = header.size();
-    let mode = header.mode().unwrap();
-    let mtime = header.mtime().unwrap();
-    let datetime = chrono::DateTime::from_timestamp_millis(mtime as i64);
-    ToteEntry::builder()
-        .name(path)
-        .original_size(size.unwrap())
-        .unix_mode(mode)
-        .date(datetime.map(|dt| dt.naive_local()))
-        .build()
+    let path = e.path()
+        .map_err(|e| Error::corrupted(archive_file, None, e))?
+        .to_string_lossy()
+        .to_string();
+    let size = e.size();
+    let mode = header.mode().ok();
+    let mtime = header.mtime().ok();
+    let datetime = mtime.and_then(|t| chrono::DateTime::from_timestamp(t as i64, 0));
+    Ok(ToteEntry::new(path, None, Some(size), mode, datetime.map(|dt| dt.naive_local())))
 }
 
 #[cfg(test)]
@@ -153,12 +385,12 @@ mod tests {
             Ok(r) => {
                 let r = r.iter().map(|e| e.name.clone()).collect::<Vec<_>>();
                 assert_eq!(r.len(), 16);
-                assert_eq!(r.get(0), Some("Cargo.toml".to_string()).as_ref());
+                assert_eq!(r.first(), Some("Cargo.toml".to_string()).as_ref());
                 assert_eq!(r.get(1), Some("build.rs".to_string()).as_ref());
                 assert_eq!(r.get(2), Some("LICENSE".to_string()).as_ref());
                 assert_eq!(r.get(3), Some("README.md".to_string()).as_ref());
             }
-            Err(_) => assert!(false),
+            Err(_) => panic!("unexpected result"),
         }
     }
 
@@ -170,12 +402,12 @@ mod tests {
             Ok(r) => {
                 let r = r.iter().map(|e| e.name.clone()).collect::<Vec<_>>();
                 assert_eq!(r.len(), 16);
-                assert_eq!(r.get(0), Some("Cargo.toml".to_string()).as_ref());
+                assert_eq!(r.first(), Some("Cargo.toml".to_string()).as_ref());
                 assert_eq!(r.get(1), Some("build.rs".to_string()).as_ref());
                 assert_eq!(r.get(2), Some("LICENSE".to_string()).as_ref());
                 assert_eq!(r.get(3), Some("README.md".to_string()).as_ref());
             }
-            Err(_) => assert!(false),
+            Err(_) => panic!("unexpected result"),
         }
     }
 
@@ -187,12 +419,12 @@ mod tests {
             Ok(r) => {
                 let r = r.iter().map(|e| e.name.clone()).collect::<Vec<_>>();
                 assert_eq!(r.len(), 16);
-                assert_eq!(r.get(0), Some("Cargo.toml".to_string()).as_ref());
+                assert_eq!(r.first(), Some("Cargo.toml".to_string()).as_ref());
                 assert_eq!(r.get(1), Some("build.rs".to_string()).as_ref());
                 assert_eq!(r.get(2), Some("LICENSE".to_string()).as_ref());
                 assert_eq!(r.get(3), Some("README.md".to_string()).as_ref());
             }
-            Err(_) => assert!(false),
+            Err(_) => panic!("unexpected result"),
         }
     }
 
@@ -204,12 +436,12 @@ mod tests {
             Ok(r) => {
                 let r = r.iter().map(|e| e.name.clone()).collect::<Vec<_>>();
                 assert_eq!(r.len(), 16);
-                assert_eq!(r.get(0), Some("Cargo.toml".to_string()).as_ref());
+                assert_eq!(r.first(), Some("Cargo.toml".to_string()).as_ref());
                 assert_eq!(r.get(1), Some("build.rs".to_string()).as_ref());
                 assert_eq!(r.get(2), Some("LICENSE".to_string()).as_ref());
                 assert_eq!(r.get(3), Some("README.md".to_string()).as_ref());
             }
-            Err(_) => assert!(false),
+            Err(_) => panic!("unexpected result"),
         }
     }
 
@@ -221,12 +453,97 @@ mod tests {
             Ok(r) => {
                 let r = r.iter().map(|e| e.name.clone()).collect::<Vec<_>>();
                 assert_eq!(r.len(), 16);
-                assert_eq!(r.get(0), Some("Cargo.toml".to_string()).as_ref());
+                assert_eq!(r.first(), Some("Cargo.toml".to_string()).as_ref());
+                assert_eq!(r.get(1), Some("build.rs".to_string()).as_ref());
+                assert_eq!(r.get(2), Some("LICENSE".to_string()).as_ref());
+                assert_eq!(r.get(3), Some("README.md".to_string()).as_ref());
+            }
+            Err(_) => panic!("unexpected result"),
+        }
+    }
+
+    #[test]
+    fn test_list_tarlz4_file() {
+        let file = PathBuf::from("../testdata/test.tar.lz4");
+        let extractor = Lz4Extractor {};
+        match extractor.list(file) {
+            Ok(r) => {
+                let r = r.iter().map(|e| e.name.clone()).collect::<Vec<_>>();
+                assert_eq!(r.len(), 16);
+                assert_eq!(r.first(), Some("Cargo.toml".to_string()).as_ref());
                 assert_eq!(r.get(1), Some("build.rs".to_string()).as_ref());
                 assert_eq!(r.get(2), Some("LICENSE".to_string()).as_ref());
                 assert_eq!(r.get(3), Some("README.md".to_string()).as_ref());
             }
-            Err(_) => assert!(false),
+            Err(_) => panic!("unexpected result"),
+        }
+    }
+
+    #[test]
+    fn test_list_tarbr_file() {
+        let file = PathBuf::from("../testdata/test.tar.br");
+        let extractor = BrExtractor {};
+        match extractor.list(file) {
+            Ok(r) => {
+                let r = r.iter().map(|e| e.name.clone()).collect::<Vec<_>>();
+                assert_eq!(r.len(), 16);
+                assert_eq!(r.first(), Some("Cargo.toml".to_string()).as_ref());
+                assert_eq!(r.get(1), Some("build.rs".to_string()).as_ref());
+                assert_eq!(r.get(2), Some("LICENSE".to_string()).as_ref());
+                assert_eq!(r.get(3), Some("README.md".to_string()).as_ref());
+            }
+            Err(_) => panic!("unexpected result"),
+        }
+    }
+
+    #[test]
+    fn test_list_tarlzma_file() {
+        let file = PathBuf::from("../testdata/test.tar.lzma");
+        let extractor = LzmaExtractor {};
+        match extractor.list(file) {
+            Ok(r) => {
+                let r = r.iter().map(|e| e.name.clone()).collect::<Vec<_>>();
+                assert_eq!(r.len(), 16);
+                assert_eq!(r.first(), Some("Cargo.toml".to_string()).as_ref());
+                assert_eq!(r.get(1), Some("build.rs".to_string()).as_ref());
+                assert_eq!(r.get(2), Some("LICENSE".to_string()).as_ref());
+                assert_eq!(r.get(3), Some("README.md".to_string()).as_ref());
+            }
+            Err(_) => panic!("unexpected result"),
+        }
+    }
+
+    #[test]
+    fn test_list_tarlz_file() {
+        let file = PathBuf::from("../testdata/test.tar.lz");
+        let extractor = LzExtractor {};
+        match extractor.list(file) {
+            Ok(r) => {
+                let r = r.iter().map(|e| e.name.clone()).collect::<Vec<_>>();
+                assert_eq!(r.len(), 16);
+                assert_eq!(r.first(), Some("Cargo.toml".to_string()).as_ref());
+                assert_eq!(r.get(1), Some("build.rs".to_string()).as_ref());
+                assert_eq!(r.get(2), Some("LICENSE".to_string()).as_ref());
+                assert_eq!(r.get(3), Some("README.md".to_string()).as_ref());
+            }
+            Err(_) => panic!("unexpected result"),
+        }
+    }
+
+    #[test]
+    fn test_list_tarz_file() {
+        let file = PathBuf::from("../testdata/test.tar.Z");
+        let extractor = ZExtractor {};
+        match extractor.list(file) {
+            Ok(r) => {
+                let r = r.iter().map(|e| e.name.clone()).collect::<Vec<_>>();
+                assert_eq!(r.len(), 16);
+                assert_eq!(r.first(), Some("Cargo.toml".to_string()).as_ref());
+                assert_eq!(r.get(1), Some("build.rs".to_string()).as_ref());
+                assert_eq!(r.get(2), Some("LICENSE".to_string()).as_ref());
+                assert_eq!(r.get(3), Some("README.md".to_string()).as_ref());
+            }
+            Err(_) => panic!("unexpected result"),
         }
     }
 
@@ -236,11 +553,10 @@ mod tests {
         let opts = crate::ExtractConfig::builder().dest("results/tar").build();
         match crate::extract(archive_file, &opts) {
             Ok(_) => {
-                assert!(true);
                 assert!(PathBuf::from("results/tar/Cargo.toml").exists());
                 std::fs::remove_dir_all(PathBuf::from("results/tar")).unwrap();
             }
-            Err(_) => assert!(false),
+            Err(_) => panic!("unexpected result"),
         };
     }
 
@@ -250,11 +566,10 @@ mod tests {
         let opts = crate::ExtractConfig::builder().dest("results/targz").build();
         match crate::extract(archive_file, &opts) {
             Ok(_) => {
-                assert!(true);
                 assert!(PathBuf::from("results/targz/Cargo.toml").exists());
                 std::fs::remove_dir_all(PathBuf::from("results/targz")).unwrap();
             }
-            Err(_) => assert!(false),
+            Err(_) => panic!("unexpected result"),
         };
     }
 
@@ -264,11 +579,10 @@ mod tests {
         let opts = crate::ExtractConfig::builder().dest("results/tarbz2").build();
         match crate::extract(archive_file, &opts) {
             Ok(_) => {
-                assert!(true);
                 assert!(PathBuf::from("results/tarbz2/Cargo.toml").exists());
                 std::fs::remove_dir_all(PathBuf::from("results/tarbz2")).unwrap();
             }
-            Err(_) => assert!(false),
+            Err(_) => panic!("unexpected result"),
         };
     }
 
@@ -278,11 +592,10 @@ mod tests {
         let opts = crate::ExtractConfig::builder().dest("results/tarxz").build();
         match crate::extract(archive_file, &opts) {
             Ok(_) => {
-                assert!(true);
                 assert!(PathBuf::from("results/tarxz/Cargo.toml").exists());
                 std::fs::remove_dir_all(PathBuf::from("results/tarxz")).unwrap();
             }
-            Err(_) => assert!(false),
+            Err(_) => panic!("unexpected result"),
         };
     }
 
@@ -292,11 +605,240 @@ mod tests {
         let opts = crate::ExtractConfig::builder().dest("results/tarzstd").build();
         match crate::extract(archive_file, &opts) {
             Ok(_) => {
-                assert!(true);
                 assert!(PathBuf::from("results/tarzstd/Cargo.toml").exists());
                 std::fs::remove_dir_all(PathBuf::from("results/tarzstd")).unwrap();
             }
-            Err(_) => assert!(false),
+            Err(_) => panic!("unexpected result"),
+        };
+    }
+
+    #[test]
+    fn test_extract_tarlz4_archive() {
+        let archive_file = PathBuf::from("../testdata/test.tar.lz4");
+        let opts = crate::ExtractConfig::builder().dest("results/tarlz4").build();
+        match crate::extract(archive_file, &opts) {
+            Ok(_) => {
+                assert!(PathBuf::from("results/tarlz4/Cargo.toml").exists());
+                std::fs::remove_dir_all(PathBuf::from("results/tarlz4")).unwrap();
+            }
+            Err(_) => panic!("unexpected result"),
+        };
+    }
+
+    #[test]
+    fn test_extract_tarbr_archive() {
+        let archive_file = PathBuf::from("../testdata/test.tar.br");
+        let opts = crate::ExtractConfig::builder().dest("results/tarbr").build();
+        match crate::extract(archive_file, &opts) {
+            Ok(_) => {
+                assert!(PathBuf::from("results/tarbr/Cargo.toml").exists());
+                std::fs::remove_dir_all(PathBuf::from("results/tarbr")).unwrap();
+            }
+            Err(_) => panic!("unexpected result"),
         };
     }
+
+    #[test]
+    fn test_extract_tarlzma_archive() {
+        let archive_file = PathBuf::from("../testdata/test.tar.lzma");
+        let opts = crate::ExtractConfig::builder().dest("results/tarlzma").build();
+        match crate::extract(archive_file, &opts) {
+            Ok(_) => {
+                assert!(PathBuf::from("results/tarlzma/Cargo.toml").exists());
+                std::fs::remove_dir_all(PathBuf::from("results/tarlzma")).unwrap();
+            }
+            Err(_) => panic!("unexpected result"),
+        };
+    }
+
+    #[test]
+    fn test_extract_tarlz_archive() {
+        let archive_file = PathBuf::from("../testdata/test.tar.lz");
+        let opts = crate::ExtractConfig::builder().dest("results/tarlz").build();
+        match crate::extract(archive_file, &opts) {
+            Ok(_) => {
+                assert!(PathBuf::from("results/tarlz/Cargo.toml").exists());
+                std::fs::remove_dir_all(PathBuf::from("results/tarlz")).unwrap();
+            }
+            Err(_) => panic!("unexpected result"),
+        };
+    }
+
+    #[test]
+    fn test_extract_tarz_archive() {
+        let archive_file = PathBuf::from("../testdata/test.tar.Z");
+        let opts = crate::ExtractConfig::builder().dest("results/tarz").build();
+        match crate::extract(archive_file, &opts) {
+            Ok(_) => {
+                assert!(PathBuf::from("results/tarz/Cargo.toml").exists());
+                std::fs::remove_dir_all(PathBuf::from("results/tarz")).unwrap();
+            }
+            Err(_) => panic!("unexpected result"),
+        };
+    }
+
+    #[test]
+    fn test_extract_traversal_archive() {
+        let archive_file = PathBuf::from("../testdata/traversal.tar");
+        let opts = crate::ExtractConfig::builder().dest("results/tar_traversal").build();
+        match crate::extract(&archive_file, &opts) {
+            Err(Error::Array(errs)) => {
+                assert_eq!(errs.len(), 2);
+                assert!(matches!(&errs[0], Error::UnsafePath(name) if name == "../escaped.txt"));
+                assert!(matches!(&errs[1], Error::UnsafePath(name) if name == "/absolute.txt"));
+            }
+            r => panic!("unexpected result: {:?}", r),
+        };
+        assert!(!PathBuf::from("results/escaped.txt").exists());
+        assert!(PathBuf::from("results/tar_traversal/safe.txt").exists());
+
+        let opts = crate::ExtractConfig::builder()
+            .dest("results/tar_traversal")
+            .overwrite(true)
+            .unsafe_path(crate::UnsafePathPolicy::Strip)
+            .build();
+        match crate::extract(&archive_file, &opts) {
+            Ok(_) => {
+                assert!(PathBuf::from("results/tar_traversal/safe.txt").exists());
+                assert!(PathBuf::from("results/tar_traversal/escaped.txt").exists());
+                assert!(PathBuf::from("results/tar_traversal/absolute.txt").exists());
+                std::fs::remove_dir_all(PathBuf::from("results/tar_traversal")).unwrap();
+            }
+            Err(e) => panic!("unexpected error: {:?}", e),
+        };
+    }
+
+    #[test]
+    fn test_extract_bomb_archive() {
+        let archive_file = PathBuf::from("../testdata/bomb.tar.zst");
+        let opts = crate::ExtractConfig::builder()
+            .dest("results/tar_bomb")
+            .max_ratio(100.0)
+            .build();
+        match crate::extract(&archive_file, &opts) {
+            Err(Error::LimitExceeded(m)) => assert_eq!(m, "zeros.bin: the compression ratio exceeds 100"),
+            r => panic!("unexpected result: {:?}", r),
+        };
+        assert!(!PathBuf::from("results/tar_bomb").exists());
+
+        let opts = crate::ExtractConfig::builder()
+            .dest("results/tar_bomb")
+            .max_total_size(1024 * 1024)
+            .build();
+        match crate::extract(&archive_file, &opts) {
+            Err(Error::LimitExceeded(m)) => assert_eq!(m, "zeros.bin: the total size exceeds 1048576 bytes"),
+            r => panic!("unexpected result: {:?}", r),
+        };
+        assert!(!PathBuf::from("results/tar_bomb").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_extract_restores_attributes() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let archive_file = PathBuf::from("../testdata/test.tar");
+        let opts = crate::ExtractConfig::builder()
+            .dest("results/tar_attrs")
+            .build();
+        crate::extract(&archive_file, &opts).unwrap();
+        let metadata = std::fs::metadata("results/tar_attrs/Cargo.toml").unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o7777, 0o600);
+        let mtime = metadata.modified().unwrap()
+            .duration_since(std::time::SystemTime::UNIX_EPOCH).unwrap();
+        assert_eq!(mtime.as_secs(), 1714435801);
+        std::fs::remove_dir_all("results/tar_attrs").unwrap();
+
+        let opts = crate::ExtractConfig::builder()
+            .dest("results/tar_attrs")
+            .preserve_mtime(false)
+            .preserve_permissions(false)
+            .build();
+        crate::extract(&archive_file, &opts).unwrap();
+        let metadata = std::fs::metadata("results/tar_attrs/Cargo.toml").unwrap();
+        let mtime = metadata.modified().unwrap()
+            .duration_since(std::time::SystemTime::UNIX_EPOCH).unwrap();
+        assert!(mtime.as_secs() > 1714435801);
+        std::fs::remove_dir_all("results/tar_attrs").unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_extract_links() {
+        let archive_file = PathBuf::from("../testdata/links.tar");
+        let opts = crate::ExtractConfig::builder()
+            .dest("results/tar_links")
+            .build();
+        crate::extract(&archive_file, &opts).unwrap();
+        let base = PathBuf::from("results/tar_links");
+        assert_eq!(std::fs::read_link(base.join("lib/libfoo.so")).unwrap(), PathBuf::from("libfoo.so.1"));
+        assert_eq!(std::fs::read_link(base.join("lib/libfoo.so.1")).unwrap(), PathBuf::from("libfoo.so.1.2"));
+        assert_eq!(std::fs::read_to_string(base.join("lib/libfoo.so")).unwrap(), "libfoo\n");
+        assert_eq!(std::fs::read_to_string(base.join("hard.txt")).unwrap(), "libfoo\n");
+        {
+            use std::os::unix::fs::MetadataExt;
+            assert_eq!(base.join("hard.txt").metadata().unwrap().ino(), base.join("lib/libfoo.so.1.2").metadata().unwrap().ino());
+        }
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_extract_escaping_links() {
+        let archive_file = PathBuf::from("../testdata/link_escape.tar");
+        let opts = crate::ExtractConfig::builder()
+            .dest("results/tar_link_escape")
+            .build();
+        match crate::extract(&archive_file, &opts) {
+            Err(Error::Array(errs)) => {
+                assert_eq!(errs.len(), 2);
+                assert!(matches!(&errs[0], Error::UnsafePath(m) if m == "passwd -> ../../etc/passwd"));
+                assert!(matches!(&errs[1], Error::UnsafePath(m) if m == "root -> /"));
+            }
+            r => panic!("unexpected result: {:?}", r),
+        }
+        let _ = std::fs::remove_dir_all("results/tar_link_escape");
+
+        let opts = crate::ExtractConfig::builder()
+            .dest("results/tar_link_escape")
+            .unsafe_path(crate::UnsafePathPolicy::Skip)
+            .build();
+        crate::extract(&archive_file, &opts).unwrap();
+        let base = PathBuf::from("results/tar_link_escape");
+        assert!(base.join("safe.txt").exists());
+        assert!(base.join("passwd").symlink_metadata().is_err());
+        assert!(base.join("root").symlink_metadata().is_err());
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_extract_directories() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mut builder = tar::Builder::new(vec![]);
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Directory);
+        header.set_mode(0o750);
+        header.set_mtime(1_000_000_000);
+        header.set_size(0);
+        builder.append_data(&mut header, "dir/", std::io::empty()).unwrap();
+        let mut header = tar::Header::new_gnu();
+        header.set_mode(0o644);
+        header.set_size(4);
+        builder.append_data(&mut header, "dir/file.txt", "hoge".as_bytes()).unwrap();
+        let data = builder.into_inner().unwrap();
+
+        let base = PathBuf::from("results/tar_dirs");
+        let config = crate::ExtractConfig::builder().build();
+        let mut dest = Destination::new(&base, &config);
+        extract_tar(tar::Archive::new(data.as_slice()), Path::new("dirs.tar"), &mut dest).unwrap();
+        let metadata = base.join("dir").metadata().unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o7777, 0o750);
+        let mtime = metadata.modified().unwrap()
+            .duration_since(std::time::SystemTime::UNIX_EPOCH).unwrap();
+        assert_eq!(mtime.as_secs(), 1_000_000_000);
+        assert_eq!(std::fs::read_to_string(base.join("dir/file.txt")).unwrap(), "hoge");
+        std::fs::remove_dir_all(&base).unwrap();
+    }
 }
@@ -0,0 +1,202 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use regex::{Regex, RegexBuilder};
+
+use crate::{Error, Result};
+
+/// A sed-style substitution (`s/REGEX/REPLACEMENT/FLAGS`) for rewriting the entry names on extraction.
+///
+/// Any character can be the delimiter instead of `/`, and the escaped delimiter (e.g., `\/`) is a literal.
+/// `\1` to `\9` and `&` in the replacement refer to the captured groups and the whole match, respectively.
+/// The available flags are `g` (replace all matches) and `i` (ignore case).
+///
+/// # Example
+///
+/// ```
+/// use totebag::extractor::Transform;
+///
+/// let t = Transform::new("s,^project-[0-9.]*/,src/,").unwrap();
+/// assert_eq!(t.apply("project-1.2.3/lib/main.rs"), "src/lib/main.rs");
+///
+/// let t = Transform::new(r"s/\(.*\)\.txt$/\1.md/").unwrap();
+/// assert_eq!(t.apply("docs/readme.txt"), "docs/readme.md");
+/// ```
+#[derive(Debug, Clone)]
+pub struct Transform {
+    expression: String,
+    regex: Regex,
+    replacement: String,
+    global: bool,
+}
+
+impl Transform {
+    /// Parses the given sed-style substitution expression.
+    pub fn new<S: AsRef<str>>(expression: S) -> Result<Self> {
+        let expression = expression.as_ref();
+        let invalid = |message: &str| Error::Extractor(format!("{expression}: invalid transform ({message})"));
+        let mut chars = expression.chars();
+        if chars.next() != Some('s') {
+            return Err(invalid("must start with 's'"));
+        }
+        let Some(delim) = chars.next() else {
+            return Err(invalid("no delimiter"));
+        };
+        let parts = split_by(chars.as_str(), delim);
+        let [pattern, replacement, flags] = parts.as_slice() else {
+            return Err(invalid("must be s/REGEX/REPLACEMENT/FLAGS"));
+        };
+        let mut global = false;
+        let mut ignore_case = false;
+        for flag in flags.chars() {
+            match flag {
+                'g' => global = true,
+                'i' => ignore_case = true,
+                _ => return Err(invalid(&format!("unknown flag '{flag}'"))),
+            }
+        }
+        let regex = RegexBuilder::new(&to_regex(pattern))
+            .case_insensitive(ignore_case)
+            .build()
+            .map_err(|e| invalid(&e.to_string()))?;
+        Ok(Self {
+            expression: expression.to_string(),
+            regex,
+            replacement: to_replacement(replacement),
+            global,
+        })
+    }
+
+    /// Applies the substitution to the given entry name.
+    pub fn apply(&self, name: &str) -> String {
+        if self.global {
+            self.regex.replace_all(name, self.replacement.as_str()).to_string()
+        } else {
+            self.regex.replace(name, self.replacement.as_str()).to_string()
+        }
+    }
+}
+
+impl FromStr for Transform {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Transform::new(s)
+    }
+}
+
+impl Display for Transform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.expression.fmt(f)
+    }
+}
+
+/// Splits the given string by the unescaped delimiter, and unescapes the escaped delimiters.
+fn split_by(s: &str, delim: char) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        let current = parts.last_mut().unwrap();
+        match c {
+            '\\' => match chars.next() {
+                Some(next) if next == delim => current.push(next),
+                Some(next) => {
+                    current.push('\\');
+                    current.push(next);
+                }
+                None => current.push('\\'),
+            },
+            c if c == delim => parts.push(String::new()),
+            c => current.push(c),
+        }
+    }
+    parts
+}
+
+/// Converts the basic regular expression of sed (`\(`, `\)`, `\{`, `\}`, `\|`, and `\+`)
+/// into the syntax of the regex crate.
+fn to_regex(pattern: &str) -> String {
+    let mut result = String::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(next @ ('(' | ')' | '{' | '}' | '|' | '+' | '?')) => result.push(next),
+                Some(next) => {
+                    result.push('\\');
+                    result.push(next);
+                }
+                None => result.push_str("\\\\"),
+            },
+            '(' | ')' | '{' | '}' | '|' | '+' | '?' => {
+                result.push('\\');
+                result.push(c);
+            }
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+/// Converts the replacement of sed (`\N`, `&`) into the syntax of the regex crate (`${N}`).
+fn to_replacement(replacement: &str) -> String {
+    let mut result = String::new();
+    let mut chars = replacement.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(n @ '0'..='9') => result.push_str(&format!("${{{n}}}")),
+                Some('$') => result.push_str("$$"),
+                Some(next) => result.push(next),
+                None => result.push('\\'),
+            },
+            '&' => result.push_str("${0}"),
+            '$' => result.push_str("$$"),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transform() {
+        let t = Transform::new("s/foo/bar/").unwrap();
+        assert_eq!(t.apply("foo/foo.txt"), "bar/foo.txt");
+
+        let t = Transform::new("s/foo/bar/g").unwrap();
+        assert_eq!(t.apply("foo/foo.txt"), "bar/bar.txt");
+
+        let t = Transform::new("s/FOO/bar/gi").unwrap();
+        assert_eq!(t.apply("foo/Foo.txt"), "bar/bar.txt");
+
+        let t = Transform::new(r"s/^src\//lib\//").unwrap();
+        assert_eq!(t.apply("src/main.rs"), "lib/main.rs");
+
+        let t = Transform::new(r"s|\([a-z]*\)\.rs$|\1/mod.rs [&] $1|").unwrap();
+        assert_eq!(t.apply("src/cli.rs"), "src/cli/mod.rs [cli.rs] $1");
+
+        let t = Transform::new("s/(v1)/v2/").unwrap();
+        assert_eq!(t.apply("doc(v1).txt"), "docv2.txt");
+    }
+
+    #[test]
+    fn test_invalid_transform() {
+        for (expression, message) in [
+            ("y/a/b/", "must start with 's'"),
+            ("s", "no delimiter"),
+            ("s/a/b", "must be s/REGEX/REPLACEMENT/FLAGS"),
+            ("s/a/b/c/", "must be s/REGEX/REPLACEMENT/FLAGS"),
+            ("s/a/b/x", "unknown flag 'x'"),
+        ] {
+            match Transform::new(expression) {
+                Err(Error::Extractor(m)) => assert_eq!(m, format!("{expression}: invalid transform ({message})")),
+                r => panic!("{expression}: unexpected result {r:?}"),
+            }
+        }
+        assert!(Transform::new(r"s/\(a/b/").is_err());
+    }
+}
@@ -1,12 +1,16 @@
-use std::fs::{File, create_dir_all};
-use std::io::copy;
-use std::path::PathBuf;
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
 
 use chrono::NaiveDateTime;
+use zip::ZipArchive;
+use zip::ExtraField;
 use zip::read::ZipFile;
 
-use crate::Result;
-use crate::extractor::{Entry, Entries, ToteExtractor};
+use crate::{Error, Result};
+use crate::extractor::destination::local_timestamp;
+use crate::extractor::{Attributes, Destination, Entry, Entries, ReadSeek, ToteExtractor};
+
+mod stream;
 
 /// ZIP format extractor implementation.
 ///
@@ -14,33 +18,55 @@ use crate::extractor::{Entry, Entries, ToteExtractor};
 pub(super) struct Extractor {}
 
 impl ToteExtractor for Extractor {
-    fn list(&self, archive_file: PathBuf) -> Result<Entries> {
-        let zip_file = File::open(&archive_file).unwrap();
-        let mut zip = zip::ZipArchive::new(zip_file).unwrap();
-
+    fn list_from(&self, reader: Box<dyn ReadSeek + '_>, archive_file: PathBuf) -> Result<Entries> {
+        let mut zip = open_zip(reader, &archive_file)?;
         let mut result = vec![];
         for i in 0..zip.len() {
-            let file = zip.by_index(i).unwrap();
+            let file = zip.by_index(i)
+                .map_err(|e| Error::corrupted(&archive_file, None, e))?;
             result.push(convert(file));
         }
         Ok(Entries::new(archive_file, result))
     }
 
-    fn perform(&self, archive_file: PathBuf, base: PathBuf) -> Result<()> {
-        let zip_file = File::open(archive_file).unwrap();
-        let mut zip = zip::ZipArchive::new(zip_file).unwrap();
+    fn perform_from(&self, reader: Box<dyn ReadSeek + '_>, dest: &mut Destination) -> Result<()> {
+        let archive_file = dest.archive_file().to_path_buf();
+        let mut zip = open_zip(reader, &archive_file)?;
         for i in 0..zip.len() {
-            let mut file = zip.by_index(i).unwrap();
+            let mut file = zip.by_index(i)
+                .map_err(|e| Error::corrupted(&archive_file, None, e))?;
             if file.is_file() {
+                let Some(dest_path) = dest.path_of(file.name())? else {
+                    continue;
+                };
                 log::info!("extracting {} ({} bytes)", file.name(), file.size());
-                let dest = base.join(file.name());
-                create_dir_all(dest.parent().unwrap()).unwrap();
-                let mut out = File::create(dest).unwrap();
-                copy(&mut file, &mut out).unwrap();
+                let attrs = Attributes::builder()
+                    .mode(file.unix_mode())
+                    .mtime(mtime_of(&file))
+                    .build();
+                dest.write(&dest_path, &mut file, &attrs)?;
+            } else if file.is_symlink() {
+                let Some(dest_path) = dest.path_of(file.name())? else {
+                    continue;
+                };
+                let mut target = String::new();
+                file.read_to_string(&mut target)
+                    .map_err(|e| Error::corrupted(&archive_file, Some(file.name()), e))?;
+                dest.symlink(&dest_path, &target)?;
             }
         }
         Ok(())
     }
+
+    fn perform_stream(&self, reader: Box<dyn Read + '_>, dest: &mut Destination) -> Result<()> {
+        let archive_file = dest.archive_file().to_path_buf();
+        stream::extract(reader, &archive_file, dest)
+    }
+}
+
+fn open_zip<R: Read + Seek>(reader: R, archive_file: &Path) -> Result<ZipArchive<R>> {
+    ZipArchive::new(reader)
+        .map_err(|e| Error::corrupted(archive_file, None, e))
 }
 
 fn convert<R: std::io::Read>(zfile: ZipFile<R>) -> Entry {
@@ -52,13 +78,21 @@ fn convert<R: std::io::Read>(zfile: ZipFile<R>) -> Entry {
         Some(t) => convert_to_datetime(t),
         None => None,
     };
-    Entry::builder()
-        .name(name)
-        .compressed_size(compressed_size)
-        .original_size(uncompresseed_size)
-        .unix_mode(mode.unwrap())
-        .date(mtime)
-        .build()
+    Entry::new(name, Some(compressed_size), Some(uncompresseed_size), mode, mtime)
+}
+
+/// Returns the modified time from the extended timestamp field, or from the MS-DOS time stamp in local time.
+fn mtime_of<R: std::io::Read>(zfile: &ZipFile<R>) -> Option<i64> {
+    let extended = zfile.extra_data_fields().find_map(|f| match f {
+        ExtraField::ExtendedTimestamp(t) => t.mod_time(),
+        _ => None,
+    });
+    match extended {
+        Some(t) => Some(t as i64),
+        None => zfile.last_modified()
+            .and_then(convert_to_datetime)
+            .and_then(|dt| local_timestamp(&dt)),
+    }
 }
 
 fn convert_to_datetime(t: zip::DateTime) -> Option<NaiveDateTime> {
@@ -71,8 +105,7 @@ fn convert_to_datetime(t: zip::DateTime) -> Option<NaiveDateTime> {
     let minute = t.minute() as u32;
     let second = t.second() as u32;
     NaiveDate::from_ymd_opt(year, month, day)
-        .unwrap()
-        .and_hms_opt(hour, minute, second)
+        .and_then(|d| d.and_hms_opt(hour, minute, second))
 }
 
 #[cfg(test)]
@@ -105,7 +138,7 @@ mod tests {
                     Some("README.md".to_string()).as_ref()
                 );
             }
-            Err(_) => assert!(false),
+            Err(_) => panic!("unexpected result"),
         }
     }
 
@@ -115,11 +148,29 @@ mod tests {
         let opts = crate::ExtractConfig::builder().dest("results/zip").build();
         match crate::extract(archive_file, &opts) {
             Ok(_) => {
-                assert!(true);
                 assert!(PathBuf::from("results/zip/Cargo.toml").exists());
                 std::fs::remove_dir_all(PathBuf::from("results/zip")).unwrap();
             }
-            Err(_) => assert!(false),
+            Err(_) => panic!("unexpected result"),
+        };
+    }
+
+    #[test]
+    fn test_extract_traversal_archive() {
+        let archive_file = PathBuf::from("../testdata/traversal.zip");
+        let opts = crate::ExtractConfig::builder()
+            .dest("results/zip_traversal")
+            .unsafe_path(crate::UnsafePathPolicy::Skip)
+            .build();
+        match crate::extract(archive_file, &opts) {
+            Ok(_) => {
+                assert!(PathBuf::from("results/zip_traversal/safe.txt").exists());
+                assert!(!PathBuf::from("results/zip_traversal/escaped.txt").exists());
+                assert!(!PathBuf::from("results/escaped.txt").exists());
+                assert!(!PathBuf::from("results/zip_traversal/absolute.txt").exists());
+                std::fs::remove_dir_all(PathBuf::from("results/zip_traversal")).unwrap();
+            }
+            Err(e) => panic!("unexpected error: {:?}", e),
         };
     }
 }
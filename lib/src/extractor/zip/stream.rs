@@ -0,0 +1,492 @@
+use std::io::{BufRead, Read};
+use std::path::Path;
+
+use chrono::NaiveDate;
+use crc::{CRC_32_ISO_HDLC, Crc};
+
+use crate::extractor::destination::local_timestamp;
+use crate::extractor::{Attributes, Destination};
+use crate::{Error, Result};
+
+const LOCAL_FILE_HEADER: u32 = 0x04034b50;
+const CENTRAL_DIRECTORY_HEADER: u32 = 0x02014b50;
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x06054b50;
+const DATA_DESCRIPTOR: u32 = 0x08074b50;
+
+const FLAG_ENCRYPTED: u16 = 0x0001;
+const FLAG_DATA_DESCRIPTOR: u16 = 0x0008;
+
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+const BZIP2: u16 = 12;
+const ZSTD: u16 = 93;
+
+const CRC32: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+
+/// The local file header of the zip entry.
+struct LocalHeader {
+    name: String,
+    flags: u16,
+    method: u16,
+    crc32: u32,
+    compressed_size: u64,
+    mtime: Option<i64>,
+    zip64: bool,
+}
+
+impl LocalHeader {
+    fn has_data_descriptor(&self) -> bool {
+        self.flags & FLAG_DATA_DESCRIPTOR != 0
+    }
+}
+
+/// Extracts the zip archive by reading the local file headers in order, without the central directory.
+///
+/// The entries whose sizes are written after the data (data descriptors) are supported for the stored,
+/// the deflated, the bzip2, and the zstd entries. Since the local file headers have no unix permissions, the symbolic links
+/// are extracted as the regular files containing the link targets.
+pub(super) fn extract<R: Read>(reader: R, archive_file: &Path, dest: &mut Destination) -> Result<()> {
+    let mut reader = PeekReader::new(reader);
+    let corrupted = |e: std::io::Error| Error::corrupted(archive_file, None, e);
+    let mut errs = vec![];
+    loop {
+        match read_u32(&mut reader) {
+            Ok(LOCAL_FILE_HEADER) => {}
+            Ok(CENTRAL_DIRECTORY_HEADER | END_OF_CENTRAL_DIRECTORY) => break,
+            Ok(sig) => {
+                errs.push(Error::corrupted(archive_file, None, format!("unknown signature: {sig:#010x}")));
+                break;
+            }
+            Err(e) => {
+                errs.push(corrupted(e));
+                break;
+            }
+        }
+        let header = match read_local_header(&mut reader) {
+            Ok(header) => header,
+            Err(e) => {
+                errs.push(corrupted(e));
+                break;
+            }
+        };
+        match extract_entry(&mut reader, &header, archive_file, dest) {
+            Err(e @ Error::LimitExceeded(_)) => return Err(e),
+            Err(e) => errs.push(e),
+            Ok(_) => {}
+        }
+    }
+    Error::error_or((), errs)
+}
+
+/// Extracts the data of the entry, and reads the stream up to the next header even if the entry is skipped.
+/// The encrypted entries and the entries rejected by [`Destination::path_of`] are reported as the errors
+/// after their data are read through.
+fn extract_entry<R: Read>(reader: &mut PeekReader<R>, header: &LocalHeader, archive_file: &Path, dest: &mut Destination) -> Result<()> {
+    let name = header.name.as_str();
+    let encrypted = header.flags & FLAG_ENCRYPTED != 0;
+    let (dest_path, skipped) = if encrypted {
+        (None, Some(Error::UnsupportedFormat(format!("{name}: encrypted entry in the stream"))))
+    } else if name.ends_with('/') {
+        (None, None)
+    } else {
+        match dest.path_of(name) {
+            Ok(path) => (path, None),
+            Err(e) => (None, Some(e)),
+        }
+    };
+    read_entry(reader, header, archive_file, dest, dest_path.as_deref(), encrypted)?;
+    match skipped {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// Reads the data of the entry up to the next header, and writes it into `dest_path` if given.
+/// The encrypted data is read through without decoding.
+fn read_entry<R: Read>(reader: &mut PeekReader<R>, header: &LocalHeader, archive_file: &Path, dest: &mut Destination, dest_path: Option<&Path>, encrypted: bool) -> Result<()> {
+    let name = header.name.as_str();
+    let corrupted = |e: std::io::Error| Error::corrupted(archive_file, Some(name), e);
+    let attrs = Attributes::builder().mtime(header.mtime).build();
+    let mut write = |data: &mut dyn Read| -> Result<()> {
+        if let Some(path) = dest_path {
+            log::info!("extracting {name}");
+            dest.write(path, data, &attrs)?;
+        }
+        std::io::copy(data, &mut std::io::sink()).map_err(corrupted)?;
+        Ok(())
+    };
+    if !header.has_data_descriptor() {
+        if encrypted {
+            std::io::copy(&mut (&mut *reader).take(header.compressed_size), &mut std::io::sink()).map_err(corrupted)?;
+            return Ok(());
+        }
+        let data = decoder((&mut *reader).take(header.compressed_size), header.method, name)?;
+        let mut data = Crc32Reader::new(data);
+        write(&mut data)?;
+        if data.finish() != header.crc32 {
+            return Err(Error::corrupted(archive_file, Some(name), "CRC-32 mismatch"));
+        }
+        return Ok(());
+    }
+    if encrypted || header.method == STORED {
+        // some writers put neither the data nor the data descriptor for the directories.
+        if name.ends_with('/')
+            && let [a, b, c, d] = reader.peek(4).map_err(corrupted)?
+            && matches!(u32::from_le_bytes([*a, *b, *c, *d]), LOCAL_FILE_HEADER | CENTRAL_DIRECTORY_HEADER) {
+            return Ok(());
+        }
+        // the CRC-32 of the encrypted entry is computed from the decrypted data, therefore, it is not verified.
+        let mut data = StoredReader::new(&mut *reader, header.zip64, !encrypted);
+        if encrypted {
+            std::io::copy(&mut data, &mut std::io::sink()).map_err(corrupted)?;
+        } else {
+            write(&mut data)?;
+        }
+        read_data_descriptor(reader, header.zip64).map_err(corrupted)?;
+        return Ok(());
+    }
+    // the compressed data is self-terminating, and the bufread decoders do not read beyond its end.
+    let data: Box<dyn Read> = match header.method {
+        DEFLATED => Box::new(flate2::bufread::DeflateDecoder::new(&mut *reader)),
+        BZIP2 => Box::new(bzip2::bufread::BzDecoder::new(&mut *reader)),
+        ZSTD => Box::new(zstd::Decoder::with_buffer(&mut *reader).map_err(corrupted)?.single_frame()),
+        method => return Err(Error::UnsupportedFormat(format!("{name}: compression method {method} with the data descriptor"))),
+    };
+    let mut data = Crc32Reader::new(data);
+    write(&mut data)?;
+    let crc32 = data.finish();
+    let descriptor = read_data_descriptor(reader, header.zip64).map_err(corrupted)?;
+    if descriptor != crc32 {
+        return Err(Error::corrupted(archive_file, Some(name), "CRC-32 mismatch"));
+    }
+    Ok(())
+}
+
+fn decoder<'a, R: Read + 'a>(reader: R, method: u16, name: &str) -> Result<Box<dyn Read + 'a>> {
+    match method {
+        STORED => Ok(Box::new(reader)),
+        DEFLATED => Ok(Box::new(flate2::read::DeflateDecoder::new(reader))),
+        BZIP2 => Ok(Box::new(bzip2::read::BzDecoder::new(reader))),
+        ZSTD => zstd::Decoder::new(reader)
+            .map(|d| Box::new(d) as Box<dyn Read>)
+            .map_err(Error::IO),
+        method => Err(Error::UnsupportedFormat(format!("{name}: compression method {method}"))),
+    }
+}
+
+fn read_local_header<R: Read>(reader: &mut R) -> std::io::Result<LocalHeader> {
+    let mut buf = [0u8; 26];
+    reader.read_exact(&mut buf)?;
+    let u16_at = |i: usize| u16::from_le_bytes([buf[i], buf[i + 1]]);
+    let u32_at = |i: usize| u32::from_le_bytes([buf[i], buf[i + 1], buf[i + 2], buf[i + 3]]);
+    let flags = u16_at(2);
+    let mut name = vec![0u8; u16_at(22) as usize];
+    reader.read_exact(&mut name)?;
+    let mut extra = vec![0u8; u16_at(24) as usize];
+    reader.read_exact(&mut extra)?;
+
+    let mut header = LocalHeader {
+        name: String::from_utf8_lossy(&name).to_string(),
+        flags,
+        method: u16_at(4),
+        crc32: u32_at(10),
+        compressed_size: u32_at(14) as u64,
+        mtime: dos_timestamp(u16_at(8), u16_at(6)),
+        zip64: false,
+    };
+    let mut fields = extra.as_slice();
+    while fields.len() >= 4 {
+        let id = u16::from_le_bytes([fields[0], fields[1]]);
+        let len = (u16::from_le_bytes([fields[2], fields[3]]) as usize).min(fields.len() - 4);
+        let data = &fields[4..4 + len];
+        match id {
+            // zip64 extended information: the uncompressed size, and the compressed size.
+            0x0001 => {
+                header.zip64 = true;
+                if header.compressed_size == 0xFFFFFFFF && data.len() >= 16 {
+                    header.compressed_size = u64::from_le_bytes(data[8..16].try_into().unwrap());
+                }
+            }
+            // extended timestamp: the flags, and the modified time if the lowest bit of the flags is set.
+            0x5455 if data.len() >= 5 && data[0] & 1 != 0 => {
+                header.mtime = Some(i32::from_le_bytes(data[1..5].try_into().unwrap()) as i64);
+            }
+            _ => {}
+        }
+        fields = &fields[4 + len..];
+    }
+    Ok(header)
+}
+
+/// Reads the data descriptor and returns its CRC-32. The signature of the descriptor is optional.
+fn read_data_descriptor<R: Read>(reader: &mut R, zip64: bool) -> std::io::Result<u32> {
+    let mut crc32 = read_u32(reader)?;
+    if crc32 == DATA_DESCRIPTOR {
+        crc32 = read_u32(reader)?;
+    }
+    let mut sizes = vec![0u8; if zip64 { 16 } else { 8 }];
+    reader.read_exact(&mut sizes)?;
+    Ok(crc32)
+}
+
+/// Reads the stored data of unknown size up to the data descriptor whose signature and compressed size
+/// (and CRC-32 if `verify_crc` is `true`) agree with the bytes read so far.
+/// The data descriptor is left in the stream.
+struct StoredReader<'a, R: Read> {
+    reader: &'a mut PeekReader<R>,
+    descriptor_len: usize,
+    zip64: bool,
+    verify_crc: bool,
+    digest: crc::Digest<'static, u32>,
+    pos: u64,
+    done: bool,
+}
+
+impl<'a, R: Read> StoredReader<'a, R> {
+    fn new(reader: &'a mut PeekReader<R>, zip64: bool, verify_crc: bool) -> Self {
+        let descriptor_len = if zip64 { 24 } else { 16 };
+        Self { reader, descriptor_len, zip64, verify_crc, digest: CRC32.digest(), pos: 0, done: false }
+    }
+
+    /// Returns `true` if the data descriptor of the data before `data[..n]` starts at `data[n..]`.
+    fn is_descriptor(&self, data: &[u8], n: usize) -> bool {
+        let d = &data[n..n + self.descriptor_len];
+        if d[0..4] != DATA_DESCRIPTOR.to_le_bytes() {
+            return false;
+        }
+        let size = match self.zip64 {
+            true => u64::from_le_bytes(d[8..16].try_into().unwrap()),
+            false => u32::from_le_bytes(d[8..12].try_into().unwrap()) as u64,
+        };
+        if size != self.pos + n as u64 {
+            return false;
+        }
+        if !self.verify_crc {
+            return true;
+        }
+        let mut digest = self.digest.clone();
+        digest.update(&data[..n]);
+        d[4..8] == digest.finalize().to_le_bytes()
+    }
+}
+
+impl<R: Read> Read for StoredReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.done || buf.is_empty() {
+            return Ok(0);
+        }
+        let want = self.descriptor_len + buf.len().min(8192);
+        let data = self.reader.peek(want)?.to_vec();
+        if data.len() < self.descriptor_len {
+            return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "no data descriptor"));
+        }
+        let mut n = 0;
+        while n < buf.len() && n + self.descriptor_len <= data.len() {
+            if self.is_descriptor(&data, n) {
+                self.done = true;
+                break;
+            }
+            n += 1;
+        }
+        buf[..n].copy_from_slice(&data[..n]);
+        self.digest.update(&data[..n]);
+        self.reader.consume(n);
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> std::io::Result<u32> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+/// Converts the MS-DOS date and time in local time into the unix timestamp.
+fn dos_timestamp(date: u16, time: u16) -> Option<i64> {
+    let year = ((date >> 9) & 0x7f) as i32 + 1980;
+    let month = ((date >> 5) & 0x0f) as u32;
+    let day = (date & 0x1f) as u32;
+    let hour = (time >> 11) as u32;
+    let minute = ((time >> 5) & 0x3f) as u32;
+    let second = ((time & 0x1f) * 2) as u32;
+    NaiveDate::from_ymd_opt(year, month, day)
+        .and_then(|d| d.and_hms_opt(hour, minute, second))
+        .and_then(|dt| local_timestamp(&dt))
+}
+
+/// Computes the CRC-32 of the data read through it.
+struct Crc32Reader<R: Read> {
+    reader: R,
+    digest: crc::Digest<'static, u32>,
+}
+
+impl<R: Read> Crc32Reader<R> {
+    fn new(reader: R) -> Self {
+        Self { reader, digest: CRC32.digest() }
+    }
+
+    fn finish(self) -> u32 {
+        self.digest.finalize()
+    }
+}
+
+impl<R: Read> Read for Crc32Reader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.reader.read(buf)?;
+        self.digest.update(&buf[..n]);
+        Ok(n)
+    }
+}
+
+/// The buffered reader which can look ahead the given number of bytes without consuming them.
+struct PeekReader<R: Read> {
+    inner: R,
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl<R: Read> PeekReader<R> {
+    fn new(inner: R) -> Self {
+        Self { inner, buf: vec![], pos: 0 }
+    }
+
+    /// Returns the next `n` bytes, or fewer bytes at the end of the stream.
+    fn peek(&mut self, n: usize) -> std::io::Result<&[u8]> {
+        if self.buf.len() - self.pos < n {
+            self.buf.drain(..self.pos);
+            self.pos = 0;
+            let mut chunk = [0u8; 8192];
+            while self.buf.len() < n {
+                match self.inner.read(&mut chunk)? {
+                    0 => break,
+                    len => self.buf.extend_from_slice(&chunk[..len]),
+                }
+            }
+        }
+        let end = self.buf.len().min(self.pos + n);
+        Ok(&self.buf[self.pos..end])
+    }
+}
+
+impl<R: Read> Read for PeekReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.fill_buf()?.read(buf)?;
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: Read> BufRead for PeekReader<R> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        if self.pos == self.buf.len() {
+            self.peek(8192)?;
+        }
+        Ok(&self.buf[self.pos..])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.pos = (self.pos + amount).min(self.buf.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::path::PathBuf;
+    use zip::write::SimpleFileOptions;
+
+    #[test]
+    fn test_data_descriptors() {
+        let mut zw = zip::ZipWriter::new_stream(vec![]);
+        let opts = SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated)
+            .compression_level(Some(6));
+        zw.add_directory("dir/", opts).unwrap();
+        zw.start_file("dir/deflated.txt", opts).unwrap();
+        zw.write_all(b"hello, deflated world").unwrap();
+        let opts = opts.compression_method(zip::CompressionMethod::Stored).compression_level(None);
+        zw.start_file("stored.txt", opts).unwrap();
+        // the stored data containing the signature of the data descriptor.
+        zw.write_all(b"hello, PK\x07\x08 stored world").unwrap();
+        let data = zw.finish().unwrap().into_inner();
+
+        let config = crate::ExtractConfig::builder()
+            .dest("results/zip_stream")
+            .overwrite(true)
+            .build();
+        let mut dest = Destination::new("results/zip_stream", &config);
+        extract(data.as_slice(), Path::new("-"), &mut dest).unwrap();
+        assert_eq!(std::fs::read("results/zip_stream/dir/deflated.txt").unwrap(), b"hello, deflated world");
+        assert_eq!(std::fs::read("results/zip_stream/stored.txt").unwrap(), b"hello, PK\x07\x08 stored world");
+        std::fs::remove_dir_all("results/zip_stream").unwrap();
+    }
+
+    #[test]
+    fn test_skipped_entries() {
+        let mut zw = zip::ZipWriter::new_stream(vec![]);
+        let opts = SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated)
+            .compression_level(Some(6));
+        for name in ["../escaped.txt", "encrypted.txt", "deflated.txt"] {
+            zw.start_file(name, opts).unwrap();
+            zw.write_all(format!("the content of {name}").as_bytes()).unwrap();
+        }
+        let opts = opts.compression_method(zip::CompressionMethod::Stored).compression_level(None);
+        zw.start_file("stored.txt", opts).unwrap();
+        zw.write_all(b"hello, stored world").unwrap();
+        let mut data = zw.finish().unwrap().into_inner();
+        // marks the second entry as encrypted.
+        let offset = data.windows(4)
+            .enumerate()
+            .filter(|(_, w)| *w == LOCAL_FILE_HEADER.to_le_bytes())
+            .nth(1).unwrap().0;
+        data[offset + 6] |= FLAG_ENCRYPTED as u8;
+
+        let config = crate::ExtractConfig::builder().dest("results/zip_stream_skipped").build();
+        let mut dest = Destination::new("results/zip_stream_skipped", &config);
+        match extract(data.as_slice(), Path::new("-"), &mut dest) {
+            Err(Error::Array(errs)) => {
+                assert_eq!(errs.len(), 2);
+                assert!(matches!(&errs[0], Error::UnsafePath(name) if name == "../escaped.txt"));
+                assert!(matches!(&errs[1], Error::UnsupportedFormat(m) if m.starts_with("encrypted.txt")));
+            }
+            r => panic!("unexpected result: {r:?}"),
+        }
+        assert_eq!(std::fs::read("results/zip_stream_skipped/deflated.txt").unwrap(), b"the content of deflated.txt");
+        assert_eq!(std::fs::read("results/zip_stream_skipped/stored.txt").unwrap(), b"hello, stored world");
+        assert!(!PathBuf::from("results/zip_stream_skipped/encrypted.txt").exists());
+        std::fs::remove_dir_all("results/zip_stream_skipped").unwrap();
+    }
+
+    #[test]
+    fn test_stored_data_limit() {
+        let mut zw = zip::ZipWriter::new_stream(vec![]);
+        let opts = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        zw.start_file("stored.bin", opts).unwrap();
+        zw.write_all(&[0u8; 100_000]).unwrap();
+        let data = zw.finish().unwrap().into_inner();
+
+        let config = crate::ExtractConfig::builder()
+            .dest("results/zip_stream_limit")
+            .max_entry_size(1024)
+            .build();
+        let mut dest = Destination::new("results/zip_stream_limit", &config);
+        match extract(data.as_slice(), Path::new("-"), &mut dest) {
+            Err(Error::LimitExceeded(m)) => assert_eq!(m, "stored.bin: the entry size exceeds 1024 bytes"),
+            r => panic!("unexpected result: {r:?}"),
+        }
+        assert!(!PathBuf::from("results/zip_stream_limit").exists());
+    }
+
+    #[test]
+    fn test_corrupted_stream() {
+        let config = crate::ExtractConfig::builder().dest("results/zip_stream_corrupted").build();
+        let mut dest = Destination::new("results/zip_stream_corrupted", &config);
+        match extract(&b"not a zip file"[..], Path::new("-"), &mut dest) {
+            Err(Error::Corrupted { message, .. }) => assert!(message.starts_with("unknown signature"), "{message}"),
+            r => panic!("unexpected result: {r:?}"),
+        }
+    }
+}
@@ -8,6 +8,8 @@
 //! 1. By file extension (default)
 //! 2. By magic number (file signature)
 //! 3. Fixed format (forcing a specific format)
+//!
+//! and the combinations of them (see [Combining Strategies](#combining-strategies)).
 //! 
 //! ### By File Extension
 //! 
@@ -27,7 +29,8 @@
 //! This strategy detects the archive format by reading the file's magic number (file signature). 
 //! This method is more reliable than using file extensions, as it examines the actual content of the file.
 //! However, it may be slightly slower due to the need to read the file.
-//! Additionally, this method cannot distinguish just `.gz` file and `tar.gz` file.
+//! For the compressed files (gzip, bzip2, xz, zstd, and lz4), it peeks into the decompressed stream for the tar header
+//! to distinguish the compressed tarball (e.g., `.tar.gz`) from the single compressed file (e.g., `.gz`).
 //! See [infer](https://docs.rs/infer/latest/infer/) crate's documentation for more details about supported formats by magic number.
 //! 
 //! ```rust
@@ -50,16 +53,66 @@
 //! let format = fd.detect(&PathBuf::from("../testdata/test.zip"))
 //!     .expect("this method always returns the fixed format (this example returns always rar format)");
 //! ```
+//!
+//! ### Combining Strategies
+//!
+//! [`chain_format_detector`] tries the given detectors in order.
+//! [`mismatch_warning_detector`] prefers the magic number, falls back to the file extension,
+//! and reports the [`FormatMismatch`] when they disagree (e.g., the files with wrong extensions).
+//! [`smart_format_detector`] is the one which logs the mismatches as warnings.
+//!
+//! ```rust
+//! use std::path::PathBuf;
+//! let fd = totebag::format::mismatch_warning_detector(|mismatch| {
+//!     assert_eq!(mismatch.by_extension.name, "Rar");
+//!     assert_eq!(mismatch.by_magic.name, "Zip");
+//! });
+//! let format = fd.detect(&PathBuf::from("../testdata/camouflage_of_zip.rar"))
+//!     .expect("the content of this file is zip");
+//! assert_eq!(format.name, "Zip");
+//! ```
+//!
+//! ## Registering Formats
+//!
+//! The applications can add their own formats by [`Registration`] with the factories of
+//! [`ToteArchiver`] and [`ToteExtractor`].
+//! The registered formats are detected by their extensions and magic bytes (see [`Format::with_magic`]),
+//! and are available on [`extract`](crate::extract), [`list`](crate::list), and [`archive`](crate::archive).
+//! The built-in formats are in the same registry, and [`Registration::replace`] overrides or extends them.
+use std::collections::HashMap;
 use std::fmt::Display;
-use std::path::Path;
-use std::sync::LazyLock;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, RwLock, RwLockReadGuard};
+
+use crate::archiver::ToteArchiver;
+use crate::extractor::ToteExtractor;
+use crate::{Error, Result};
+
+static MANAGER: LazyLock<RwLock<Manager>> = LazyLock::new(|| RwLock::new(Manager::default()));
 
-static MANAGER: LazyLock<Manager> = LazyLock::new(Manager::default);
+/// The factory of the [`ToteArchiver`] for the format.
+pub type ArchiverFactory = Arc<dyn Fn() -> Box<dyn ToteArchiver> + Send + Sync>;
+
+/// The factory of the [`ToteExtractor`] for the format.
+pub type ExtractorFactory = Arc<dyn Fn() -> Box<dyn ToteExtractor> + Send + Sync>;
 
 /// Archive format manager.
-#[derive(Debug, Clone)]
 struct Manager {
-    formats: Vec<Format>,
+    formats: Vec<&'static Format>,
+    factories: HashMap<String, Factories>,
+}
+
+/// The factories of the format, the built-in ones or the ones given by [`Registration`].
+#[derive(Clone, Default)]
+struct Factories {
+    archiver: Option<ArchiverFactory>,
+    extractor: Option<ExtractorFactory>,
+}
+
+fn manager() -> RwLockReadGuard<'static, Manager> {
+    MANAGER.read().unwrap_or_else(|e| e.into_inner())
 }
 
 /// Returns an instance of the format detector by file extension.
@@ -77,6 +130,30 @@ pub fn fixed_format_detector(format: &'static Format) -> Box<dyn FormatDetector>
     Box::new(FixedFormatDetector::new(format))
 }
 
+/// Returns an instance of the format detector which tries the given detectors in order,
+/// and returns the first detected format.
+///
+/// ```
+/// use totebag::format::{chain_format_detector, default_format_detector, magic_number_format_detector};
+/// let fd = chain_format_detector(vec![magic_number_format_detector(), default_format_detector()]);
+/// assert_eq!(fd.detect("../testdata/camouflage_of_zip.rar".as_ref()).unwrap().name, "Zip");
+/// assert_eq!(fd.detect("not_exist_file.tar.gz".as_ref()).unwrap().name, "TarGz");
+/// ```
+pub fn chain_format_detector(detectors: Vec<Box<dyn FormatDetector>>) -> Box<dyn FormatDetector> {
+    Box::new(ChainFormatDetector { detectors })
+}
+
+/// Returns an instance of the format detector which prefers the magic number and falls back to the file extension.
+/// The given handler receives the [`FormatMismatch`] when the magic number and the file extension disagree.
+pub fn mismatch_warning_detector<F: Fn(&FormatMismatch) + 'static>(handler: F) -> Box<dyn FormatDetector> {
+    Box::new(MismatchWarningDetector { handler: Box::new(handler) })
+}
+
+/// Returns an instance of [`mismatch_warning_detector`] which logs the [`FormatMismatch`] as a warning.
+pub fn smart_format_detector() -> Box<dyn FormatDetector> {
+    mismatch_warning_detector(|mismatch| log::warn!("{mismatch}"))
+}
+
 /// The trait for detecting the archive format of a file.
 pub trait FormatDetector {
     /// Detects the archive format of the given file path.
@@ -84,8 +161,36 @@ pub trait FormatDetector {
     fn detect(&self, path: &Path) -> Option<&Format>;
 }
 
+/// The handler of the [`FormatMismatch`] found by [`mismatch_warning_detector`].
+pub type MismatchHandler = Box<dyn Fn(&FormatMismatch)>;
+
+/// The disagreement between the file extension and the magic number of the file,
+/// e.g., a zip file named `camouflage_of_zip.rar`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatMismatch {
+    /// the path of the file.
+    pub path: PathBuf,
+    /// the format detected by the file extension.
+    pub by_extension: &'static Format,
+    /// the format detected by the magic number, which [`mismatch_warning_detector`] adopts.
+    pub by_magic: &'static Format,
+}
+
+impl Display for FormatMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: the extension indicates {}, but the content is {}",
+            self.path.display(), self.by_extension, self.by_magic)
+    }
+}
+
 struct ExtensionFormatDetector;
 struct MagicNumberFormatDetector;
+struct ChainFormatDetector {
+    detectors: Vec<Box<dyn FormatDetector>>,
+}
+struct MismatchWarningDetector {
+    handler: MismatchHandler,
+}
 struct FixedFormatDetector {
     format: &'static Format,
 }
@@ -96,44 +201,141 @@ impl FixedFormatDetector {
     }
 }
 
-impl FormatDetector for MagicNumberFormatDetector {
-    fn detect(&self, filename: &Path) -> Option<&Format> {
-        match infer::get_from_path(filename) {
-            Err(e) => {
-                log::error!("Failed to read file for format detection: {e:?}");
-                None
-            },
-            Ok(Some(info)) => {
-                match info.mime_type() {
-                    "application/x-archive" => find_format_by_name("Ar"),
-                    "application/x-cab" => find_format_by_name("Cab"),
-                    "application/x-cpio" => find_format_by_name("Cpio"),
-                    "application/x-lzh" | "application/x-lha" => find_format_by_name("Lha"),
-                    "application/x-7z-compressed" => find_format_by_name("SevenZ"),
-                    "application/vnd.rar" => find_format_by_name("Rar"),
-                    "application/x-tar" => find_format_by_name("Tar"),
-                    "application/gzip" => find_format_by_name("TarGz"),
-                    "application/x-bzip2" => find_format_by_name("TarBz2"),
-                    "application/x-xz" => find_format_by_name("TarXz"),
-                    "application/zstd" => find_format_by_name("TarZstd"),
-                    "application/zip" | "application/java-archive" => find_format_by_name("Zip"),
-                    other => {
-                        log::error!("Unknown file format detected by magic number: {filename:?} (mime-type: {other})");
-                        None
-                    }
+/// Detects the format by the magic number of the given file.
+fn detect_by_magic(filename: &Path) -> Option<&'static Format> {
+    if let Some(format) = detect_registered_magic(filename) {
+        return Some(format);
+    }
+    match infer::get_from_path(filename) {
+        Err(e) => {
+            log::error!("Failed to read file for format detection: {e:?}");
+            None
+        },
+        Ok(Some(info)) => {
+            match info.mime_type() {
+                "application/x-archive" => find_format_by_name("Ar"),
+                "application/vnd.debian.binary-package" => find_format_by_name("Deb"),
+                "application/x-cab" => find_format_by_name("Cab"),
+                "application/x-cpio" => find_format_by_name("Cpio"),
+                "application/x-lzh" | "application/x-lha" => find_format_by_name("Lha"),
+                "application/x-7z-compressed" => find_format_by_name("SevenZ"),
+                "application/vnd.rar" => find_format_by_name("Rar"),
+                "application/x-rpm" => find_format_by_name("Rpm"),
+                "application/x-tar" => find_format_by_name("Tar"),
+                "application/gzip" => detect_compressed(filename, "TarGz", "Gz", |f| Ok(Box::new(flate2::read::GzDecoder::new(f)))),
+                "application/x-bzip2" => detect_compressed(filename, "TarBz2", "Bz2", |f| Ok(Box::new(bzip2::read::BzDecoder::new(f)))),
+                "application/x-xz" => detect_compressed(filename, "TarXz", "Xz", |f| Ok(Box::new(xz2::read::XzDecoder::new(f)))),
+                "application/zstd" => detect_compressed(filename, "TarZstd", "Zstd", |f| Ok(Box::new(zstd::Decoder::new(f)?))),
+                "application/x-lz4" => detect_compressed(filename, "TarLz4", "Lz4", |f| Ok(Box::new(lz4_flex::frame::FrameDecoder::new(f)))),
+                "application/x-lzip" => find_format_by_name("TarLz"),
+                "application/x-compress" => find_format_by_name("TarZ"),
+                "application/zip" | "application/java-archive" => find_format_by_name("Zip"),
+                other => {
+                    log::error!("Unknown file format detected by magic number: {filename:?} (mime-type: {other})");
+                    None
                 }
-            },
-            Ok(None) => {
-                log::error!("Could not detect file format from magic number: {filename:?}");
-                None
             }
+        },
+        Ok(None) => {
+            log::error!("Could not detect file format from magic number: {filename:?}");
+            None
         }
     }
 }
 
+type Decompressor = fn(File) -> std::io::Result<Box<dyn Read>>;
+
+/// Returns the format of the compressed tarball (`tar_name`) if the decompressed stream of the given file
+/// starts with the tar header, otherwise, returns the format of the single compressed file (`name`).
+fn detect_compressed(filename: &Path, tar_name: &str, name: &str, decompressor: Decompressor) -> Option<&'static Format> {
+    let mut block = Vec::with_capacity(512);
+    let is_tar = File::open(filename)
+        .and_then(decompressor)
+        .and_then(|r| r.take(512).read_to_end(&mut block))
+        .is_ok_and(|_| is_tar_header(&block));
+    find_format_by_name(if is_tar { tar_name } else { name })
+}
+
+/// Returns `true` if the given block is the header of tar, i.e.,
+/// it has the magic of ustar/GNU tar, or the valid checksum of the old (v7) tar.
+pub(crate) fn is_tar_header(block: &[u8]) -> bool {
+    if block.len() < 512 {
+        return false;
+    }
+    if &block[257..262] == b"ustar" {
+        return true;
+    }
+    let checksum = std::str::from_utf8(&block[148..156]).ok()
+        .map(|s| s.trim_matches(|c: char| c == ' ' || c == '\0'))
+        .and_then(|s| u32::from_str_radix(s, 8).ok());
+    let sum = block[..512].iter().enumerate()
+        .map(|(i, b)| if (148..156).contains(&i) { b' ' as u32 } else { *b as u32 })
+        .sum::<u32>();
+    checksum == Some(sum)
+}
+
+/// Returns the format whose magic bytes (see [`Format::with_magic`]) match the header of the given file,
+/// e.g., the registered formats, ISO 9660, and SquashFS images, which [infer](https://docs.rs/infer/latest/infer/) does not detect.
+fn detect_registered_magic(filename: &Path) -> Option<&'static Format> {
+    let manager = manager();
+    let length = manager.formats.iter()
+        .flat_map(|f| f.magics.iter())
+        .map(|(offset, bytes)| offset + bytes.len())
+        .max()?;
+    let mut header = Vec::with_capacity(length);
+    File::open(filename).ok()?
+        .take(length as u64)
+        .read_to_end(&mut header).ok()?;
+    manager.formats.iter().find(|f| f.match_magic(&header)).copied()
+}
+
+/// Detects the format with the longest matching extension,
+/// e.g., the format of `.tar.gz` precedes the one of `.gz`.
+fn detect_by_ext(path: &Path) -> Option<&'static Format> {
+    let name = path.to_string_lossy().to_lowercase();
+    let mut found: Option<(&'static Format, usize)> = None;
+    for format in manager().formats.iter() {
+        for ext in format.exts.iter().filter(|ext| name.ends_with(ext.as_str())) {
+            if found.is_none_or(|(_, len)| len < ext.len()) {
+                found = Some((format, ext.len()));
+            }
+        }
+    }
+    found.map(|(format, _)| format)
+}
+
+impl FormatDetector for MagicNumberFormatDetector {
+    fn detect(&self, path: &Path) -> Option<&Format> {
+        detect_by_magic(path)
+    }
+}
+
 impl FormatDetector for ExtensionFormatDetector {
     fn detect(&self, path: &Path) -> Option<&Format> {
-        MANAGER.formats.iter().find(|f| f.match_exts(path))
+        detect_by_ext(path)
+    }
+}
+
+impl FormatDetector for ChainFormatDetector {
+    fn detect(&self, path: &Path) -> Option<&Format> {
+        self.detectors.iter().find_map(|d| d.detect(path))
+    }
+}
+
+impl FormatDetector for MismatchWarningDetector {
+    fn detect(&self, path: &Path) -> Option<&Format> {
+        let by_magic = detect_by_magic(path);
+        let by_extension = detect_by_ext(path);
+        if let (Some(by_magic), Some(by_extension)) = (by_magic, by_extension)
+            && by_magic != by_extension
+        {
+            (self.handler)(&FormatMismatch {
+                path: path.to_path_buf(),
+                by_extension,
+                by_magic,
+            });
+        }
+        by_magic.or(by_extension)
     }
 }
 
@@ -145,7 +347,7 @@ impl FormatDetector for FixedFormatDetector {
 
 impl Default for Manager {
     fn default() -> Self {
-        Manager::new(vec![
+        let mut manager = Manager::new(vec![
             Format::new("Ar", vec![".ar", ".a", ".lib"]),
             Format::new("Cab", vec![".cab"]),
             Format::new("Cpio", vec![".cpio"]),
@@ -158,7 +360,28 @@ impl Default for Manager {
             Format::new("TarXz", vec![".tar.xz", ".txz"]),
             Format::new("TarZstd", vec![".tar.zst", ".tzst", ".tar.zstd", ".tzstd"]),
             Format::new("Zip", vec![".zip", ".jar", ".war", ".ear"]),
-        ])
+            Format::new("Gz", vec![".gz"]),
+            Format::new("Bz2", vec![".bz2"]),
+            Format::new("Xz", vec![".xz"]),
+            Format::new("Zstd", vec![".zst", ".zstd"]),
+            Format::new("TarLz4", vec![".tar.lz4", ".tlz4"]),
+            Format::new("Lz4", vec![".lz4"]),
+            Format::new("TarLzma", vec![".tar.lzma", ".tlz"]),
+            Format::new("TarLz", vec![".tar.lz"]),
+            Format::new("TarZ", vec![".tar.z", ".taz"]),
+            Format::new("TarBr", vec![".tar.br", ".tbr"]),
+            Format::new("Deb", vec![".deb"]),
+            Format::new("Rpm", vec![".rpm"]),
+            Format::new("Iso", vec![".iso"]).with_magic(32769, b"CD001"),
+            Format::new("SquashFs", vec![".squashfs", ".sqfs", ".snap"]).with_magic(0, b"hsqs"),
+        ]);
+        for (name, factory) in crate::archiver::builtin_factories() {
+            manager.factories.entry(name.to_lowercase()).or_default().archiver = Some(factory);
+        }
+        for (name, factory) in crate::extractor::builtin_factories() {
+            manager.factories.entry(name.to_lowercase()).or_default().extractor = Some(factory);
+        }
+        manager
     }
 }
 
@@ -172,7 +395,7 @@ pub fn is_all_archive_file<P: AsRef<Path>>(args: &[P], fd: &dyn FormatDetector)
 pub fn find_format_by_name<S: AsRef<str>>(name: S) -> Option<&'static Format> {
     let name = name.as_ref().to_lowercase();
     log::debug!("find format by name: {name}");
-    MANAGER.formats.iter().find(|f| f.name.to_lowercase() == name)
+    manager().formats.iter().find(|f| f.name.to_lowercase() == name).copied()
 }
 
 /// Find the instance of [`Format`] from the given file extension.
@@ -183,12 +406,127 @@ pub fn find_format_by_ext<S: AsRef<str>>(ext: S) -> Option<&'static Format> {
     } else {
         ext.to_string()
     }.to_lowercase();
-    MANAGER.formats.iter().find(|f| f.exts.contains(&ext))
+    manager().formats.iter().find(|f| f.exts.contains(&ext)).copied()
+}
+
+/// Returns all of the available formats, including the ones registered by [`Registration::register`].
+pub fn formats() -> Vec<&'static Format> {
+    manager().formats.clone()
+}
+
+/// Creates the archiver of the given format from the registry, including the built-in formats.
+/// Returns `None` if the given format is unknown.
+pub(crate) fn create_archiver(format: &Format) -> Option<Result<Box<dyn ToteArchiver>>> {
+    let factories = manager().factories.get(&format.name.to_lowercase()).cloned()?;
+    Some(match factories.archiver {
+        Some(factory) => Ok(factory()),
+        None => Err(Error::UnsupportedFormat(format!("{}: archiving", format.name))),
+    })
+}
+
+/// Creates the extractor of the given format from the registry, including the built-in formats.
+/// Returns `None` if the given format is unknown.
+pub(crate) fn create_extractor(format: &Format) -> Option<Result<Box<dyn ToteExtractor>>> {
+    let factories = manager().factories.get(&format.name.to_lowercase()).cloned()?;
+    Some(match factories.extractor {
+        Some(factory) => Ok(factory()),
+        None => Err(Error::UnsupportedFormat(format!("{}: extraction", format.name))),
+    })
 }
 
 impl Manager {
-    pub(crate) fn new(formats: Vec<Format>) -> Self {
-        Self { formats }
+    fn new(formats: Vec<Format>) -> Self {
+        Self {
+            formats: formats.into_iter().map(|f| &*Box::leak(Box::new(f))).collect(),
+            factories: HashMap::new(),
+        }
+    }
+}
+
+/// Registers the third-party [`Format`] with the factories of its [`ToteArchiver`] and [`ToteExtractor`].
+/// The registered format works through [`extract`](crate::extract), [`list`](crate::list),
+/// [`archive`](crate::archive), and the format detectors, as the same as the built-in formats.
+///
+/// # Example
+///
+/// ```
+/// use std::path::PathBuf;
+/// use totebag::format::{Format, Registration};
+/// # struct MyArchiver;
+/// # impl totebag::archiver::ToteArchiver for MyArchiver {
+/// #     fn perform(&self, _: Box<dyn totebag::archiver::WriteSeek + '_>, _: &[PathBuf], _: &totebag::ArchiveConfig)
+/// #         -> totebag::Result<Vec<totebag::archiver::ArchiveEntry>> { Ok(vec![]) }
+/// #     fn enable(&self) -> bool { true }
+/// # }
+///
+/// let format = Registration::new(Format::new("MyFormat", vec![".myf"]).with_magic(0, b"MYF1"))
+///     .archiver(|| Box::new(MyArchiver))
+///     .register()
+///     .unwrap();
+/// assert_eq!(totebag::format::find_format_by_ext(".myf"), Some(format));
+/// ```
+pub struct Registration {
+    format: Format,
+    factories: Factories,
+}
+
+impl Registration {
+    /// Creates the registration of the given format without archiver and extractor.
+    pub fn new(format: Format) -> Self {
+        Self { format, factories: Factories::default() }
+    }
+
+    /// Sets the factory of the archiver. Archiving into the format is unsupported without it.
+    pub fn archiver<F>(mut self, factory: F) -> Self
+    where
+        F: Fn() -> Box<dyn ToteArchiver> + Send + Sync + 'static,
+    {
+        self.factories.archiver = Some(Arc::new(factory));
+        self
+    }
+
+    /// Sets the factory of the extractor. Extracting and listing the format are unsupported without it.
+    pub fn extractor<F>(mut self, factory: F) -> Self
+    where
+        F: Fn() -> Box<dyn ToteExtractor> + Send + Sync + 'static,
+    {
+        self.factories.extractor = Some(Arc::new(factory));
+        self
+    }
+
+    /// Registers the format, and returns the registered one.
+    /// Returns [`Error::FormatExists`] if a format with the same name (case-insensitive) is already available.
+    pub fn register(self) -> Result<&'static Format> {
+        let mut manager = MANAGER.write().unwrap_or_else(|e| e.into_inner());
+        let name = self.format.name.to_lowercase();
+        if manager.formats.iter().any(|f| f.name.to_lowercase() == name) {
+            return Err(Error::FormatExists(self.format.name));
+        }
+        let format: &'static Format = Box::leak(Box::new(self.format));
+        manager.formats.push(format);
+        manager.factories.insert(name, self.factories);
+        Ok(format)
+    }
+
+    /// Replaces the factories of the available format of the same name (case-insensitive),
+    /// including the built-in formats, and returns the available format.
+    /// Only the factories set to this registration are replaced, e.g., setting only the archiver
+    /// adds archiving to the extraction-only format. The extensions and the magic bytes are not changed.
+    /// Returns [`Error::UnknownFormat`] if no format of the name is available.
+    pub fn replace(self) -> Result<&'static Format> {
+        let mut manager = MANAGER.write().unwrap_or_else(|e| e.into_inner());
+        let name = self.format.name.to_lowercase();
+        let Some(format) = manager.formats.iter().find(|f| f.name.to_lowercase() == name).copied() else {
+            return Err(Error::UnknownFormat(format!("{}: unknown format", self.format.name)));
+        };
+        let factories = manager.factories.entry(name).or_default();
+        if let Some(archiver) = self.factories.archiver {
+            factories.archiver = Some(archiver);
+        }
+        if let Some(extractor) = self.factories.extractor {
+            factories.extractor = Some(extractor);
+        }
+        Ok(format)
     }
 }
 
@@ -198,6 +536,7 @@ pub struct Format {
     /// The general format name.
     pub name: String,
     exts: Vec<String>,
+    magics: Vec<(usize, Vec<u8>)>,
 }
 
 impl Display for Format {
@@ -224,13 +563,32 @@ impl Format {
         Self {
             name: name.into(),
             exts: exts.into_iter().map(|e| e.into().to_lowercase()).collect(),
+            magics: vec![],
         }
     }
 
+    /// Adds the magic bytes at the given offset of the file header for [`magic_number_format_detector`].
+    /// The format matches if any of its magic bytes matches.
+    pub fn with_magic<B: Into<Vec<u8>>>(mut self, offset: usize, bytes: B) -> Self {
+        self.magics.push((offset, bytes.into()));
+        self
+    }
+
+    /// Returns the extensions of this format (lower case, with the leading dot).
+    pub fn exts(&self) -> &[String] {
+        &self.exts
+    }
+
+    /// Returns `true` if the given file header has any of the magic bytes of this format.
+    pub fn match_magic(&self, header: &[u8]) -> bool {
+        self.magics.iter()
+            .any(|(offset, bytes)| header.get(*offset..offset + bytes.len()) == Some(bytes.as_slice()))
+    }
+
     /// Returns `true` if the given file name has the extension of this format.
     pub fn match_exts<P: AsRef<Path>>(&self, p: P) -> bool {
         let p = p.as_ref();
-        let name = p.to_str().unwrap().to_lowercase();
+        let name = p.to_string_lossy().to_lowercase();
         for ext in &self.exts {
             if name.ends_with(ext) {
                 return true;
@@ -243,36 +601,59 @@ impl Format {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::io::Write;
 
     #[test]
     fn test_format() {
         use std::path::PathBuf;
+        let formats = formats();
         let fd = default_format_detector();
         assert_eq!(fd.detect(&PathBuf::from("hoge.unknown")), None);
-        assert_eq!(fd.detect(&PathBuf::from("test.a")), Some(&MANAGER.formats[0]));
-        assert_eq!(fd.detect(&PathBuf::from("test.ar")), Some(&MANAGER.formats[0]));
-        assert_eq!(fd.detect(&PathBuf::from("test.lib")), Some(&MANAGER.formats[0]));
-        assert_eq!(fd.detect(&PathBuf::from("test.cab")), Some(&MANAGER.formats[1]));
-        assert_eq!(fd.detect(&PathBuf::from("test.cpio")), Some(&MANAGER.formats[2]));
-        assert_eq!(fd.detect(&PathBuf::from("test.lha")), Some(&MANAGER.formats[3]));
-        assert_eq!(fd.detect(&PathBuf::from("test.lzh")), Some(&MANAGER.formats[3]));
-        assert_eq!(fd.detect(&PathBuf::from("test.7z")), Some(&MANAGER.formats[4]));
-        assert_eq!(fd.detect(&PathBuf::from("test.rar")), Some(&MANAGER.formats[5]));
-        assert_eq!(fd.detect(&PathBuf::from("test.tar")), Some(&MANAGER.formats[6]));
-        assert_eq!(fd.detect(&PathBuf::from("test.tar.gz")), Some(&MANAGER.formats[7]));
-        assert_eq!(fd.detect(&PathBuf::from("test.tgz")), Some(&MANAGER.formats[7]));
-        assert_eq!(fd.detect(&PathBuf::from("test.tar.bz2")), Some(&MANAGER.formats[8]));
-        assert_eq!(fd.detect(&PathBuf::from("test.tbz2")), Some(&MANAGER.formats[8]));
-        assert_eq!(fd.detect(&PathBuf::from("test.tar.xz")), Some(&MANAGER.formats[9]));
-        assert_eq!(fd.detect(&PathBuf::from("test.txz")), Some(&MANAGER.formats[9]));
-        assert_eq!(fd.detect(&PathBuf::from("test.tar.zst")), Some(&MANAGER.formats[10]));
-        assert_eq!(fd.detect(&PathBuf::from("test.tzst")), Some(&MANAGER.formats[10]));
-        assert_eq!(fd.detect(&PathBuf::from("test.tar.zstd")), Some(&MANAGER.formats[10]));
-        assert_eq!(fd.detect(&PathBuf::from("test.tzstd")), Some(&MANAGER.formats[10]));
-        assert_eq!(fd.detect(&PathBuf::from("test.zip")), Some(&MANAGER.formats[11]));
-        assert_eq!(fd.detect(&PathBuf::from("test.jar")), Some(&MANAGER.formats[11]));
-        assert_eq!(fd.detect(&PathBuf::from("test.ear")), Some(&MANAGER.formats[11]));
-        assert_eq!(fd.detect(&PathBuf::from("test.war")), Some(&MANAGER.formats[11]));
+        assert_eq!(fd.detect(&PathBuf::from("test.a")), Some(formats[0]));
+        assert_eq!(fd.detect(&PathBuf::from("test.ar")), Some(formats[0]));
+        assert_eq!(fd.detect(&PathBuf::from("test.lib")), Some(formats[0]));
+        assert_eq!(fd.detect(&PathBuf::from("test.cab")), Some(formats[1]));
+        assert_eq!(fd.detect(&PathBuf::from("test.cpio")), Some(formats[2]));
+        assert_eq!(fd.detect(&PathBuf::from("test.lha")), Some(formats[3]));
+        assert_eq!(fd.detect(&PathBuf::from("test.lzh")), Some(formats[3]));
+        assert_eq!(fd.detect(&PathBuf::from("test.7z")), Some(formats[4]));
+        assert_eq!(fd.detect(&PathBuf::from("test.rar")), Some(formats[5]));
+        assert_eq!(fd.detect(&PathBuf::from("test.tar")), Some(formats[6]));
+        assert_eq!(fd.detect(&PathBuf::from("test.tar.gz")), Some(formats[7]));
+        assert_eq!(fd.detect(&PathBuf::from("test.tgz")), Some(formats[7]));
+        assert_eq!(fd.detect(&PathBuf::from("test.tar.bz2")), Some(formats[8]));
+        assert_eq!(fd.detect(&PathBuf::from("test.tbz2")), Some(formats[8]));
+        assert_eq!(fd.detect(&PathBuf::from("test.tar.xz")), Some(formats[9]));
+        assert_eq!(fd.detect(&PathBuf::from("test.txz")), Some(formats[9]));
+        assert_eq!(fd.detect(&PathBuf::from("test.tar.zst")), Some(formats[10]));
+        assert_eq!(fd.detect(&PathBuf::from("test.tzst")), Some(formats[10]));
+        assert_eq!(fd.detect(&PathBuf::from("test.tar.zstd")), Some(formats[10]));
+        assert_eq!(fd.detect(&PathBuf::from("test.tzstd")), Some(formats[10]));
+        assert_eq!(fd.detect(&PathBuf::from("test.zip")), Some(formats[11]));
+        assert_eq!(fd.detect(&PathBuf::from("test.jar")), Some(formats[11]));
+        assert_eq!(fd.detect(&PathBuf::from("test.ear")), Some(formats[11]));
+        assert_eq!(fd.detect(&PathBuf::from("test.war")), Some(formats[11]));
+        assert_eq!(fd.detect(&PathBuf::from("test.gz")), Some(formats[12]));
+        assert_eq!(fd.detect(&PathBuf::from("test.bz2")), Some(formats[13]));
+        assert_eq!(fd.detect(&PathBuf::from("test.xz")), Some(formats[14]));
+        assert_eq!(fd.detect(&PathBuf::from("test.zst")), Some(formats[15]));
+        assert_eq!(fd.detect(&PathBuf::from("test.zstd")), Some(formats[15]));
+        assert_eq!(fd.detect(&PathBuf::from("test.tar.lz4")), Some(formats[16]));
+        assert_eq!(fd.detect(&PathBuf::from("test.tlz4")), Some(formats[16]));
+        assert_eq!(fd.detect(&PathBuf::from("test.lz4")), Some(formats[17]));
+        assert_eq!(fd.detect(&PathBuf::from("test.tar.lzma")), Some(formats[18]));
+        assert_eq!(fd.detect(&PathBuf::from("test.tlz")), Some(formats[18]));
+        assert_eq!(fd.detect(&PathBuf::from("test.tar.lz")), Some(formats[19]));
+        assert_eq!(fd.detect(&PathBuf::from("test.tar.Z")), Some(formats[20]));
+        assert_eq!(fd.detect(&PathBuf::from("test.taz")), Some(formats[20]));
+        assert_eq!(fd.detect(&PathBuf::from("test.tar.br")), Some(formats[21]));
+        assert_eq!(fd.detect(&PathBuf::from("test.tbr")), Some(formats[21]));
+        assert_eq!(fd.detect(&PathBuf::from("test.deb")), Some(formats[22]));
+        assert_eq!(fd.detect(&PathBuf::from("test.rpm")), Some(formats[23]));
+        assert_eq!(fd.detect(&PathBuf::from("test.iso")), Some(formats[24]));
+        assert_eq!(fd.detect(&PathBuf::from("test.squashfs")), Some(formats[25]));
+        assert_eq!(fd.detect(&PathBuf::from("test.sqfs")), Some(formats[25]));
+        assert_eq!(fd.detect(&PathBuf::from("test.snap")), Some(formats[25]));
     }
 
     #[test]
@@ -333,10 +714,232 @@ mod tests {
         assert_eq!(format.name, "Rar");
         let format = detector.detect(Path::new("../testdata/camouflage_of_zip.rar")).unwrap();
         assert_eq!(format.name, "Zip");
+        for (file, name) in [
+            ("test.tar.gz", "TarGz"), ("README.md.gz", "Gz"),
+            ("test.tar.bz2", "TarBz2"), ("README.md.bz2", "Bz2"),
+            ("test.tar.xz", "TarXz"), ("README.md.xz", "Xz"),
+            ("test.tar.zst", "TarZstd"), ("README.md.zst", "Zstd"),
+            ("test.tar.lz4", "TarLz4"), ("README.md.lz4", "Lz4"),
+            ("test.tar.lz", "TarLz"), ("test.tar.Z", "TarZ"), ("test.deb", "Deb"),
+            ("test.rpm", "Rpm"), ("test.iso", "Iso"), ("test_joliet.iso", "Iso"),
+            ("test.squashfs", "SquashFs"), ("test_lz4.snap", "SquashFs"),
+            ("single_root.tar.gz", "TarGz"), ("bomb.tar.zst", "TarZstd"),
+        ] {
+            let format = detector.detect(&Path::new("../testdata").join(file)).unwrap();
+            assert_eq!(format.name, name, "{file}");
+        }
         let format = detector.detect(Path::new("../testdata/not_exist_file.rar"));
         assert!(format.is_none());
     }
 
+    struct BoxArchiver;
+
+    impl ToteArchiver for BoxArchiver {
+        fn perform(
+            &self,
+            mut writer: Box<dyn crate::archiver::WriteSeek + '_>,
+            targets: &[std::path::PathBuf],
+            config: &crate::ArchiveConfig,
+        ) -> Result<Vec<crate::archiver::ArchiveEntry>> {
+            writer.write_all(b"TBOX").map_err(Error::IO)?;
+            crate::archiver::create_with(find_format_by_name("Tar").unwrap())?
+                .perform_stream(writer, targets, config)
+        }
+
+        fn enable(&self) -> bool {
+            true
+        }
+    }
+
+    struct BoxExtractor;
+
+    impl BoxExtractor {
+        fn tar() -> Box<dyn ToteExtractor> {
+            crate::extractor::create_with("", find_format_by_name("Tar")).unwrap()
+        }
+
+        fn skip_magic(reader: &mut dyn Read) -> Result<()> {
+            let mut magic = [0u8; 4];
+            reader.read_exact(&mut magic).map_err(Error::IO)?;
+            if &magic != b"TBOX" {
+                return Err(Error::Extractor("not a tbox file".to_string()));
+            }
+            Ok(())
+        }
+    }
+
+    impl ToteExtractor for BoxExtractor {
+        fn list_from(&self, mut reader: Box<dyn crate::extractor::ReadSeek + '_>, archive_file: std::path::PathBuf) -> Result<crate::extractor::Entries> {
+            Self::skip_magic(&mut reader)?;
+            Self::tar().list_from(reader, archive_file)
+        }
+
+        fn perform_from(&self, mut reader: Box<dyn crate::extractor::ReadSeek + '_>, dest: &mut crate::extractor::Destination) -> Result<()> {
+            Self::skip_magic(&mut reader)?;
+            Self::tar().perform_from(reader, dest)
+        }
+    }
+
+    #[test]
+    fn test_register_format() {
+        let format = Registration::new(Format::new("TestBox", vec![".tbox"]).with_magic(0, b"TBOX"))
+            .archiver(|| Box::new(BoxArchiver))
+            .extractor(|| Box::new(BoxExtractor))
+            .register()
+            .unwrap();
+        assert_eq!(find_format_by_name("testbox"), Some(format));
+        assert_eq!(find_format_by_ext("TBOX"), Some(format));
+        assert!(formats().contains(&format));
+        assert_eq!(default_format_detector().detect(Path::new("test.tbox")), Some(format));
+
+        let config = crate::ArchiveConfig::builder()
+            .dest("results/registry/test.tbox")
+            .overwrite(true)
+            .build();
+        crate::archive(&["Cargo.toml", "src/format.rs"], &config).unwrap();
+
+        // the magic bytes of the registered formats take precedence over the file extension.
+        std::fs::copy("results/registry/test.tbox", "results/registry/camouflage.zip").unwrap();
+        let detector = magic_number_format_detector();
+        assert_eq!(detector.detect(Path::new("results/registry/camouflage.zip")), Some(format));
+        assert_eq!(detector.detect(Path::new("../testdata/test.zip")).map(|f| f.name.as_str()), Some("Zip"));
+
+        let entries = crate::entries("results/registry/test.tbox", default_format_detector().as_ref()).unwrap();
+        assert!(entries.iter().any(|e| e.name == "src/format.rs"), "{entries:?}");
+
+        let config = crate::ExtractConfig::builder()
+            .dest("results/registry/extracted")
+            .overwrite(true)
+            .build();
+        crate::extract("results/registry/test.tbox", &config).unwrap();
+        assert!(Path::new("results/registry/extracted/src/format.rs").exists());
+        let _ = std::fs::remove_dir_all("results/registry");
+    }
+
+    #[test]
+    fn test_register_format_without_extractor() {
+        Registration::new(Format::new("TestBoxWriteOnly", vec![".tboxw"]))
+            .archiver(|| Box::new(BoxArchiver))
+            .register()
+            .unwrap();
+        match crate::entries("test.tboxw", default_format_detector().as_ref()) {
+            Err(Error::UnsupportedFormat(m)) => assert_eq!(m, "TestBoxWriteOnly: extraction"),
+            r => panic!("unexpected result: {r:?}"),
+        }
+    }
+
+    #[test]
+    fn test_replace_format() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        static CREATED: AtomicUsize = AtomicUsize::new(0);
+
+        // wraps the built-in extractor not to change the behavior for the other tests.
+        let builtin = manager().factories.get("ar").and_then(|f| f.extractor.clone()).unwrap();
+        let format = Registration::new(Format::new("AR", vec![]))
+            .extractor(move || {
+                CREATED.fetch_add(1, Ordering::SeqCst);
+                builtin()
+            })
+            .replace()
+            .unwrap();
+        assert_eq!(format.name, "Ar");
+        assert_eq!(format.exts(), &[".ar", ".a", ".lib"]);
+        let entries = crate::entries("../testdata/test.ar", default_format_detector().as_ref()).unwrap();
+        assert!(!entries.is_empty());
+        assert!(CREATED.load(Ordering::SeqCst) > 0);
+        assert!(crate::archiver::create_with(format).is_ok());
+
+        match Registration::new(Format::new("NotExist", vec![".notexist"])).replace() {
+            Err(Error::UnknownFormat(m)) => assert_eq!(m, "NotExist: unknown format"),
+            r => panic!("unexpected result: {r:?}"),
+        }
+    }
+
+    #[test]
+    fn test_register_existing_format() {
+        match Registration::new(Format::new("zIP", vec![".myzip"])).register() {
+            Err(Error::FormatExists(name)) => assert_eq!(name, "zIP"),
+            r => panic!("unexpected result: {r:?}"),
+        }
+        assert!(find_format_by_ext(".myzip").is_none());
+    }
+
+    #[test]
+    fn test_is_tar_header() {
+        let tar = std::fs::read("../testdata/test.tar").unwrap();
+        assert!(is_tar_header(&tar[..512]));
+        let readme = std::fs::read("../testdata/sample/README.md").unwrap();
+        assert!(!is_tar_header(&readme[..512]));
+        assert!(!is_tar_header(&tar[..511]));
+
+        // the old (v7) tar header has no magic, but has the valid checksum.
+        let mut v7 = tar[..512].to_vec();
+        v7[257..265].fill(0);
+        let sum = v7.iter().enumerate()
+            .map(|(i, b)| if (148..156).contains(&i) { 32 } else { *b as u32 })
+            .sum::<u32>();
+        v7[148..156].copy_from_slice(format!("{sum:06o}\0 ").as_bytes());
+        assert!(is_tar_header(&v7));
+        v7[0] ^= 1;
+        assert!(!is_tar_header(&v7));
+    }
+
+    #[test]
+    fn test_match_magic() {
+        let format = Format::new("Test", vec![".test"])
+            .with_magic(0, b"TEST")
+            .with_magic(4, vec![0x01, 0x02]);
+        assert!(format.match_magic(b"TEST"));
+        assert!(format.match_magic(b"xxxx\x01\x02yy"));
+        assert!(!format.match_magic(b"TES"));
+        assert!(!format.match_magic(b"xxxx\x01"));
+    }
+
+    #[test]
+    fn test_chain_format_detector() {
+        let detector = chain_format_detector(vec![
+            default_format_detector(),
+            magic_number_format_detector(),
+        ]);
+        assert_eq!(detector.detect(Path::new("../testdata/camouflage_of_zip.rar")).unwrap().name, "Rar");
+        assert_eq!(detector.detect(Path::new("../testdata/files/excludes.txt")), None);
+
+        let detector = chain_format_detector(vec![
+            magic_number_format_detector(),
+            default_format_detector(),
+        ]);
+        assert_eq!(detector.detect(Path::new("../testdata/camouflage_of_zip.rar")).unwrap().name, "Zip");
+        assert_eq!(detector.detect(Path::new("not_exist_file.tar.xz")).unwrap().name, "TarXz");
+
+        assert_eq!(chain_format_detector(vec![]).detect(Path::new("test.zip")), None);
+    }
+
+    #[test]
+    fn test_mismatch_warning_detector() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let mismatches = Rc::new(RefCell::new(vec![]));
+        let m = Rc::clone(&mismatches);
+        let detector = mismatch_warning_detector(move |mismatch| m.borrow_mut().push(mismatch.clone()));
+
+        assert_eq!(detector.detect(Path::new("../testdata/camouflage_of_zip.rar")).unwrap().name, "Zip");
+        assert_eq!(detector.detect(Path::new("../testdata/test.zip")).unwrap().name, "Zip");
+        assert_eq!(detector.detect(Path::new("../testdata/test.tar.gz")).unwrap().name, "TarGz");
+        assert_eq!(detector.detect(Path::new("not_exist_file.rar")).unwrap().name, "Rar");
+        assert_eq!(detector.detect(Path::new("not_exist_file.unknown")), None);
+
+        let mismatches = mismatches.borrow();
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].path, PathBuf::from("../testdata/camouflage_of_zip.rar"));
+        assert_eq!(mismatches[0].by_extension.name, "Rar");
+        assert_eq!(mismatches[0].by_magic.name, "Zip");
+        assert_eq!(
+            mismatches[0].to_string(),
+            "../testdata/camouflage_of_zip.rar: the extension indicates Rar, but the content is Zip"
+        );
+    }
+
     #[test]
     fn test_fixed_format_detector() {
         let format = find_format_by_name("Zip").unwrap();
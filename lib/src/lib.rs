@@ -5,19 +5,22 @@
 pub mod archiver;
 pub mod extractor;
 pub mod format;
+pub(crate) mod codec;
 pub(crate) mod outputs;
 
 use clap::ValueEnum;
+use ignore::overrides::{Override, OverrideBuilder};
 use ignore::WalkBuilder;
 use std::collections::HashSet;
 use std::fmt::Display;
+use std::io::{Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
 
 use typed_builder::TypedBuilder;
 
 use crate::archiver::ArchiveEntries;
-use crate::extractor::Entries;
-use crate::format::{default_format_detector, FormatDetector};
+use crate::extractor::{Destination, Entries, ToteExtractor, Transform};
+use crate::format::{default_format_detector, Format, FormatDetector};
 
 /// Define the result type for this library.
 pub type Result<T> = std::result::Result<T, Error>;
@@ -39,6 +42,38 @@ pub enum IgnoreType {
     Ignore,
 }
 
+/// Define the policies for the entries whose paths escape the destination directory
+/// (absolute paths, drive prefixes, `..` components, and symbolic links pointing outside).
+#[derive(Debug, Clone, ValueEnum, PartialEq, Copy, Hash, Eq)]
+pub enum UnsafePathPolicy {
+    /// reject the entry with [`Error::UnsafePath`].
+    Reject,
+    /// skip the entry and continue the extraction.
+    Skip,
+    /// strip the unsafe components from the path, and extract the entry into the destination.
+    Strip,
+}
+
+/// Define the policies for the files which already exist in the destination directory.
+#[derive(Debug, Clone, ValueEnum, PartialEq, Copy, Hash, Eq)]
+pub enum OverwritePolicy {
+    /// keep the existing file and skip the entry.
+    Never,
+    /// overwrite the existing file.
+    Always,
+    /// overwrite the existing file only if the entry is newer than it.
+    /// The entries without the modification time (e.g., links) are skipped.
+    IfNewer,
+    /// extract the entry with the numbered suffix (e.g., `file.txt.1`) and keep the existing file.
+    Rename,
+    /// ask [`ExtractConfig::overwrite_prompt`] whether to overwrite the existing file.
+    /// The entry is skipped if the prompt is not given.
+    Ask,
+}
+
+/// The callback for [`OverwritePolicy::Ask`], see [`ExtractConfig::overwrite_prompt`].
+pub type OverwritePrompt = Box<dyn Fn(&Path) -> bool>;
+
 /// Errors that can occur when using this library.
 ///
 /// This enum represents all possible errors that can be returned
@@ -61,6 +96,8 @@ pub enum Error {
     FileNotFound(PathBuf),
     /// The file already exists when it shouldn't be overwritten
     FileExists(PathBuf),
+    /// The format with the same name is already registered
+    FormatExists(String),
     /// Standard I/O error
     IO(std::io::Error),
     /// JSON serialization/deserialization error
@@ -73,6 +110,21 @@ pub enum Error {
     UnknownFormat(String),
     /// The format is recognized but not supported for the operation
     UnsupportedFormat(String),
+    /// The entry in the archive file points to the outside of the destination directory
+    UnsafePath(String),
+    /// The extraction exceeds the limits in [`ExtractConfig`]
+    LimitExceeded(String),
+    /// The archive file is truncated or corrupted
+    Corrupted {
+        /// the path of the archive file
+        archive: PathBuf,
+        /// the name of the entry being processed, if known
+        entry: Option<String>,
+        /// the description of the error
+        message: String,
+    },
+    /// The entry specified by [`ExtractConfig::entries`] is not found in the archive file
+    EntryNotFound(PathBuf, String),
     /// XML serialization/deserialization error
     Xml(serde_xml_rs::Error),
 }
@@ -88,18 +140,24 @@ impl Display for Error {
                     .join("\n")
                     .fmt(f)
             },
-            Error::DestIsDir(p) => write!(f, "{}: Destination is a directory", p.to_str().unwrap()),
-            Error::DirExists(p) => write!(f, "{}: Directory already exists", p.to_str().unwrap()),
+            Error::DestIsDir(p) => write!(f, "{}: Destination is a directory", p.display()),
+            Error::DirExists(p) => write!(f, "{}: Directory already exists", p.display()),
             Error::Extractor(s) => write!(f, "Extractor error: {s}"),
             Error::Fatal(e) => write!(f, "Error: {e}"),
-            Error::FileNotFound(p) => write!(f, "{}: File not found", p.to_str().unwrap()),
-            Error::FileExists(p) => write!(f, "{}: File already exists", p.to_str().unwrap()),
+            Error::FileNotFound(p) => write!(f, "{}: File not found", p.display()),
+            Error::FileExists(p) => write!(f, "{}: File already exists", p.display()),
+            Error::FormatExists(s) => write!(f, "{s}: Format already registered"),
             Error::IO(e) => write!(f, "IO error: {e}"),
             Error::Json(e) => write!(f, "Json error: {e}"),
             Error::NoArgumentsGiven => write!(f, "No arguments given. Use --help for usage."),
             Error::Warn(s) => write!(f, "Unknown error: {s}"),
             Error::UnknownFormat(s) => write!(f, "{s}: Unknown format"),
             Error::UnsupportedFormat(s) => write!(f, "{s}: Unsupported format"),
+            Error::UnsafePath(s) => write!(f, "{s}: Unsafe path in the archive"),
+            Error::LimitExceeded(s) => write!(f, "Limit exceeded: {s}"),
+            Error::Corrupted { archive, entry: Some(entry), message } => write!(f, "{}: {entry}: Corrupted archive ({message})", archive.display()),
+            Error::Corrupted { archive, entry: None, message } => write!(f, "{}: Corrupted archive ({message})", archive.display()),
+            Error::EntryNotFound(archive, entry) => write!(f, "{}: {entry}: Entry not found in the archive", archive.display()),
             Error::Xml(e) => write!(f, "Xml error: {e}"),
         }
     }
@@ -130,6 +188,15 @@ impl Error {
         }
     }
 
+    /// Creates [`Error::Corrupted`] from the given archive file, entry name, and the cause.
+    pub fn corrupted<P: AsRef<Path>, D: Display>(archive: P, entry: Option<&str>, cause: D) -> Self {
+        Error::Corrupted {
+            archive: archive.as_ref().to_path_buf(),
+            entry: entry.map(str::to_string),
+            message: cause.to_string(),
+        }
+    }
+
     /// Returns `Ok(ok())` if there are no errors, otherwise returns an appropriate error.
     /// see [`Error::error_or`] for details of error handling.
     pub fn error_or_else<F, O>(ok: F, errs: Vec<Self>) -> Result<O>
@@ -169,9 +236,101 @@ impl Error {
 /// ```
 pub fn extract<P: AsRef<Path>>(archive_file: P, config: &ExtractConfig) -> Result<()> {
     let archive_file = archive_file.as_ref();
-    let base_dir = config.dest(archive_file)?;
     let extractor = config.extractor(archive_file)?;
-    extractor.perform(archive_file.to_path_buf(), base_dir)
+    let base_dir = config.dest(archive_file, extractor.as_ref())?;
+    let mut dest = Destination::new(base_dir, config).with_archive_file(archive_file);
+    extractor.perform(archive_file.to_path_buf(), &mut dest)?;
+    let errs = dest.unmatched_entries().into_iter()
+        .map(|name| Error::EntryNotFound(archive_file.to_path_buf(), name.display().to_string()))
+        .collect();
+    Error::error_or((), errs)
+}
+
+/// Extract the archive read from the given reader (e.g., the in-memory buffer) to the specified destination directory.
+///
+/// The format of the archive cannot be detected from the reader, therefore, it must be given.
+/// All formats except `rar` are available, and `rar` returns [`Error::UnsupportedFormat`].
+/// Since the reader has no file name, the archive name directory options of [`ExtractConfig`] are ignored.
+///
+/// # Arguments
+///
+/// * `reader` - The reader of the archive
+/// * `format` - The format of the archive
+/// * `config` - The extraction configuration
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+/// use totebag::{extract_from, ExtractConfig};
+/// use totebag::format::find_format_by_name;
+///
+/// let blob = std::fs::read("../testdata/test.zip").unwrap();
+/// let config = ExtractConfig::builder()
+///     .dest("results/blob")
+///     .overwrite(true)
+///     .build();
+/// match extract_from(Cursor::new(blob), find_format_by_name("Zip").unwrap(), &config) {
+///     Ok(_) => println!("Extraction successful"),
+///     Err(e) => eprintln!("Error: {:?}", e),
+/// }
+/// ```
+pub fn extract_from<R: Read + Seek>(mut reader: R, format: &Format, config: &ExtractConfig) -> Result<()> {
+    let archive_file = Path::new("-");
+    let extractor = crate::extractor::create_with(archive_file, Some(format))?;
+    let base_dir = config.check_dest(config.dest.clone())?;
+    let size = reader.seek(SeekFrom::End(0))
+        .and_then(|size| reader.rewind().map(|_| size))
+        .map_err(Error::IO)?;
+    let mut dest = Destination::new(base_dir, config)
+        .with_archive_file(archive_file)
+        .with_archive_size(size);
+    extractor.perform_from(Box::new(reader), &mut dest)?;
+    let errs = dest.unmatched_entries().into_iter()
+        .map(|name| Error::EntryNotFound(archive_file.to_path_buf(), name.display().to_string()))
+        .collect();
+    Error::error_or((), errs)
+}
+
+/// Extract the archive read from the given stream (e.g., stdin) to the specified destination directory.
+///
+/// The format of the archive cannot be detected from the stream, therefore, it must be given.
+/// The streaming extraction is available for `ar`, `cpio`, `tar`, `tar.gz`, `tar.bz2`, `tar.xz`, `tar.zst`, `tar.lz4`,
+/// `tar.lzma`, `tar.lz`, `tar.Z`, `tar.br`, `deb`, `rpm`, `zip`, `gz`, `bz2`, `xz`, `zst`, and `lz4`, and the other formats return [`Error::UnsupportedFormat`].
+/// Since the stream has no file name, the archive name directory options of [`ExtractConfig`] are ignored.
+///
+/// # Arguments
+///
+/// * `reader` - The stream of the archive
+/// * `format` - The format of the archive
+/// * `config` - The extraction configuration
+///
+/// # Examples
+///
+/// ```
+/// use totebag::{extract_stream, ExtractConfig};
+/// use totebag::format::find_format_by_name;
+///
+/// let config = ExtractConfig::builder()
+///     .dest("results/stream")
+///     .overwrite(true)
+///     .build();
+/// let file = std::fs::File::open("../testdata/test.tar.gz").unwrap();
+/// match extract_stream(file, find_format_by_name("TarGz").unwrap(), &config) {
+///     Ok(_) => println!("Extraction successful"),
+///     Err(e) => eprintln!("Error: {:?}", e),
+/// }
+/// ```
+pub fn extract_stream<R: Read>(reader: R, format: &Format, config: &ExtractConfig) -> Result<()> {
+    let archive_file = Path::new("-");
+    let extractor = crate::extractor::create_with(archive_file, Some(format))?;
+    let base_dir = config.check_dest(config.dest.clone())?;
+    let (mut dest, reader) = Destination::new(base_dir, config).with_stream(reader);
+    extractor.perform_stream(Box::new(reader), &mut dest)?;
+    let errs = dest.unmatched_entries().into_iter()
+        .map(|name| Error::EntryNotFound(archive_file.to_path_buf(), name.display().to_string()))
+        .collect();
+    Error::error_or((), errs)
 }
 
 /// Configuration for extracting archive files.
@@ -196,24 +355,101 @@ pub struct ExtractConfig {
     /// The destination directory for extraction.
     #[builder(setter(into), default = PathBuf::from("."))]
     pub dest: PathBuf,
-    /// Overwrite flag, if `true`, overwrite the files.
+    /// Overwrite flag, if `true`, extract the files into the existing destination directory.
     #[builder(default = false)]
     pub overwrite: bool,
+    /// The policy for each extracted file which already exists in the destination directory.
+    #[builder(default = OverwritePolicy::Always)]
+    pub overwrite_policy: OverwritePolicy,
+    /// The callback for [`OverwritePolicy::Ask`], which receives the path of the existing file
+    /// and returns `true` to overwrite it.
+    #[builder(default = None, setter(strip_option))]
+    pub overwrite_prompt: Option<OverwritePrompt>,
     /// If `true`, the destination path becomes `{dest}/{archive_file.file_stem()}`.
     #[builder(default = false)]
     pub use_archive_name_dir: bool,
+    /// If `true`, the destination path becomes `{dest}/{archive_file.file_stem()}` only when
+    /// the archive file has multiple top-level entries, otherwise the single root is extracted into `{dest}`.
+    /// This flag takes precedence over `use_archive_name_dir`.
+    #[builder(default = false)]
+    pub auto_archive_name_dir: bool,
+    /// The policy for the entries whose paths escape the destination directory.
+    #[builder(default = UnsafePathPolicy::Reject)]
+    pub unsafe_path: UnsafePathPolicy,
+    /// The maximum total bytes of the extracted files. `None` means no limit.
+    #[builder(default = None, setter(strip_option))]
+    pub max_total_size: Option<u64>,
+    /// The maximum bytes of each extracted file. `None` means no limit.
+    #[builder(default = None, setter(strip_option))]
+    pub max_entry_size: Option<u64>,
+    /// The maximum number of the extracted files. `None` means no limit.
+    #[builder(default = None, setter(strip_option))]
+    pub max_entries: Option<usize>,
+    /// The maximum ratio of the total extracted bytes to the archive file size, or to the bytes read so far
+    /// from the stream given to [`extract_stream`]. `None` means no limit.
+    #[builder(default = None, setter(strip_option))]
+    pub max_ratio: Option<f64>,
+    /// The names of the entries to be extracted. Naming a directory extracts its contents.
+    /// If both of this and `includes` are empty, all entries are extracted.
+    #[builder(default, setter(into))]
+    pub entries: Vec<String>,
+    /// The glob patterns (gitignore syntax) of the entries to be extracted in addition to `entries`.
+    #[builder(default, setter(into))]
+    pub includes: Vec<String>,
+    /// The glob patterns (gitignore syntax) of the entries not to be extracted.
+    /// The excludes take precedence over `entries` and `includes`.
+    #[builder(default, setter(into))]
+    pub excludes: Vec<String>,
+    /// The number of the leading path components removed from each entry name, like `tar --strip-components`.
+    /// The entries having no more components are not extracted.
+    #[builder(default = 0)]
+    pub strip_components: usize,
+    /// The sed-style substitutions applied to each entry name in order after `strip_components`.
+    /// The entries whose names become empty are not extracted.
+    #[builder(default, setter(into))]
+    pub transforms: Vec<Transform>,
+    /// If `true`, restore the modification times of the extracted files.
+    #[builder(default = true)]
+    pub preserve_mtime: bool,
+    /// If `true`, restore the permission bits of the extracted files.
+    #[builder(default = true)]
+    pub preserve_permissions: bool,
+    /// If `true`, drop the setuid and setgid bits on restoring the permission bits.
+    #[builder(default = true)]
+    pub strip_setuid: bool,
+    /// If `true`, restore the owner and the group of the extracted files.
+    /// This option takes effect only when running as root on unix platforms.
+    #[builder(default = false)]
+    pub preserve_ownership: bool,
+    /// If `true`, extract the control files of the packages into `DEBIAN/` in addition to the data files,
+    /// like `dpkg-deb --raw-extract`. Only `deb` has the control files.
+    #[builder(default = false)]
+    pub package_control: bool,
     /// The format detector to use for determining archive format.
     #[builder(default = default_format_detector())]
     pub format_detector: Box<dyn FormatDetector>,
 }
 
 impl ExtractConfig {
+    /// Returns `true` if the entries should be extracted into the directory named after the archive file.
+    /// If `auto_archive_name_dir` is `true`, the entries in the archive file are listed by the given extractor,
+    /// and their top-level names are counted after the selection and the rewriting of the entry names.
+    pub(crate) fn uses_archive_name_dir(&self, archive_file: &Path, extractor: &dyn ToteExtractor) -> Result<bool> {
+        if self.auto_archive_name_dir {
+            let entries = extractor.list(archive_file.to_path_buf())?;
+            let dest = extractor::Destination::new(&self.dest, self);
+            Ok(dest.roots(&entries).len() > 1)
+        } else {
+            Ok(self.use_archive_name_dir)
+        }
+    }
+
     /// Determines the destination path for extraction based on configuration.
     ///
     /// This internal method calculates the final destination path,
-    /// taking into account the `use_archive_name_dir` flag.
-    pub(crate) fn dest(&self, archive_file: &Path) -> Result<PathBuf> {
-        let dest = if self.use_archive_name_dir {
+    /// taking into account the `use_archive_name_dir` and `auto_archive_name_dir` flags.
+    pub(crate) fn dest(&self, archive_file: &Path, extractor: &dyn ToteExtractor) -> Result<PathBuf> {
+        let dest = if self.uses_archive_name_dir(archive_file, extractor)? {
             let stem = archive_file
                 .file_stem()
                 .unwrap_or_else(|| std::ffi::OsStr::new("archive"));
@@ -221,8 +457,13 @@ impl ExtractConfig {
         } else {
             self.dest.clone()
         };
+        self.check_dest(dest)
+    }
+
+    /// Returns the given destination directory, or [`Error::DirExists`] if it exists and overwriting is not allowed.
+    fn check_dest(&self, dest: PathBuf) -> Result<PathBuf> {
         if dest.exists() && !self.overwrite {
-            if dest == PathBuf::from(".") || dest == PathBuf::from("..") {
+            if dest == Path::new(".") || dest == Path::new("..") {
                 Ok(dest)
             } else {
                 Err(Error::DirExists(dest))
@@ -280,6 +521,26 @@ pub fn entries<P: AsRef<Path>>(archive_file: P, format_detector: &dyn FormatDete
     extractor.list(archive_file.to_path_buf())
 }
 
+/// Returns the entries (file list) in the archive read from the given reader (e.g., the in-memory buffer).
+/// The resultant [`Entries`] has `-` as its path.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+/// use totebag::entries_from;
+/// use totebag::format::find_format_by_name;
+///
+/// let blob = std::fs::read("../testdata/test.tar.gz").unwrap();
+/// let entries = entries_from(Cursor::new(blob), find_format_by_name("TarGz").unwrap()).unwrap();
+/// assert!(entries.iter().any(|e| e.name == "Cargo.toml"));
+/// ```
+pub fn entries_from<R: Read + Seek>(reader: R, format: &Format) -> Result<Entries> {
+    let archive_file = Path::new("-");
+    let extractor = crate::extractor::create_with(archive_file, Some(format))?;
+    extractor.list_from(Box::new(reader), archive_file.to_path_buf())
+}
+
 /// Returns a formatted string representation of the entries in the given archive file.
 ///
 /// # Arguments
@@ -384,7 +645,7 @@ pub enum OutputFormat {
 ///     // .no_recursive(false) // Default is false.
 ///     .build();
 /// let targets = vec!["src", "Cargo.toml"].iter() // files to be archived.
-///    .map(|s| PathBuf::from(s)).collect::<Vec<PathBuf>>();
+///    .map(PathBuf::from).collect::<Vec<PathBuf>>();
 /// archive(&targets, &config)
 ///     .expect("Archiving should succeed");
 /// ``` 
@@ -393,18 +654,20 @@ pub fn archive<P: AsRef<Path>>(
     config: &ArchiveConfig,
 ) -> Result<ArchiveEntries> {
     let dest_file = config.dest_file()?;
+    for target in archive_targets {
+        config.filter(target)?;
+    }
     log::info!("{:?}: {}", dest_file, dest_file.exists());
     let archiver = archiver::create(&dest_file)?;
-    if let Some(parent) = dest_file.parent() {
-        if !parent.exists() {
-            if let Err(e) = std::fs::create_dir_all(parent) {
-                return Err(Error::IO(e));
-            }
-        }
+    if let Some(parent) = dest_file.parent()
+        && !parent.exists()
+        && let Err(e) = std::fs::create_dir_all(parent)
+    {
+        return Err(Error::IO(e));
     }
     let targets = prepare_targets(archive_targets);
     match std::fs::File::create(&dest_file) {
-        Ok(file) => match archiver.perform(file, &targets, config) {
+        Ok(file) => match archiver.perform(Box::new(file), &targets, config) {
             Ok(entries) => {
                 let compressed = dest_file.metadata().map(|m| m.len()).unwrap_or(0);
                 Ok(ArchiveEntries::new(dest_file, entries, compressed))
@@ -415,6 +678,91 @@ pub fn archive<P: AsRef<Path>>(
     }
 }
 
+/// Write an archive of the specified targets into the given writer (e.g., the in-memory buffer).
+///
+/// All formats except `iso`, `rar`, `rpm`, and `squashfs` are available. [`ArchiveConfig::dest`] is ignored,
+/// and the resultant [`ArchiveEntries`] has `-` as its archive file.
+/// Pass `&mut writer` to use the writer after archiving.
+///
+/// # Arguments
+///
+/// * `writer` - The writer of the archive
+/// * `format` - The format of the archive
+/// * `archive_targets` - A slice of paths to files or directories to archive
+/// * `config` - The archive configuration
+///
+/// # Examples
+/// ```
+/// use std::io::Cursor;
+/// use std::path::PathBuf;
+/// use totebag::{archive_to, ArchiveConfig};
+/// use totebag::format::find_format_by_name;
+/// let config = ArchiveConfig::builder().build();
+/// let mut buffer = Cursor::new(Vec::new());
+/// let entries = archive_to(&mut buffer, find_format_by_name("Zip").unwrap(), &[PathBuf::from("src")], &config)
+///     .expect("Archiving should succeed");
+/// assert_eq!(entries.compressed, buffer.get_ref().len() as u64);
+/// ```
+pub fn archive_to<W: Write + Seek, P: AsRef<Path>>(
+    mut writer: W,
+    format: &Format,
+    archive_targets: &[P],
+    config: &ArchiveConfig,
+) -> Result<ArchiveEntries> {
+    for target in archive_targets {
+        config.filter(target)?;
+    }
+    let archiver = archiver::create_with(format)?;
+    let targets = prepare_targets(archive_targets);
+    let start = writer.stream_position().map_err(Error::IO)?;
+    let entries = archiver.perform(Box::new(&mut writer), &targets, config)?;
+    // some formats (e.g., 7z) rewind the writer to update the header at last.
+    let end = writer.seek(SeekFrom::End(0)).map_err(Error::IO)?;
+    Ok(ArchiveEntries::new("-", entries, end.saturating_sub(start)))
+}
+
+/// Write an archive of the specified targets into the given stream (e.g., stdout).
+///
+/// The streaming archiving is available for `ar`, `cpio`, `tar`, `tar.gz`, `tar.bz2`, `tar.xz`, `tar.zst`, `tar.lz4`,
+/// `tar.lzma`, `tar.lz`, `tar.Z`, `tar.br`, `deb`, `zip` (with the data descriptors), `gz`, `bz2`, `xz`, `zst`, and `lz4`, and the other formats return [`Error::UnsupportedFormat`].
+/// [`ArchiveConfig::dest`] is ignored, and the resultant [`ArchiveEntries`] has `-` as its archive file
+/// and `0` as its compressed size.
+///
+/// # Arguments
+///
+/// * `writer` - The stream to write the archive
+/// * `format` - The format of the archive
+/// * `archive_targets` - A slice of paths to files or directories to archive
+/// * `config` - The archive configuration
+///
+/// # Examples
+/// ```
+/// use totebag::{archive_stream, ArchiveConfig};
+/// use totebag::format::find_format_by_name;
+/// use std::path::PathBuf;
+/// let config = ArchiveConfig::builder().build();
+/// let targets = vec![PathBuf::from("src")];
+/// let writer = std::io::stdout(); // the archive is written into stdout.
+/// # let writer = Vec::new();
+/// archive_stream(writer, find_format_by_name("TarGz").unwrap(), &targets, &config)
+///     .expect("Archiving should succeed");
+/// ```
+pub fn archive_stream<W: Write, P: AsRef<Path>>(
+    mut writer: W,
+    format: &Format,
+    archive_targets: &[P],
+    config: &ArchiveConfig,
+) -> Result<ArchiveEntries> {
+    for target in archive_targets {
+        config.filter(target)?;
+    }
+    let archiver = archiver::create_with(format)?;
+    let targets = prepare_targets(archive_targets);
+    let entries = archiver.perform_stream(Box::new(&mut writer), &targets, config)?;
+    writer.flush().map_err(Error::IO)?;
+    Ok(ArchiveEntries::new("-", entries, 0))
+}
+
 fn prepare_targets<P: AsRef<Path>>(targets: &[P]) -> Vec<PathBuf> {
     targets
         .iter()
@@ -440,6 +788,7 @@ fn prepare_targets<P: AsRef<Path>>(targets: &[P]) -> Vec<PathBuf> {
 ///     .overwrite(true)
 ///     .no_recursive(false)
 ///     .ignore(vec![IgnoreType::GitIgnore, IgnoreType::Hidden])
+///     .excludes(vec!["target/**".to_string()])
 ///     .build();
 /// ```
 #[derive(TypedBuilder, Debug, Clone)]
@@ -461,13 +810,34 @@ pub struct ArchiveConfig {
     pub overwrite: bool,
 
     /// By default (`false`), read files by traversing the each `targets`.
-    /// If `true`, it archives the specified files in `targets`.
+    /// If `true`, it archives only the specified files and directories (without their contents) in `targets`.
     #[builder(default = false)]
     pub no_recursive: bool,
 
+    /// The maximum depth for traversing the each `targets` (`0` means the `targets` themselves).
+    /// `None` means no limit. This option is ignored when `no_recursive` is `true`.
+    #[builder(default = None, setter(strip_option(fallback = max_depth_opt)))]
+    pub max_depth: Option<usize>,
+
     /// specifies the ignore types for traversing.
     #[builder(default = vec![IgnoreType::Default], setter(into))]
     pub ignore: Vec<IgnoreType>,
+
+    /// The glob patterns of the files to be archived (gitignore syntax, relative to the each `targets`).
+    /// If not empty, the files not matching any of them are skipped (directories are still traversed).
+    #[builder(default, setter(into))]
+    pub includes: Vec<String>,
+
+    /// The glob patterns of the files and directories to be skipped (gitignore syntax, relative to the each `targets`).
+    /// The excludes take precedence over the `includes`.
+    #[builder(default, setter(into))]
+    pub excludes: Vec<String>,
+
+    /// By default (`false`), the symbolic links and the hard links are stored as links
+    /// if the archive format supports them.
+    /// If `true`, the links are followed and the linked contents are stored.
+    #[builder(default = false)]
+    pub follow_links: bool,
 }
 
 impl ArchiveConfig {
@@ -527,8 +897,8 @@ impl ArchiveConfig {
     ///
     /// An iterator over directory entries.
     pub fn iter<P: AsRef<Path>>(&self, path: P) -> impl Iterator<Item = ignore::DirEntry> {
-        let mut builder = WalkBuilder::new(path);
-        build_walker_impl(self, &mut builder);
+        let mut builder = WalkBuilder::new(path.as_ref());
+        build_walker_impl(self, path.as_ref(), &mut builder);
         builder.build().flatten()
     }
 
@@ -563,9 +933,33 @@ impl ArchiveConfig {
             r.into_iter().collect()
         }
     }
+
+    /// Builds the matcher from `includes` and `excludes` for the files under the given root.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Error::Archiver` if any pattern is not a valid glob.
+    pub fn filter<P: AsRef<Path>>(&self, root: P) -> Result<Override> {
+        let mut builder = OverrideBuilder::new(root);
+        let patterns = self.includes.iter().map(|p| (p, p.to_string()))
+            .chain(self.excludes.iter().map(|p| (p, format!("!{p}"))));
+        for (original, pattern) in patterns {
+            if builder.add(&pattern).is_err() {
+                return Err(Error::Archiver(format!("{original}: invalid glob pattern")));
+            }
+        }
+        builder.build().map_err(|e| Error::Archiver(e.to_string()))
+    }
 }
 
-fn build_walker_impl(opts: &ArchiveConfig, w: &mut WalkBuilder) {
+fn build_walker_impl(opts: &ArchiveConfig, root: &Path, w: &mut WalkBuilder) {
+    let max_depth = if opts.no_recursive {
+        Some(0)
+    } else {
+        opts.max_depth
+    };
+    w.follow_links(opts.follow_links)
+        .max_depth(max_depth);
     for it in opts.ignore_types() {
         match it {
             IgnoreType::Default => w
@@ -580,6 +974,18 @@ fn build_walker_impl(opts: &ArchiveConfig, w: &mut WalkBuilder) {
             IgnoreType::Ignore => w.ignore(true),
         };
     }
+    // the filters are applied in addition to the ignore types (not as the overrides of the walker,
+    // which take precedence over the ignore files).
+    match opts.filter(root) {
+        Ok(filter) if !filter.is_empty() => {
+            w.filter_entry(move |e| {
+                let is_dir = e.file_type().is_some_and(|t| t.is_dir());
+                !filter.matched(e.path(), is_dir).is_ignore()
+            });
+        }
+        Ok(_) => {}
+        Err(e) => log::warn!("{e}"),
+    };
 }
 
 mod tests {
@@ -606,7 +1012,7 @@ mod tests {
             "hoge: Directory already exists"
         );
         assert_eq!(
-            Error::Fatal(Box::new(std::io::Error::new(std::io::ErrorKind::Other, "hoge"))).to_string(),
+            Error::Fatal(Box::new(std::io::Error::other("hoge"))).to_string(),
             "Error: hoge"
         );        
         assert_eq!(
@@ -629,6 +1035,10 @@ mod tests {
             Error::FileExists("hoge".into()).to_string(),
             "hoge: File already exists"
         );
+        assert_eq!(
+            Error::FormatExists("hoge".into()).to_string(),
+            "hoge: Format already registered"
+        );
         assert_eq!(
             Error::UnknownFormat("hoge".to_string()).to_string(),
             "hoge: Unknown format"
@@ -637,6 +1047,26 @@ mod tests {
             Error::UnsupportedFormat("hoge".to_string()).to_string(),
             "hoge: Unsupported format"
         );
+        assert_eq!(
+            Error::UnsafePath("../hoge".to_string()).to_string(),
+            "../hoge: Unsafe path in the archive"
+        );
+        assert_eq!(
+            Error::LimitExceeded("hoge".to_string()).to_string(),
+            "Limit exceeded: hoge"
+        );
+        assert_eq!(
+            Error::corrupted("hoge.zip", Some("fuga.txt"), "invalid header").to_string(),
+            "hoge.zip: fuga.txt: Corrupted archive (invalid header)"
+        );
+        assert_eq!(
+            Error::corrupted("hoge.zip", None, "invalid header").to_string(),
+            "hoge.zip: Corrupted archive (invalid header)"
+        );
+        assert_eq!(
+            Error::EntryNotFound(PathBuf::from("hoge.zip"), "fuga.txt".to_string()).to_string(),
+            "hoge.zip: fuga.txt: Entry not found in the archive"
+        );
         assert_eq!(
             Error::Warn("message".to_string()).to_string(),
             "Unknown error: message"
@@ -25,6 +25,7 @@ pub fn to_string(entries: &Entries) -> Result<String> {
 /// Convert entries to a detailed long format string.
 ///
 /// Each line includes permissions, compressed/original sizes, date, and filename.
+/// The properties of the archive file (e.g., the package name of `.rpm`) precede them as `name: value` lines.
 ///
 /// # Arguments
 ///
@@ -35,8 +36,10 @@ pub fn to_string(entries: &Entries) -> Result<String> {
 /// Returns a formatted string with detailed information for each entry.
 pub fn to_string_long(entries: &Entries) -> Result<String> {
     Ok(entries
+        .properties
         .iter()
-        .map(to_long_format)
+        .map(|p| format!("{}: {}", p.name, p.value))
+        .chain(entries.iter().map(to_long_format))
         .collect::<Vec<String>>()
         .join("\n"))
 }